@@ -0,0 +1,167 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use tokio::time::{Duration, sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::activitywatch::client::{AwClient, Event};
+use crate::capture::CaptureState;
+use crate::config::IdleConfig;
+
+/// afkstatus 事件结束超过该时长即视为 aw-watcher-afk 已停止,按非 AFK 处理
+const STALE_AFTER_SECS: i64 = 120;
+
+/// 轮询 aw-server 的 afkstatus bucket,并把 AFK 状态同步到 `CaptureState`
+pub struct AfkWatcher {
+    client: AwClient,
+    config: IdleConfig,
+}
+
+impl AfkWatcher {
+    pub fn new(client: AwClient, config: IdleConfig) -> Self {
+        Self { client, config }
+    }
+
+    /// 持续轮询直到收到取消信号
+    ///
+    /// 查询失败时视为非 AFK,避免 aw-server 不可用时停止截图
+    pub async fn run(self, state: CaptureState, cancel_token: CancellationToken) {
+        info!("AFK watcher started: {}", self.config);
+        let mut bucket = (!self.config.bucket.is_empty()).then(|| self.config.bucket.clone());
+        let mut last_error: Option<String> = None;
+
+        loop {
+            match self.query_afk(&mut bucket).await {
+                Ok(afk) => {
+                    if last_error.take().is_some() {
+                        info!("AFK status available again");
+                    }
+                    state.set_afk(afk);
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    if last_error.as_deref() != Some(message.as_str()) {
+                        warn!("Failed to query AFK status: {}", message);
+                    }
+                    last_error = Some(message);
+                    state.set_afk(false);
+                }
+            }
+
+            tokio::select! {
+                _ = sleep(Duration::from_millis(self.config.poll_interval)) => {}
+                _ = cancel_token.cancelled() => {
+                    info!("AFK watcher received cancellation signal");
+                    break;
+                }
+            }
+        }
+
+        state.set_afk(false);
+        info!("AFK watcher terminated");
+    }
+
+    /// 查询一次 AFK 状态,必要时自动查找 afkstatus bucket
+    async fn query_afk(&self, bucket: &mut Option<String>) -> Result<bool> {
+        let bucket_id = match bucket {
+            Some(id) => id.clone(),
+            None => {
+                let found = self
+                    .client
+                    .find_bucket("afkstatus")
+                    .await?
+                    .ok_or_else(|| anyhow!("No afkstatus bucket found on aw-server"))?;
+                info!("Using AFK bucket {}", found.id);
+                bucket.insert(found.id).clone()
+            }
+        };
+
+        let events = self.client.events(&bucket_id, Some(1), None, None).await?;
+        let afk = events
+            .first()
+            .map(|event| is_afk(event, Utc::now()))
+            .unwrap_or(false);
+        debug!("AFK status from {}: {}", bucket_id, afk);
+        Ok(afk)
+    }
+}
+
+/// 根据最新的 afkstatus 事件判断是否 AFK
+fn is_afk(event: &Event, now: DateTime<Utc>) -> bool {
+    if now - event.end() > chrono::Duration::seconds(STALE_AFTER_SECS) {
+        return false;
+    }
+    event.data.get("status").and_then(|s| s.as_str()) == Some("afk")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activitywatch::stand_in::StandInServer;
+    use std::sync::{Arc, Mutex};
+
+    fn afk_event(status: &str, timestamp: DateTime<Utc>, duration: f64) -> Event {
+        let mut data = serde_json::Map::new();
+        data.insert("status".to_string(), status.into());
+        Event {
+            id: None,
+            timestamp,
+            duration,
+            data,
+        }
+    }
+
+    #[test]
+    fn test_is_afk() {
+        let now = Utc::now();
+        let recent = now - chrono::Duration::seconds(600);
+        assert!(is_afk(&afk_event("afk", recent, 590.0), now));
+        assert!(!is_afk(&afk_event("not-afk", recent, 590.0), now));
+        // aw-watcher-afk 已停止上报
+        assert!(!is_afk(&afk_event("afk", recent, 60.0), now));
+    }
+
+    #[tokio::test]
+    async fn test_watcher_follows_server_status() {
+        let status = Arc::new(Mutex::new("afk"));
+        let server_status = status.clone();
+        let server = StandInServer::spawn(move |request| match request.path.as_str() {
+            "/api/0/buckets/" => (
+                200,
+                r#"{"aw-watcher-afk_test": {"id": "aw-watcher-afk_test", "type": "afkstatus"}}"#
+                    .to_string(),
+            ),
+            "/api/0/buckets/aw-watcher-afk_test/events" => {
+                let event = afk_event(
+                    *server_status.lock().unwrap(),
+                    Utc::now() - chrono::Duration::seconds(10),
+                    10.0,
+                );
+                (200, serde_json::to_string(&vec![event]).unwrap())
+            }
+            _ => (404, "{}".to_string()),
+        })
+        .await;
+
+        let config = IdleConfig {
+            enable: true,
+            poll_interval: 20,
+            ..IdleConfig::default()
+        };
+        let watcher = AfkWatcher::new(AwClient::new(server.url()).unwrap(), config);
+        let state = CaptureState::new();
+        let mut afk_rx = state.subscribe_afk();
+        let cancel_token = CancellationToken::new();
+        let handle = tokio::spawn(watcher.run(state.clone(), cancel_token.clone()));
+
+        afk_rx.wait_for(|afk| *afk).await.unwrap();
+        assert!(state.is_afk());
+
+        *status.lock().unwrap() = "not-afk";
+        afk_rx.wait_for(|afk| !*afk).await.unwrap();
+        assert!(!state.is_afk());
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::activitywatch::client::{AwClient, Event};
+use crate::config::ActivityWatchConfig;
+use crate::event::ActivityContext;
+
+/// 重新查找 bucket 与分类规则的间隔,期间新启动的 watcher 不会被发现
+const SOURCES_TTL: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// aw-watcher-web 的 bucket 类型
+const WEB_BUCKET_TYPE: &str = "web.tab.current";
+
+/// 查询所用的 bucket 与分类规则
+#[derive(Debug, Default)]
+struct Sources {
+    window: Option<String>,
+    afk: Option<String>,
+    web: Option<String>,
+    /// 转换为 `categorize` 参数格式的分类规则
+    classes: Option<Value>,
+}
+
+impl Sources {
+    fn is_empty(&self) -> bool {
+        self.window.is_none() && self.afk.is_none() && self.web.is_none()
+    }
+
+    /// 一次查询取回所有 bucket 中与截图时间重叠的事件
+    fn statements(&self) -> Vec<String> {
+        let mut statements = Vec::new();
+        let mut returned = Vec::new();
+        for (name, bucket) in [
+            ("window", &self.window),
+            ("afk", &self.afk),
+            ("web", &self.web),
+        ] {
+            if let Some(bucket) = bucket {
+                statements.push(format!("{} = query_bucket({});", name, json!(bucket)));
+                returned.push(format!("\"{}\": {}", name, name));
+            }
+        }
+        if let (Some(_), Some(classes)) = (&self.window, &self.classes) {
+            statements.push(format!("window = categorize(window, {});", classes));
+        }
+        statements.push(format!("RETURN = {{{}}};", returned.join(", ")));
+        statements
+    }
+}
+
+/// 为截图标注 ActivityWatch 记录的活动
+///
+/// 每张截图最多一次查询:bucket 与分类规则缓存 [`SOURCES_TTL`],时间相近的截图
+/// (例如同一节拍的多个显示器)复用上一次的结果
+pub struct ActivityAnnotator {
+    client: AwClient,
+    max_age: Duration,
+    cache_window: Duration,
+    sources: Option<(Instant, Sources)>,
+    cache: Option<(DateTime<Utc>, ActivityContext)>,
+    last_error: Option<String>,
+}
+
+impl ActivityAnnotator {
+    pub fn new(client: AwClient, config: &ActivityWatchConfig) -> Self {
+        Self {
+            client,
+            max_age: Duration::milliseconds(config.annotate_max_age as i64),
+            cache_window: Duration::milliseconds(config.annotate_cache as i64),
+            sources: None,
+            cache: None,
+            last_error: None,
+        }
+    }
+
+    /// 查询截图时的活动,失败时只记录日志并返回 `None`
+    pub async fn annotate(&mut self, timestamp: DateTime<Utc>) -> Option<ActivityContext> {
+        match self.context_at(timestamp).await {
+            Ok(context) => {
+                if self.last_error.take().is_some() {
+                    info!("Capture annotation available again");
+                }
+                Some(context)
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                if self.last_error.as_deref() != Some(message.as_str()) {
+                    warn!("Failed to annotate capture: {}", message);
+                }
+                self.last_error = Some(message);
+                None
+            }
+        }
+    }
+
+    async fn context_at(&mut self, timestamp: DateTime<Utc>) -> Result<ActivityContext> {
+        if let Some((at, context)) = &self.cache
+            && (timestamp - *at).abs() <= self.cache_window
+        {
+            return Ok(context.clone());
+        }
+
+        let max_age = self.max_age;
+        let sources = self.sources().await?;
+        if sources.is_empty() {
+            return Ok(ActivityContext::default());
+        }
+        let statements = sources.statements();
+        let result = self
+            .client
+            .query(&[(timestamp - max_age, timestamp)], &statements)
+            .await?
+            .into_iter()
+            .next()
+            .context("aw-server returned no query result")?;
+        let events: HashMap<String, Vec<Event>> =
+            serde_json::from_value(result).context("Unexpected query result")?;
+        let context = activity_context(&events);
+        debug!("Activity at {}: {:?}", timestamp, context);
+
+        self.cache = Some((timestamp, context.clone()));
+        Ok(context)
+    }
+
+    /// 返回缓存的 bucket 与分类规则,过期后重新查找
+    async fn sources(&mut self) -> Result<&Sources> {
+        let fresh = self
+            .sources
+            .as_ref()
+            .is_some_and(|(at, _)| at.elapsed() < SOURCES_TTL);
+        if !fresh {
+            let buckets = self.client.buckets().await?;
+            let latest = |bucket_type: &str| {
+                buckets
+                    .values()
+                    .filter(|b| b.bucket_type == bucket_type)
+                    .max_by_key(|b| b.last_updated)
+                    .map(|b| b.id.clone())
+            };
+            let sources = Sources {
+                window: latest("currentwindow"),
+                afk: latest("afkstatus"),
+                web: latest(WEB_BUCKET_TYPE),
+                // 旧版 aw-server 没有设置接口,此时不标注分类
+                classes: self
+                    .client
+                    .setting("classes")
+                    .await
+                    .inspect_err(|e| debug!("Failed to read categories: {:#}", e))
+                    .ok()
+                    .flatten()
+                    .and_then(|classes| categorize_rules(&classes)),
+            };
+            info!(
+                "Annotating captures from buckets: window {:?}, afk {:?}, web {:?}",
+                sources.window, sources.afk, sources.web
+            );
+            self.sources = Some((Instant::now(), sources));
+        }
+        Ok(&self.sources.as_ref().expect("sources were just loaded").1)
+    }
+}
+
+/// 把 aw-webui 保存的分类(`[{"name": [...], "rule": {...}}]`)转换为
+/// `categorize` 需要的 `[[name, rule], ...]`
+fn categorize_rules(classes: &Value) -> Option<Value> {
+    let rules: Vec<Value> = classes
+        .as_array()?
+        .iter()
+        .filter_map(|class| Some(json!([class.get("name")?, class.get("rule")?])))
+        .collect();
+    (!rules.is_empty()).then_some(Value::Array(rules))
+}
+
+/// 各 bucket 中结束最晚的事件即截图时正在进行的活动
+fn activity_context(events: &HashMap<String, Vec<Event>>) -> ActivityContext {
+    let latest = |name: &str| events.get(name)?.iter().max_by_key(|e| e.end());
+    let text = |event: &Event, key: &str| {
+        event
+            .data
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    let mut context = ActivityContext::default();
+    if let Some(window) = latest("window") {
+        context.app = text(window, "app");
+        context.title = text(window, "title");
+        context.category = window
+            .data
+            .get("$category")
+            .and_then(Value::as_array)
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|n| n.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+    if let Some(afk) = latest("afk") {
+        context.afk = text(afk, "status").map(|status| status == "afk");
+    }
+    // 浏览器不在前台时标签页事件仍在继续,只有标题对得上才认为正在浏览
+    if let (Some(web), Some(title)) = (latest("web"), &context.title) {
+        let tab_title = text(web, "title").unwrap_or_default();
+        if !tab_title.is_empty() && title.contains(&tab_title) {
+            context.url = text(web, "url");
+        }
+    }
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activitywatch::stand_in::StandInServer;
+
+    #[tokio::test]
+    async fn test_annotates_with_one_cached_query() {
+        let server = StandInServer::spawn(|request| match request.path.as_str() {
+            "/api/0/buckets/" => (
+                200,
+                r#"{
+                    "aw-watcher-window_desk": {"id": "aw-watcher-window_desk", "type": "currentwindow"},
+                    "aw-watcher-afk_desk": {"id": "aw-watcher-afk_desk", "type": "afkstatus"},
+                    "aw-watcher-web-firefox": {"id": "aw-watcher-web-firefox", "type": "web.tab.current"}
+                }"#
+                .to_string(),
+            ),
+            "/api/0/settings/classes" => (
+                200,
+                r#"[{"id": 1, "name": ["Work", "Programming"],
+                    "rule": {"type": "regex", "regex": "GitHub"}}]"#
+                    .to_string(),
+            ),
+            "/api/0/query/" => (
+                200,
+                r#"[{
+                    "window": [{"timestamp": "2024-06-01T11:59:00Z", "duration": 59.0,
+                        "data": {"app": "firefox", "title": "GitHub - Mozilla Firefox",
+                            "$category": ["Work", "Programming"]}}],
+                    "afk": [{"timestamp": "2024-06-01T11:00:00Z", "duration": 3590.0,
+                        "data": {"status": "not-afk"}}],
+                    "web": [{"timestamp": "2024-06-01T11:59:30Z", "duration": 29.0,
+                        "data": {"url": "https://github.com/", "title": "GitHub"}}]
+                }]"#
+                .to_string(),
+            ),
+            _ => (404, "{}".to_string()),
+        })
+        .await;
+        let mut annotator = ActivityAnnotator::new(
+            AwClient::new(server.url()).unwrap(),
+            &ActivityWatchConfig::default(),
+        );
+
+        let at: DateTime<Utc> = "2024-06-01T12:00:00Z".parse().unwrap();
+        let context = annotator.annotate(at).await.unwrap();
+        assert_eq!(
+            context,
+            ActivityContext {
+                app: Some("firefox".to_string()),
+                title: Some("GitHub - Mozilla Firefox".to_string()),
+                category: vec!["Work".to_string(), "Programming".to_string()],
+                url: Some("https://github.com/".to_string()),
+                afk: Some(false),
+            }
+        );
+        // 同一时刻的另一张截图复用查询结果
+        let cached = annotator.annotate(at + Duration::milliseconds(200)).await;
+        assert_eq!(cached, Some(context));
+
+        let requests = server.requests();
+        let queries: Vec<_> = requests
+            .iter()
+            .filter(|r| r.path == "/api/0/query/")
+            .collect();
+        assert_eq!(queries.len(), 1);
+        let body: Value = serde_json::from_str(&queries[0].body).unwrap();
+        assert_eq!(
+            body["timeperiods"][0],
+            "2024-06-01T11:59:55+00:00/2024-06-01T12:00:00+00:00"
+        );
+        let statements = body["query"].to_string();
+        assert!(statements.contains(r#"query_bucket(\"aw-watcher-web-firefox\")"#));
+        assert!(statements.contains(r#"categorize(window, [[[\"Work\",\"Programming\"]"#));
+    }
+}
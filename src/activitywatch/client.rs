@@ -0,0 +1,311 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// 创建 bucket 时上报的客户端名称
+pub const CLIENT_NAME: &str = "aw-watcher-screenshot";
+
+/// aw-server REST API 的最小客户端
+#[derive(Debug, Clone)]
+pub struct AwClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+/// `GET /api/0/info` 的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub hostname: String,
+    pub version: String,
+    #[serde(default)]
+    pub testing: bool,
+}
+
+/// `GET /api/0/buckets/` 中的单个 bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bucket {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub bucket_type: String,
+    #[serde(default)]
+    pub client: String,
+    #[serde(default)]
+    pub hostname: String,
+    pub last_updated: Option<DateTime<Utc>>,
+}
+
+/// ActivityWatch 事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub timestamp: DateTime<Utc>,
+    /// 持续时间(秒)
+    pub duration: f64,
+    pub data: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Event {
+    /// 事件结束时间,按毫秒取整
+    pub fn end(&self) -> DateTime<Utc> {
+        self.timestamp + chrono::Duration::milliseconds((self.duration * 1000.0).round() as i64)
+    }
+}
+
+impl AwClient {
+    /// `base_url` 形如 `http://localhost:5600`
+    pub fn new(base_url: impl Into<String>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/0/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    /// 获取服务端信息,可用于检查连通性
+    pub async fn info(&self) -> Result<ServerInfo> {
+        let info = self
+            .http
+            .get(self.url("info"))
+            .send()
+            .await
+            .context("Failed to connect to aw-server")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Failed to parse aw-server info")?;
+        Ok(info)
+    }
+
+    /// 获取所有 bucket,以 bucket id 为键
+    pub async fn buckets(&self) -> Result<HashMap<String, Bucket>> {
+        let buckets = self
+            .http
+            .get(self.url("buckets/"))
+            .send()
+            .await
+            .context("Failed to connect to aw-server")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Failed to parse aw-server buckets")?;
+        Ok(buckets)
+    }
+
+    /// 获取 bucket 中的事件,按时间倒序返回
+    pub async fn events(
+        &self,
+        bucket_id: &str,
+        limit: Option<usize>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Event>> {
+        let mut query = Vec::new();
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(start) = start {
+            query.push(("start", start.to_rfc3339()));
+        }
+        if let Some(end) = end {
+            query.push(("end", end.to_rfc3339()));
+        }
+
+        let events = self
+            .http
+            .get(self.url(&format!("buckets/{}/events", bucket_id)))
+            .query(&query)
+            .send()
+            .await
+            .context("Failed to connect to aw-server")?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Failed to parse events of bucket {}", bucket_id))?;
+        Ok(events)
+    }
+
+    /// 创建 bucket,已存在时 aw-server 返回 304,同样视为成功
+    pub async fn create_bucket(
+        &self,
+        bucket_id: &str,
+        bucket_type: &str,
+        hostname: &str,
+    ) -> Result<()> {
+        self.http
+            .post(self.url(&format!("buckets/{}", bucket_id)))
+            .json(&serde_json::json!({
+                "client": CLIENT_NAME,
+                "type": bucket_type,
+                "hostname": hostname,
+            }))
+            .send()
+            .await
+            .context("Failed to connect to aw-server")?
+            .error_for_status()
+            .with_context(|| format!("Failed to create bucket {}", bucket_id))?;
+        Ok(())
+    }
+
+    /// 向 bucket 插入事件
+    pub async fn insert_events(&self, bucket_id: &str, events: &[Event]) -> Result<()> {
+        self.http
+            .post(self.url(&format!("buckets/{}/events", bucket_id)))
+            .json(events)
+            .send()
+            .await
+            .context("Failed to connect to aw-server")?
+            .error_for_status()
+            .with_context(|| format!("Failed to insert events into bucket {}", bucket_id))?;
+        Ok(())
+    }
+
+    /// 发送心跳
+    ///
+    /// 与 bucket 中最后一个事件数据相同且间隔不超过 `pulsetime` 秒时,aw-server 会把它
+    /// 合并进该事件并延长持续时间,否则新建事件
+    pub async fn heartbeat(&self, bucket_id: &str, event: &Event, pulsetime: f64) -> Result<()> {
+        self.http
+            .post(self.url(&format!("buckets/{}/heartbeat", bucket_id)))
+            .query(&[("pulsetime", pulsetime)])
+            .json(event)
+            .send()
+            .await
+            .context("Failed to connect to aw-server")?
+            .error_for_status()
+            .with_context(|| format!("Failed to send heartbeat to bucket {}", bucket_id))?;
+        Ok(())
+    }
+
+    /// 执行 aw-server 查询,按 `timeperiods` 的顺序返回每个时间段中 `RETURN` 的值
+    pub async fn query(
+        &self,
+        timeperiods: &[(DateTime<Utc>, DateTime<Utc>)],
+        statements: &[String],
+    ) -> Result<Vec<serde_json::Value>> {
+        let timeperiods: Vec<String> = timeperiods
+            .iter()
+            .map(|(start, end)| format!("{}/{}", start.to_rfc3339(), end.to_rfc3339()))
+            .collect();
+        let results = self
+            .http
+            .post(self.url("query/"))
+            .json(&serde_json::json!({
+                "timeperiods": timeperiods,
+                "query": statements,
+            }))
+            .send()
+            .await
+            .context("Failed to connect to aw-server")?
+            .error_for_status()
+            .context("aw-server rejected the query")?
+            .json()
+            .await
+            .context("Failed to parse query result")?;
+        Ok(results)
+    }
+
+    /// 读取 aw-server 保存的设置,不存在时返回 `None`
+    pub async fn setting(&self, key: &str) -> Result<Option<serde_json::Value>> {
+        let response = self
+            .http
+            .get(self.url(&format!("settings/{}", key)))
+            .send()
+            .await
+            .context("Failed to connect to aw-server")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let value = response
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Failed to parse setting {}", key))?;
+        Ok(Some(value))
+    }
+
+    /// 找到指定类型中最近更新的 bucket,例如 `afkstatus`、`currentwindow`
+    pub async fn find_bucket(&self, bucket_type: &str) -> Result<Option<Bucket>> {
+        let bucket = self
+            .buckets()
+            .await?
+            .into_values()
+            .filter(|b| b.bucket_type == bucket_type)
+            .max_by_key(|b| b.last_updated);
+        Ok(bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activitywatch::stand_in::StandInServer;
+
+    #[test]
+    fn test_url() {
+        let client = AwClient::new("http://localhost:5600/").unwrap();
+        assert_eq!(client.base_url(), "http://localhost:5600");
+        assert_eq!(client.url("info"), "http://localhost:5600/api/0/info");
+        assert_eq!(
+            client.url("/buckets/"),
+            "http://localhost:5600/api/0/buckets/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_bucket_and_events() {
+        let server = StandInServer::spawn(|request| match request.path.as_str() {
+            "/api/0/buckets/" => (
+                200,
+                r#"{
+                    "aw-watcher-afk_old": {"id": "aw-watcher-afk_old", "type": "afkstatus",
+                        "last_updated": "2024-01-01T00:00:00Z"},
+                    "aw-watcher-afk_host": {"id": "aw-watcher-afk_host", "type": "afkstatus",
+                        "last_updated": "2024-06-01T00:00:00Z"},
+                    "aw-watcher-window_host": {"id": "aw-watcher-window_host", "type": "currentwindow",
+                        "last_updated": "2024-06-02T00:00:00Z"}
+                }"#
+                .to_string(),
+            ),
+            "/api/0/buckets/aw-watcher-afk_host/events" => (
+                200,
+                r#"[{"id": 1, "timestamp": "2024-06-01T00:00:00Z", "duration": 90.5,
+                    "data": {"status": "afk"}}]"#
+                    .to_string(),
+            ),
+            _ => (404, "{}".to_string()),
+        })
+        .await;
+        let client = AwClient::new(server.url()).unwrap();
+
+        let bucket = client.find_bucket("afkstatus").await.unwrap().unwrap();
+        assert_eq!(bucket.id, "aw-watcher-afk_host");
+
+        let events = client
+            .events(&bucket.id, Some(1), None, None)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data["status"], "afk");
+        assert_eq!(
+            events[0].end(),
+            "2024-06-01T00:01:30.500Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        let requests = server.requests();
+        assert_eq!(requests[1].method, "GET");
+        assert!(requests[1].query.contains("limit=1"));
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::activitywatch::client::{AwClient, CLIENT_NAME, Event};
+use crate::capture::CaptureState;
+use crate::event::WindowActivity;
+
+/// 把窗口任务发布的焦点窗口以 `currentwindow` 心跳发送到 aw-server
+///
+/// 事件数据与 aw-watcher-window 相同(`app`、`title`),另附该窗口最近保存的截图ID
+/// `screenshot`。截图变化时数据不同,aw-server 会新建事件,因此每个事件对应一张截图
+pub struct WindowHeartbeats {
+    client: AwClient,
+}
+
+impl WindowHeartbeats {
+    pub fn new(client: AwClient) -> Self {
+        Self { client }
+    }
+
+    /// 持续发送心跳直到收到取消信号
+    ///
+    /// 发送失败只记录日志,不影响截图
+    pub async fn run(self, state: CaptureState, cancel_token: CancellationToken) {
+        info!("Window heartbeats started");
+        let mut activity_rx = state.subscribe_window_activity();
+        let mut bucket: Option<String> = None;
+        let mut last_error: Option<String> = None;
+
+        loop {
+            tokio::select! {
+                Ok(()) = activity_rx.changed() => {}
+                _ = cancel_token.cancelled() => {
+                    info!("Window heartbeats received cancellation signal");
+                    break;
+                }
+            }
+
+            let Some(activity) = activity_rx.borrow_and_update().clone() else {
+                continue;
+            };
+            match self.send(&mut bucket, &activity).await {
+                Ok(()) => {
+                    if last_error.take().is_some() {
+                        info!("Window heartbeats delivered again");
+                    }
+                }
+                Err(e) => {
+                    let message = format!("{:#}", e);
+                    if last_error.as_deref() != Some(message.as_str()) {
+                        warn!("Failed to send window heartbeat: {}", message);
+                    }
+                    last_error = Some(message);
+                }
+            }
+        }
+
+        info!("Window heartbeats terminated");
+    }
+
+    /// 发送一次心跳,首次发送前按 aw-server 的主机名创建 bucket
+    async fn send(&self, bucket: &mut Option<String>, activity: &WindowActivity) -> Result<()> {
+        let bucket_id = match bucket {
+            Some(id) => id.clone(),
+            None => {
+                let hostname = self.client.info().await?.hostname;
+                let id = format!("{}-window_{}", CLIENT_NAME, hostname);
+                self.client
+                    .create_bucket(&id, "currentwindow", &hostname)
+                    .await?;
+                info!("Sending window heartbeats to bucket {}", id);
+                bucket.insert(id).clone()
+            }
+        };
+
+        debug!("Window heartbeat: {} - {}", activity.app, activity.title);
+        self.client
+            .heartbeat(
+                &bucket_id,
+                &heartbeat_event(activity),
+                pulsetime(activity.interval),
+            )
+            .await
+    }
+}
+
+fn heartbeat_event(activity: &WindowActivity) -> Event {
+    let mut data = serde_json::Map::new();
+    data.insert("app".to_string(), activity.app.clone().into());
+    data.insert("title".to_string(), activity.title.clone().into());
+    if let Some(screenshot) = &activity.screenshot {
+        data.insert("screenshot".to_string(), screenshot.clone().into());
+    }
+    Event {
+        id: None,
+        timestamp: activity.timestamp,
+        duration: 0.0,
+        data,
+    }
+}
+
+/// 与 aw-watcher-window 一致:检查间隔再加 1 秒,容忍一次检查的延迟
+fn pulsetime(interval: u64) -> f64 {
+    interval as f64 / 1000.0 + 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activitywatch::stand_in::StandInServer;
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_creates_bucket_then_sends_heartbeats() {
+        let server = StandInServer::spawn(|request| match request.path.as_str() {
+            "/api/0/info" => (
+                200,
+                r#"{"hostname": "desk", "version": "v0.13.2"}"#.to_string(),
+            ),
+            "/api/0/buckets/aw-watcher-screenshot-window_desk" => (304, String::new()),
+            "/api/0/buckets/aw-watcher-screenshot-window_desk/heartbeat" => (200, "{}".to_string()),
+            _ => (404, "{}".to_string()),
+        })
+        .await;
+        let heartbeats = WindowHeartbeats::new(AwClient::new(server.url()).unwrap());
+
+        let mut activity = WindowActivity {
+            timestamp: Utc::now(),
+            app: "firefox".to_string(),
+            title: "ActivityWatch".to_string(),
+            screenshot: None,
+            interval: 1000,
+        };
+        let mut bucket = None;
+        heartbeats.send(&mut bucket, &activity).await.unwrap();
+        activity.screenshot = Some("window_firefox_42_20240601_120000.000".to_string());
+        heartbeats.send(&mut bucket, &activity).await.unwrap();
+
+        let requests = server.requests();
+        let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/api/0/info",
+                "/api/0/buckets/aw-watcher-screenshot-window_desk",
+                "/api/0/buckets/aw-watcher-screenshot-window_desk/heartbeat",
+                "/api/0/buckets/aw-watcher-screenshot-window_desk/heartbeat",
+            ]
+        );
+        let created: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(created["type"], "currentwindow");
+        assert_eq!(created["hostname"], "desk");
+
+        assert_eq!(requests[2].query, "pulsetime=2.0");
+        let first: Event = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(first.data["app"], "firefox");
+        assert!(!first.data.contains_key("screenshot"));
+        let second: Event = serde_json::from_str(&requests[3].body).unwrap();
+        assert_eq!(
+            second.data["screenshot"],
+            "window_firefox_42_20240601_120000.000"
+        );
+    }
+}
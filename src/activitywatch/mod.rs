@@ -0,0 +1,13 @@
+pub mod afk;
+pub mod annotate;
+pub mod client;
+pub mod heartbeat;
+pub mod screenshots;
+#[cfg(test)]
+pub mod stand_in;
+
+pub use afk::*;
+pub use annotate::*;
+pub use client::*;
+pub use heartbeat::*;
+pub use screenshots::*;
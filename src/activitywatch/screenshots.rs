@@ -0,0 +1,297 @@
+use anyhow::Result;
+use chrono::{DurationRound, TimeDelta};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::activitywatch::client::{AwClient, CLIENT_NAME, Event};
+use crate::config::ScreenshotEventMode;
+use crate::event::{screenshot_id, screenshot_stream};
+use crate::storage::CaptureMetadata;
+
+/// 截图事件所在 bucket 的类型
+pub const SCREENSHOT_BUCKET_TYPE: &str = "screenshot";
+
+/// 已保存的截图,经通道交给 [`ScreenshotEvents::run`] 上报
+#[derive(Debug, Clone)]
+pub struct SavedScreenshot {
+    pub metadata: CaptureMetadata,
+    /// 该来源的强制截图间隔(毫秒),用于计算心跳的 pulsetime
+    pub enforce_interval: u64,
+}
+
+/// 把保存的截图作为事件上报到 aw-server
+///
+/// 事件数据为 `{"source": 来源ID, "screenshot": 截图ID}`,保存了缩略图时附加 `thumbnail`
+/// (缩略图在存储中的 key),截图带有活动标注时附加
+/// `app`、`title`、`category`、`url` 与 `afk`。`heartbeat` 模式下,同一来源连续相似的
+/// 截图沿用合并中事件首张截图的数据,aw-server 据此把它们合并为一个持续事件。
+/// aw-server 只合并数据相同的心跳,最后一张截图无法写入数据,但事件结束时刻就是它的
+/// 截图时间,由 `screenshot_id(source, event.end())` 即可得到
+///
+/// aw-server 只会把心跳合并进 bucket 的最后一个事件,因此每路截图使用单独的 bucket
+pub struct ScreenshotEvents {
+    client: AwClient,
+    mode: ScreenshotEventMode,
+    hostname: Option<String>,
+    /// 已创建的 bucket
+    buckets: HashSet<String>,
+    /// 各来源合并中事件的首张截图的事件数据
+    first: HashMap<String, Map<String, Value>>,
+    last_error: Option<String>,
+}
+
+impl ScreenshotEvents {
+    pub fn new(client: AwClient, mode: ScreenshotEventMode) -> Self {
+        Self {
+            client,
+            mode,
+            hostname: None,
+            buckets: HashSet::new(),
+            first: HashMap::new(),
+            last_error: None,
+        }
+    }
+
+    /// 逐个上报收到的截图,发送端全部关闭且队列清空后退出
+    ///
+    /// 在单独的任务中运行,aw-server 无响应时不会拖慢截图的保存
+    pub async fn run(mut self, mut saved_rx: mpsc::Receiver<SavedScreenshot>) {
+        info!("Screenshot events started");
+        while let Some(saved) = saved_rx.recv().await {
+            self.record(&saved).await;
+        }
+        info!("Screenshot events terminated");
+    }
+
+    /// 上报一张已保存的截图
+    ///
+    /// 发送失败只记录日志,不影响截图
+    async fn record(&mut self, saved: &SavedScreenshot) {
+        match self.send(saved).await {
+            Ok(()) => {
+                if self.last_error.take().is_some() {
+                    info!("Screenshot events delivered again");
+                }
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                if self.last_error.as_deref() != Some(message.as_str()) {
+                    warn!("Failed to send screenshot event: {}", message);
+                }
+                self.last_error = Some(message);
+            }
+        }
+    }
+
+    async fn send(&mut self, saved: &SavedScreenshot) -> Result<()> {
+        if self.mode == ScreenshotEventMode::Off {
+            return Ok(());
+        }
+        let metadata = &saved.metadata;
+        let bucket_id = self.bucket(&metadata.source).await?;
+        // 截图ID精确到毫秒,事件时间同样取整,便于由事件结束时刻还原最后一张截图
+        let timestamp = metadata
+            .timestamp
+            .duration_trunc(TimeDelta::milliseconds(1))?;
+        let id = screenshot_id(&metadata.source, timestamp);
+
+        if self.mode == ScreenshotEventMode::Event {
+            debug!("Screenshot event: {}", id);
+            let event = Event {
+                id: None,
+                timestamp,
+                duration: 0.0,
+                data: event_data(metadata, id),
+            };
+            return self.client.insert_events(&bucket_id, &[event]).await;
+        }
+
+        let data = match self.first.get(&metadata.source) {
+            Some(first) if metadata.similar => first.clone(),
+            _ => event_data(metadata, id),
+        };
+        debug!(
+            "Screenshot heartbeat: {} (first {})",
+            metadata.source, data["screenshot"]
+        );
+        self.first.insert(metadata.source.clone(), data.clone());
+        let event = Event {
+            id: None,
+            timestamp,
+            duration: 0.0,
+            data,
+        };
+        self.client
+            .heartbeat(&bucket_id, &event, pulsetime(saved.enforce_interval))
+            .await
+    }
+
+    /// 返回来源对应的 bucket,首次使用前按 aw-server 的主机名创建
+    async fn bucket(&mut self, source: &str) -> Result<String> {
+        let hostname = match &self.hostname {
+            Some(hostname) => hostname.clone(),
+            None => {
+                let hostname = self.client.info().await?.hostname;
+                self.hostname.insert(hostname).clone()
+            }
+        };
+        let bucket_id = format!("{}_{}_{}", CLIENT_NAME, screenshot_stream(source), hostname);
+        if !self.buckets.contains(&bucket_id) {
+            self.client
+                .create_bucket(&bucket_id, SCREENSHOT_BUCKET_TYPE, &hostname)
+                .await?;
+            info!("Sending screenshot events to bucket {}", bucket_id);
+            self.buckets.insert(bucket_id.clone());
+        }
+        Ok(bucket_id)
+    }
+}
+
+fn event_data(metadata: &CaptureMetadata, screenshot: String) -> Map<String, Value> {
+    let mut data = Map::new();
+    data.insert("source".to_string(), metadata.source.clone().into());
+    data.insert("screenshot".to_string(), screenshot.into());
+    if let Some(thumbnail) = &metadata.thumbnail {
+        data.insert("thumbnail".to_string(), thumbnail.clone().into());
+    }
+    if let Some(activity) = &metadata.activity
+        && let Ok(Value::Object(fields)) = serde_json::to_value(activity)
+    {
+        // 没有对应事件的字段不写入
+        data.extend(
+            fields
+                .into_iter()
+                .filter(|(_, v)| !v.is_null() && v.as_array().is_none_or(|a| !a.is_empty())),
+        );
+    }
+    data
+}
+
+/// 相似截图只在距上次保存超过 `enforce_interval` 后才保存,再留出半个间隔的余量,
+/// 容忍检查间隔与截图耗时带来的延迟
+fn pulsetime(enforce_interval: u64) -> f64 {
+    enforce_interval as f64 / 1000.0 * 1.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activitywatch::stand_in::StandInServer;
+    use crate::event::CaptureResult;
+    use chrono::{DateTime, Duration, Utc};
+    use image::RgbaImage;
+
+    const BUCKET: &str = "/api/0/buckets/aw-watcher-screenshot_DP-1_1920_1080_0_0_desk";
+
+    async fn stand_in() -> StandInServer {
+        StandInServer::spawn(|request| match request.path.as_str() {
+            "/api/0/info" => (
+                200,
+                r#"{"hostname": "desk", "version": "v0.13.2"}"#.to_string(),
+            ),
+            path if path.starts_with("/api/0/buckets/") => (200, "{}".to_string()),
+            _ => (404, "{}".to_string()),
+        })
+        .await
+    }
+
+    fn capture(at: DateTime<Utc>, similar: bool, thumbnail: Option<&str>) -> SavedScreenshot {
+        let result = CaptureResult::new("DP-1_1920_1080_0_0".to_string(), RgbaImage::new(1, 1), at)
+            .with_dedup(similar, 30000);
+        let mut metadata = CaptureMetadata::new(&result);
+        metadata.thumbnail = thumbnail.map(str::to_string);
+        SavedScreenshot {
+            metadata,
+            enforce_interval: result.enforce_interval,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_similar_screenshots_share_heartbeat_data() {
+        let server = stand_in().await;
+        let events = ScreenshotEvents::new(
+            AwClient::new(server.url()).unwrap(),
+            ScreenshotEventMode::Heartbeat,
+        );
+
+        let start: DateTime<Utc> = "2024-06-01T12:00:00.250Z".parse().unwrap();
+        let (saved_tx, saved_rx) = mpsc::channel(8);
+        for (offset, similar) in [(0, false), (31, true), (40, false)] {
+            saved_tx
+                .send(capture(start + Duration::seconds(offset), similar, None))
+                .await
+                .unwrap();
+        }
+        drop(saved_tx);
+        // 发送端关闭后上报完队列中的截图才退出
+        events.run(saved_rx).await;
+
+        let requests = server.requests();
+        let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+        let heartbeat = format!("{}/heartbeat", BUCKET);
+        assert_eq!(
+            paths,
+            vec!["/api/0/info", BUCKET, &heartbeat, &heartbeat, &heartbeat]
+        );
+        let created: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(created["type"], "screenshot");
+
+        assert_eq!(requests[2].query, "pulsetime=45.0");
+        let sent: Vec<Event> = requests[2..]
+            .iter()
+            .map(|r| serde_json::from_str(&r.body).unwrap())
+            .collect();
+        // 相似的截图沿用首张截图ID,aw-server 会把它合并进上一个事件
+        assert_eq!(sent[0].data, sent[1].data);
+        assert_eq!(
+            sent[1].data["screenshot"],
+            "DP-1_1920_1080_0_0_20240601_120000.250"
+        );
+        assert_eq!(
+            sent[2].data["screenshot"],
+            "DP-1_1920_1080_0_0_20240601_120040.250"
+        );
+
+        // aw-server 合并后的事件从首张截图开始,结束于最后一张截图
+        let merged = Event {
+            duration: (sent[1].timestamp - sent[0].timestamp).num_milliseconds() as f64 / 1000.0,
+            ..sent[0].clone()
+        };
+        assert_eq!(
+            screenshot_id("DP-1_1920_1080_0_0", merged.end()),
+            "DP-1_1920_1080_0_0_20240601_120031.250"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_mode_posts_each_screenshot() {
+        let server = stand_in().await;
+        let mut events = ScreenshotEvents::new(
+            AwClient::new(server.url()).unwrap(),
+            ScreenshotEventMode::Event,
+        );
+
+        let start = Utc::now();
+        events
+            .send(&capture(start, false, Some("thumb.jpg")))
+            .await
+            .unwrap();
+        events
+            .send(&capture(start + Duration::seconds(31), true, None))
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        let posted: Vec<Vec<Event>> = requests
+            .iter()
+            .filter(|r| r.path == format!("{}/events", BUCKET))
+            .map(|r| serde_json::from_str(&r.body).unwrap())
+            .collect();
+        assert_eq!(posted.len(), 2);
+        assert_ne!(posted[0][0].data, posted[1][0].data);
+        assert_eq!(posted[0][0].data["thumbnail"], "thumb.jpg");
+        assert!(!posted[1][0].data.contains_key("thumbnail"));
+    }
+}
@@ -0,0 +1,118 @@
+//! 测试用的 aw-server 替身
+//!
+//! 只实现了足够 `AwClient` 使用的 HTTP/1.1 子集:每个连接处理一个请求后关闭
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 替身收到的请求
+#[derive(Debug, Clone)]
+pub struct StandInRequest {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub body: String,
+}
+
+type Handler = dyn Fn(&StandInRequest) -> (u16, String) + Send + Sync;
+
+pub struct StandInServer {
+    url: String,
+    requests: Arc<Mutex<Vec<StandInRequest>>>,
+}
+
+impl StandInServer {
+    /// 在随机端口启动替身,`handler` 返回状态码与 JSON 响应体
+    pub async fn spawn<F>(handler: F) -> Self
+    where
+        F: Fn(&StandInRequest) -> (u16, String) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let recorded = recorded.clone();
+                tokio::spawn(async move {
+                    serve_one(stream, handler.as_ref(), &recorded).await;
+                });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// 按到达顺序返回已处理的请求
+    pub fn requests(&self) -> Vec<StandInRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve_one(
+    mut stream: TcpStream,
+    handler: &Handler,
+    recorded: &Mutex<Vec<StandInRequest>>,
+) -> Option<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?.to_string();
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buffer.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let request = StandInRequest {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        body: String::from_utf8_lossy(&buffer[header_end..]).to_string(),
+    };
+
+    let (status, body) = handler(&request);
+    // 先记录再响应,保证客户端收到响应时请求已可见
+    recorded.lock().unwrap().push(request);
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        if status < 400 { "OK" } else { "Error" },
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await.ok()?;
+    stream.shutdown().await.ok()
+}
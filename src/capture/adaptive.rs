@@ -0,0 +1,68 @@
+use crate::config::AdaptiveConfig;
+
+/// 根据画面变化动态调整的检查间隔
+#[derive(Debug, Clone)]
+pub struct AdaptiveInterval {
+    config: AdaptiveConfig,
+    current: u64,
+}
+
+impl AdaptiveInterval {
+    pub fn new(config: AdaptiveConfig) -> Self {
+        let current = config.min_interval;
+        Self { config, current }
+    }
+
+    /// 当前的检查间隔(毫秒)
+    pub fn current(&self) -> u64 {
+        self.current
+    }
+
+    /// 根据本次截图与上次保存截图的哈希距离更新间隔
+    ///
+    /// - 距离达到阈值(画面变化明显)或没有可比较的历史时回到最小间隔
+    /// - 画面相似时按退避系数增大间隔,不超过最大间隔
+    pub fn update(&mut self, distance: Option<u32>, threshold: u32) -> u64 {
+        self.current = match distance {
+            Some(distance) if distance < threshold => {
+                let next = (self.current as f64 * self.config.backoff_factor).ceil() as u64;
+                next.min(self.config.max_interval)
+            }
+            _ => self.config.min_interval,
+        };
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adaptive() -> AdaptiveInterval {
+        AdaptiveInterval::new(AdaptiveConfig {
+            min_interval: 500,
+            max_interval: 2000,
+            backoff_factor: 2.0,
+        })
+    }
+
+    #[test]
+    fn test_backoff_until_max() {
+        let mut interval = adaptive();
+        assert_eq!(interval.current(), 500);
+        assert_eq!(interval.update(Some(1), 10), 1000);
+        assert_eq!(interval.update(Some(1), 10), 2000);
+        assert_eq!(interval.update(Some(0), 10), 2000);
+    }
+
+    #[test]
+    fn test_snap_back_on_spike() {
+        let mut interval = adaptive();
+        interval.update(Some(1), 10);
+        interval.update(Some(1), 10);
+        assert_eq!(interval.update(Some(10), 10), 500);
+
+        interval.update(Some(1), 10);
+        assert_eq!(interval.update(None, 10), 500);
+    }
+}
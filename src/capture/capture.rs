@@ -1,25 +1,97 @@
 use anyhow::Result;
 use std::collections::HashMap;
-use tokio::sync::mpsc::Sender;
+use std::sync::Arc;
+use tokio::sync::mpsc::{Sender, WeakSender};
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
-use tokio::time::{Duration, sleep};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info, warn};
-use xcap::Monitor;
-
-use crate::capture::monitor::SafeMonitor;
-use crate::capture::window::SafeWindow;
-use crate::config::{MonitorConfig, WindowConfig};
+use tracing::{error, info, warn};
+
+use crate::capture::desktop::SafeDesktop;
+use crate::capture::focus::run_focus_watcher;
+use crate::capture::monitor::{MonitorInfo, SafeMonitor};
+use crate::capture::rules::WindowRules;
+use crate::capture::scheduler::run_clock;
+use crate::capture::state::CaptureState;
+use crate::capture::task::{
+    DesktopSource, MonitorSource, TargetSource, WindowSource, run_capture_task,
+};
+use crate::config::{
+    DesktopConfig, FocusConfig, IdleConfig, MonitorConfig, ScheduleConfig, WindowConfig,
+    WindowTarget,
+};
 use crate::event::CaptureResult;
 
+/// 窗口截图任务在状态统计中使用的来源ID
+pub const WINDOW_SOURCE_ID: &str = "window";
+
+/// 虚拟桌面拼接截图的来源ID,同时作为截图ID
+pub const DESKTOP_SOURCE_ID: &str = "desktop";
+
+/// 窗口目标任务在状态统计中使用的来源ID,格式为 `target:<name>`
+pub fn target_source_id(name: &str) -> String {
+    format!("target:{}", name)
+}
+
+/// 运行中的截图任务
+///
+/// 通过 `config_tx` 在不重启任务的情况下更新配置,保留任务内的去重状态
+struct TaskHandle<C> {
+    handle: JoinHandle<()>,
+    cancel_token: CancellationToken,
+    config_tx: watch::Sender<C>,
+}
+
+impl<C> TaskHandle<C> {
+    /// 取消任务并等待其退出,返回任务是否正常结束
+    async fn stop(self) -> bool {
+        self.cancel_token.cancel();
+        self.handle.await.is_ok()
+    }
+}
+
+/// 一次配置热更新对截图任务的影响
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReloadSummary {
+    /// 新启动的任务
+    pub added: Vec<String>,
+    /// 已停止的任务
+    pub removed: Vec<String>,
+    /// 原地更新了配置的任务
+    pub updated: Vec<String>,
+}
+
+impl ReloadSummary {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
+}
+
 /// 统一的截图管理器
 ///
 /// 管理多个显示器和窗口的并发截图任务
 pub struct Capture {
     monitor_configs: HashMap<String, MonitorConfig>,
     window_config: Option<WindowConfig>,
+    target_configs: HashMap<String, WindowTarget>,
+    desktop_config: DesktopConfig,
+    idle_tx: watch::Sender<IdleConfig>,
+    schedule_tx: watch::Sender<ScheduleConfig>,
+    focus_tx: watch::Sender<FocusConfig>,
+    rules_tx: watch::Sender<Arc<WindowRules>>,
+    state: CaptureState,
     cancellation_token: CancellationToken,
-    task_handles: Option<Vec<JoinHandle<()>>>,
+    sender: Option<WeakSender<CaptureResult>>,
+    monitor_tasks: HashMap<String, TaskHandle<MonitorConfig>>,
+    window_task: Option<TaskHandle<WindowConfig>>,
+    target_tasks: HashMap<String, TaskHandle<WindowTarget>>,
+    desktop_task: Option<TaskHandle<DesktopConfig>>,
+    /// 运行中的桌面任务拼接的显示器,变化时需要重启任务
+    desktop_monitors: Vec<String>,
+    /// 共享时钟任务,未启用同步时只等待配置变化
+    clock_task: Option<JoinHandle<()>>,
+    /// 焦点监视任务,未启用时只等待配置变化
+    focus_task: Option<JoinHandle<()>>,
 }
 
 impl Capture {
@@ -49,236 +121,547 @@ impl Capture {
         }
         info!("Initialized window configuration");
 
+        let (idle_tx, _) = watch::channel(IdleConfig::default());
+        let (schedule_tx, _) = watch::channel(ScheduleConfig::default());
+        let (focus_tx, _) = watch::channel(FocusConfig::default());
+        let (rules_tx, _) = watch::channel(Arc::new(WindowRules::default()));
+
         Self {
             monitor_configs: configs,
             window_config,
+            target_configs: HashMap::new(),
+            desktop_config: DesktopConfig::default(),
+            idle_tx,
+            schedule_tx,
+            focus_tx,
+            rules_tx,
+            state: CaptureState::new(),
             cancellation_token: CancellationToken::new(),
-            task_handles: None,
+            sender: None,
+            monitor_tasks: HashMap::new(),
+            window_task: None,
+            target_tasks: HashMap::new(),
+            desktop_task: None,
+            desktop_monitors: Vec::new(),
+            clock_task: None,
+            focus_task: None,
         }
     }
 
-    /// 启动所有截图任务(包括监视器和窗口)
+    /// 设置 AFK 期间的截图策略
     ///
-    /// 返回成功启动的任务数量
-    pub fn start_capture(&mut self, sender: Sender<CaptureResult>) -> usize {
-        let mut handles = Vec::new();
+    /// AFK 状态由外部(如 `AfkWatcher`)写入共享状态
+    pub fn with_idle_config(self, idle_config: IdleConfig) -> Self {
+        if idle_config.enable {
+            info!("Initialized idle configuration: {}", idle_config);
+        }
+        self.idle_tx.send_replace(idle_config);
+        self
+    }
 
-        // 启动所有监视器任务
-        for (monitor_id, config) in &self.monitor_configs {
-            info!("Starting monitor capture loop for {}", monitor_id);
-            let monitor = match SafeMonitor::new(monitor_id.clone()) {
-                Ok(m) => m,
-                Err(e) => {
-                    warn!("Failed to init monitor {}: {}", monitor_id, e);
-                    continue;
-                }
-            };
+    /// 设置共享时钟,启用同步后所有截图任务在同一节拍截图
+    pub fn with_schedule(self, schedule: ScheduleConfig) -> Self {
+        if schedule.synchronized {
+            info!("Initialized synchronized schedule: {}", schedule);
+        }
+        self.schedule_tx.send_replace(schedule);
+        self
+    }
 
-            let monitor_id = monitor_id.clone();
-            let sender = sender.clone();
-            let config = config.clone();
-            let cancel_token = self.cancellation_token.child_token();
+    /// 热更新共享时钟配置,返回配置是否有变化
+    ///
+    /// 任务不需要重启,关闭同步后各任务回到自己的间隔
+    pub fn update_schedule(&self, schedule: ScheduleConfig) -> bool {
+        let changed = *self.schedule_tx.borrow() != schedule;
+        if changed {
+            info!("Updating schedule configuration: {}", schedule);
+            self.schedule_tx.send_replace(schedule);
+        }
+        changed
+    }
 
-            let handle = tokio::spawn(async move {
-                Self::monitor_task(monitor, monitor_id, sender, config, cancel_token).await;
-            });
+    /// 设置焦点切换触发,切换窗口后立即截图而不必等到下一个间隔
+    pub fn with_focus(self, focus: FocusConfig) -> Self {
+        if focus.enable {
+            info!("Initialized focus trigger: {}", focus);
+        }
+        self.focus_tx.send_replace(focus);
+        self
+    }
 
-            handles.push(handle);
+    /// 热更新焦点切换触发配置,返回配置是否有变化
+    pub fn update_focus(&self, focus: FocusConfig) -> bool {
+        let changed = *self.focus_tx.borrow() != focus;
+        if changed {
+            info!("Updating focus trigger configuration: {}", focus);
+            self.focus_tx.send_replace(focus);
+        }
+        changed
+    }
+
+    /// 设置窗口规则,命中隐私规则的窗口不截图
+    pub fn with_window_rules(self, rules: WindowRules) -> Self {
+        self.rules_tx.send_replace(Arc::new(rules));
+        self
+    }
+
+    /// 设置按规则截图的窗口目标,每个启用的目标运行独立任务
+    pub fn with_window_targets(mut self, targets: Vec<WindowTarget>) -> Self {
+        self.target_configs = enabled_targets(targets);
+        for (name, config) in &self.target_configs {
+            info!("Initialized window target {}: {}", name, config);
+        }
+        self
+    }
+
+    /// 设置虚拟桌面拼接截图,拼接所有启用的显示器
+    pub fn with_desktop(mut self, desktop_config: DesktopConfig) -> Self {
+        if desktop_config.enable {
+            info!("Initialized desktop configuration: {}", desktop_config);
+        }
+        self.desktop_config = desktop_config;
+        self
+    }
+
+    /// 启动所有截图任务(包括监视器和窗口)
+    ///
+    /// 返回成功启动的任务数量
+    pub fn start_capture(&mut self, sender: Sender<CaptureResult>) -> usize {
+        self.sender = Some(sender.downgrade());
+        self.state.set_queue(sender.downgrade());
+
+        // 先启动时钟,任务的第一次等待即可跟随节拍
+        let schedule_rx = self.schedule_tx.subscribe();
+        let idle_rx = self.idle_tx.subscribe();
+        let state = self.state.clone();
+        let cancel_token = self.cancellation_token.child_token();
+        self.clock_task = Some(tokio::spawn(async move {
+            run_clock(schedule_rx, idle_rx, state, cancel_token).await;
+        }));
+
+        let focus_rx = self.focus_tx.subscribe();
+        let state = self.state.clone();
+        let cancel_token = self.cancellation_token.child_token();
+        self.focus_task = Some(tokio::spawn(async move {
+            run_focus_watcher(focus_rx, state, cancel_token).await;
+        }));
+
+        // 启动所有监视器任务,只保存拼接截图时跳过
+        let configs: Vec<_> = if self.desktop_replaces_monitors() {
+            Vec::new()
+        } else {
+            self.monitor_configs
+                .iter()
+                .map(|(id, config)| (id.clone(), config.clone()))
+                .collect()
+        };
+        for (monitor_id, config) in configs {
+            self.spawn_monitor(monitor_id, config, &sender);
+        }
+
+        if self.desktop_config.enable {
+            info!("Starting desktop capture loop");
+            let monitor_ids = self.desktop_monitor_ids();
+            self.spawn_desktop(self.desktop_config.clone(), monitor_ids, &sender);
         }
 
         // 启动窗口任务(如果启用)
         info!("Starting window capture loop");
-        if let Some(config) = &self.window_config {
+        if let Some(config) = self.window_config.clone() {
             if config.enable {
-                let sender = sender.clone();
-                let config = config.clone();
-                let cancel_token = self.cancellation_token.child_token();
-
-                let handle = tokio::spawn(async move {
-                    Self::window_task(sender, config, cancel_token).await;
-                });
-
-                handles.push(handle);
+                self.spawn_window(config, &sender);
             }
         }
 
-        let count = handles.len();
-        self.task_handles = Some(handles);
+        let targets: Vec<_> = self
+            .target_configs
+            .iter()
+            .map(|(name, config)| (name.clone(), config.clone()))
+            .collect();
+        for (name, config) in targets {
+            self.spawn_target(name, config, &sender);
+        }
+
+        let count = self.task_count();
         info!("Started {} capture tasks", count);
         count
     }
 
-    /// 单个监视器的截图任务
-    async fn monitor_task(
-        mut monitor: SafeMonitor,
+    /// 启动单个监视器任务,返回是否成功
+    fn spawn_monitor(
+        &mut self,
         monitor_id: String,
-        sender: Sender<CaptureResult>,
         config: MonitorConfig,
-        cancel_token: CancellationToken,
-    ) {
-        let mut consecutive_errors = 0;
-        const MAX_CONSECUTIVE_ERRORS: u32 = 10;
-
-        info!("Monitor {} capture task started", monitor_id);
+        sender: &Sender<CaptureResult>,
+    ) -> bool {
+        info!("Starting monitor capture loop for {}", monitor_id);
+        let monitor = match SafeMonitor::new(monitor_id.clone()) {
+            Ok(m) => m,
+            Err(e) => {
+                warn!("Failed to init monitor {}: {}", monitor_id, e);
+                self.state
+                    .update_source(&monitor_id, |s| s.last_error = Some(e.to_string()));
+                return false;
+            }
+        };
+
+        let (config_tx, config_rx) = watch::channel(config);
+        let sender = sender.clone();
+        let idle_rx = self.idle_tx.subscribe();
+        let state = self.state.clone();
+        let cancel_token = self.cancellation_token.child_token();
+        let task_token = cancel_token.clone();
+
+        let source = MonitorSource::new(monitor, monitor_id.clone(), &config_rx.borrow());
+        let handle = tokio::spawn(run_capture_task(
+            source, sender, config_rx, idle_rx, state, task_token,
+        ));
+
+        self.monitor_tasks.insert(
+            monitor_id,
+            TaskHandle {
+                handle,
+                cancel_token,
+                config_tx,
+            },
+        );
+        true
+    }
 
-        loop {
-            if cancel_token.is_cancelled() {
-                info!("Monitor {} received cancellation signal", monitor_id);
-                break;
+    /// 启动桌面拼接任务,返回是否成功
+    fn spawn_desktop(
+        &mut self,
+        config: DesktopConfig,
+        monitor_ids: Vec<String>,
+        sender: &Sender<CaptureResult>,
+    ) -> bool {
+        let desktop = match SafeDesktop::new(&monitor_ids) {
+            Ok(desktop) => desktop,
+            Err(e) => {
+                warn!("Failed to init desktop capture: {}", e);
+                self.state
+                    .update_source(DESKTOP_SOURCE_ID, |s| s.last_error = Some(e.to_string()));
+                return false;
             }
+        };
+
+        let (config_tx, config_rx) = watch::channel(config);
+        let sender = sender.clone();
+        let idle_rx = self.idle_tx.subscribe();
+        let state = self.state.clone();
+        let cancel_token = self.cancellation_token.child_token();
+        let task_token = cancel_token.clone();
+
+        info!(
+            "Desktop capture covers {}",
+            desktop.monitor_ids().join(", ")
+        );
+        let handle = tokio::spawn(run_capture_task(
+            DesktopSource::new(desktop),
+            sender,
+            config_rx,
+            idle_rx,
+            state,
+            task_token,
+        ));
+
+        self.desktop_task = Some(TaskHandle {
+            handle,
+            cancel_token,
+            config_tx,
+        });
+        self.desktop_monitors = monitor_ids;
+        true
+    }
 
-            match Self::monitor_capture_once(&mut monitor, &sender, &config).await {
-                Ok(()) => {
-                    consecutive_errors = 0;
-                }
+    /// 参与桌面拼接的显示器,即所有启用的显示器
+    fn desktop_monitor_ids(&self) -> Vec<String> {
+        let mut monitor_ids: Vec<String> = self.monitor_configs.keys().cloned().collect();
+        monitor_ids.sort();
+        monitor_ids
+    }
+
+    fn desktop_replaces_monitors(&self) -> bool {
+        self.desktop_config.enable && self.desktop_config.replace_monitors
+    }
+
+    /// 启动窗口任务
+    fn spawn_window(&mut self, config: WindowConfig, sender: &Sender<CaptureResult>) {
+        let (config_tx, config_rx) = watch::channel(config);
+        let sender = sender.clone();
+        let idle_rx = self.idle_tx.subscribe();
+        let rules_rx = self.rules_tx.subscribe();
+        let state = self.state.clone();
+        let cancel_token = self.cancellation_token.child_token();
+        let task_token = cancel_token.clone();
+
+        let source = WindowSource::new(rules_rx, state.clone());
+        let handle = tokio::spawn(run_capture_task(
+            source, sender, config_rx, idle_rx, state, task_token,
+        ));
+
+        self.window_task = Some(TaskHandle {
+            handle,
+            cancel_token,
+            config_tx,
+        });
+    }
+
+    fn spawn_target(&mut self, name: String, config: WindowTarget, sender: &Sender<CaptureResult>) {
+        info!("Starting window target capture loop for {}", name);
+        let (config_tx, config_rx) = watch::channel(config);
+        let sender = sender.clone();
+        let idle_rx = self.idle_tx.subscribe();
+        let rules_rx = self.rules_tx.subscribe();
+        let state = self.state.clone();
+        let cancel_token = self.cancellation_token.child_token();
+        let task_token = cancel_token.clone();
+        let name_for_task = name.clone();
+
+        let handle = tokio::spawn(async move {
+            let source = match TargetSource::new(&config_rx.borrow(), rules_rx) {
+                Ok(source) => source,
                 Err(e) => {
-                    error!("Capture error for monitor {}: {}", monitor_id, e);
-                    consecutive_errors += 1;
-
-                    if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
-                        error!(
-                            "Monitor {} exceeded max consecutive errors ({}), terminating task",
-                            monitor_id, MAX_CONSECUTIVE_ERRORS
-                        );
-                        break;
-                    }
+                    error!("Invalid window target {}: {:#}", name_for_task, e);
+                    state.update_source(&target_source_id(&name_for_task), |s| {
+                        s.last_error = Some(e.to_string())
+                    });
+                    return;
+                }
+            };
+            run_capture_task(source, sender, config_rx, idle_rx, state, task_token).await;
+        });
+
+        self.target_tasks.insert(
+            name,
+            TaskHandle {
+                handle,
+                cancel_token,
+                config_tx,
+            },
+        );
+    }
+
+    /// 热更新配置
+    ///
+    /// 已有任务原地更新间隔与阈值(保留去重状态),新增或重新启用的显示器与窗口目标
+    /// 启动新任务,删除或禁用的停止对应任务。调用方负责在此之前完成配置校验
+    pub async fn reload(
+        &mut self,
+        monitor_configs: HashMap<String, MonitorConfig>,
+        window_config: Option<WindowConfig>,
+        window_targets: Vec<WindowTarget>,
+        idle_config: IdleConfig,
+        window_rules: WindowRules,
+        desktop_config: DesktopConfig,
+    ) -> ReloadSummary {
+        let enabled: HashMap<String, MonitorConfig> = monitor_configs
+            .into_iter()
+            .filter(|(_, config)| config.enable)
+            .collect();
+        // 只保存拼接截图时不运行单独的显示器任务
+        let desired = if desktop_config.enable && desktop_config.replace_monitors {
+            HashMap::new()
+        } else {
+            enabled.clone()
+        };
+
+        // 已退出的任务(例如连续错误过多)视为未运行,允许重新启动
+        self.monitor_tasks
+            .retain(|_, task| !task.handle.is_finished());
+        let running: HashMap<String, MonitorConfig> = self
+            .monitor_tasks
+            .iter()
+            .map(|(id, task)| (id.clone(), task.config_tx.borrow().clone()))
+            .collect();
+
+        let mut summary = plan_reload(&running, &desired);
 
-                    tokio::select! {
-                        _ = sleep(Duration::from_millis(config.interval * 3)) => {}
-                        _ = cancel_token.cancelled() => {
-                            info!("Monitor {} cancelled during error backoff", monitor_id);
-                            break;
-                        }
+        for monitor_id in &summary.removed {
+            if let Some(task) = self.monitor_tasks.remove(monitor_id) {
+                info!("Stopping monitor capture loop for {}", monitor_id);
+                task.stop().await;
+            }
+        }
+
+        for monitor_id in &summary.updated {
+            if let Some(task) = self.monitor_tasks.get(monitor_id) {
+                info!(
+                    "Updating monitor configuration for {}: {}",
+                    monitor_id, desired[monitor_id]
+                );
+                task.config_tx.send_replace(desired[monitor_id].clone());
+            }
+        }
+
+        let sender = self.sender.as_ref().and_then(WeakSender::upgrade);
+        match &sender {
+            Some(sender) => {
+                let added = std::mem::take(&mut summary.added);
+                for monitor_id in added {
+                    let config = desired[&monitor_id].clone();
+                    if self.spawn_monitor(monitor_id.clone(), config, sender) {
+                        summary.added.push(monitor_id);
                     }
-                    continue;
                 }
             }
+            None if !summary.added.is_empty() => {
+                warn!("Capture is not running, cannot start new monitor tasks");
+                summary.added.clear();
+            }
+            None => {}
+        }
 
-            tokio::select! {
-                _ = sleep(Duration::from_millis(config.interval)) => {}
-                _ = cancel_token.cancelled() => {
-                    info!("Monitor {} cancelled during interval", monitor_id);
-                    break;
+        // 窗口任务
+        if self
+            .window_task
+            .as_ref()
+            .is_some_and(|task| task.handle.is_finished())
+        {
+            self.window_task = None;
+        }
+        match (&window_config, self.window_task.take()) {
+            (Some(config), Some(task)) if config.enable => {
+                if *task.config_tx.borrow() != *config {
+                    info!("Updating window configuration: {}", config);
+                    task.config_tx.send_replace(config.clone());
+                    summary.updated.push(WINDOW_SOURCE_ID.to_string());
                 }
+                self.window_task = Some(task);
+            }
+            (_, Some(task)) => {
+                info!("Stopping window capture loop");
+                task.stop().await;
+                summary.removed.push(WINDOW_SOURCE_ID.to_string());
             }
+            (Some(config), None) if config.enable => {
+                if let Some(sender) = &sender {
+                    info!("Starting window capture loop");
+                    self.spawn_window(config.clone(), sender);
+                    summary.added.push(WINDOW_SOURCE_ID.to_string());
+                }
+            }
+            _ => {}
+        }
+
+        self.reload_targets(
+            enabled_targets(window_targets),
+            sender.as_ref(),
+            &mut summary,
+        )
+        .await;
+
+        self.monitor_configs = enabled;
+        self.reload_desktop(desktop_config, sender.as_ref(), &mut summary)
+            .await;
+
+        if *self.idle_tx.borrow() != idle_config {
+            info!("Updating idle configuration: {}", idle_config);
+            self.idle_tx.send_replace(idle_config);
         }
+        self.rules_tx.send_replace(Arc::new(window_rules));
 
-        info!("Monitor {} capture task terminated", monitor_id);
+        self.window_config = window_config;
+
+        info!(
+            "Capture reloaded: {} added, {} removed, {} updated",
+            summary.added.len(),
+            summary.removed.len(),
+            summary.updated.len()
+        );
+        summary
     }
 
-    /// 窗口截图任务
-    async fn window_task(
-        sender: Sender<CaptureResult>,
-        config: WindowConfig,
-        cancel_token: CancellationToken,
+    /// 按名称增删或原地更新窗口目标任务,变化记入 `summary`
+    async fn reload_targets(
+        &mut self,
+        desired: HashMap<String, WindowTarget>,
+        sender: Option<&Sender<CaptureResult>>,
+        summary: &mut ReloadSummary,
     ) {
-        let mut window = SafeWindow::new();
-        let mut consecutive_errors = 0;
-        const MAX_CONSECUTIVE_ERRORS: u32 = 10;
-
-        info!("Window capture task started");
+        self.target_tasks
+            .retain(|_, task| !task.handle.is_finished());
+        let running: HashMap<String, WindowTarget> = self
+            .target_tasks
+            .iter()
+            .map(|(name, task)| (name.clone(), task.config_tx.borrow().clone()))
+            .collect();
+        let plan = plan_reload(&running, &desired);
 
-        loop {
-            if cancel_token.is_cancelled() {
-                info!("Window capture received cancellation signal");
-                break;
+        for name in &plan.removed {
+            if let Some(task) = self.target_tasks.remove(name) {
+                info!("Stopping window target capture loop for {}", name);
+                task.stop().await;
+                summary.removed.push(target_source_id(name));
             }
-
-            match Self::window_capture_once(&mut window, &sender, &config).await {
-                Ok(captured) => {
-                    consecutive_errors = 0;
-                    if captured {
-                        if let Some((app, title)) = window.last_window_info() {
-                            debug!("Captured window: {} - {}", app, title);
-                        }
-                    }
-                }
-                Err(e) => {
-                    let error_msg = e.to_string();
-
-                    // 没有焦点窗口是正常情况,不计入连续错误
-                    if error_msg.contains("No focused window found") {
-                        debug!("No focused window, skipping capture");
-                    } else {
-                        error!("Window capture error: {}", e);
-                        consecutive_errors += 1;
-
-                        if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
-                            error!(
-                                "Window capture exceeded max consecutive errors ({}), terminating task",
-                                MAX_CONSECUTIVE_ERRORS
-                            );
-                            break;
-                        }
-
-                        tokio::select! {
-                            _ = sleep(Duration::from_millis(config.interval * 3)) => {}
-                            _ = cancel_token.cancelled() => {
-                                info!("Window capture cancelled during error backoff");
-                                break;
-                            }
-                        }
-                        continue;
-                    }
-                }
+        }
+        for name in &plan.updated {
+            if let Some(task) = self.target_tasks.get(name) {
+                info!("Updating window target {}: {}", name, desired[name]);
+                task.config_tx.send_replace(desired[name].clone());
+                summary.updated.push(target_source_id(name));
             }
-
-            tokio::select! {
-                _ = sleep(Duration::from_millis(config.interval)) => {}
-                _ = cancel_token.cancelled() => {
-                    info!("Window capture cancelled during interval");
-                    break;
+        }
+        match sender {
+            Some(sender) => {
+                for name in &plan.added {
+                    self.spawn_target(name.clone(), desired[name].clone(), sender);
+                    summary.added.push(target_source_id(name));
                 }
             }
+            None if !plan.added.is_empty() => {
+                warn!("Capture is not running, cannot start new window target tasks");
+            }
+            None => {}
         }
 
-        info!("Window capture task terminated");
+        self.target_configs = desired;
     }
 
-    /// 执行一次监视器截图
-    async fn monitor_capture_once(
-        monitor: &mut SafeMonitor,
-        sender: &Sender<CaptureResult>,
-        config: &MonitorConfig,
-    ) -> Result<()> {
-        let result = monitor.capture_once(
-            config.enforce_interval,
-            config.dhash_threshold,
-            config.dhash_resolution,
-        )?;
-
-        if let Some(capture_result) = result {
-            sender
-                .send(capture_result)
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to send capture result: {}", e))?;
-        }
-
-        Ok(())
-    }
+    /// 原地更新桌面拼接任务,启用的显示器变化时重启任务
+    async fn reload_desktop(
+        &mut self,
+        desired: DesktopConfig,
+        sender: Option<&Sender<CaptureResult>>,
+        summary: &mut ReloadSummary,
+    ) {
+        let monitor_ids = self.desktop_monitor_ids();
+        if self
+            .desktop_task
+            .as_ref()
+            .is_some_and(|task| task.handle.is_finished())
+        {
+            self.desktop_task = None;
+        }
 
-    /// 执行一次窗口截图
-    async fn window_capture_once(
-        window: &mut SafeWindow,
-        sender: &Sender<CaptureResult>,
-        config: &WindowConfig,
-    ) -> Result<bool> {
-        let result = window.capture_once(
-            config.enforce_interval,
-            config.dhash_threshold,
-            config.dhash_resolution,
-            config.enable_ocr,
-        )?;
-
-        if let Some(capture_result) = result {
-            sender
-                .send(capture_result)
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to send capture result: {}", e))?;
-            Ok(true)
-        } else {
-            Ok(false)
+        if let Some(task) = self.desktop_task.take() {
+            if desired.enable && self.desktop_monitors == monitor_ids {
+                if *task.config_tx.borrow() != desired {
+                    info!("Updating desktop configuration: {}", desired);
+                    task.config_tx.send_replace(desired.clone());
+                    summary.updated.push(DESKTOP_SOURCE_ID.to_string());
+                }
+                self.desktop_task = Some(task);
+            } else {
+                info!("Stopping desktop capture loop");
+                task.stop().await;
+                summary.removed.push(DESKTOP_SOURCE_ID.to_string());
+            }
+        }
+
+        if desired.enable && self.desktop_task.is_none() {
+            match sender {
+                Some(sender) => {
+                    info!("Starting desktop capture loop");
+                    if self.spawn_desktop(desired.clone(), monitor_ids, sender) {
+                        summary.added.push(DESKTOP_SOURCE_ID.to_string());
+                    }
+                }
+                None => warn!("Capture is not running, cannot start desktop capture"),
+            }
         }
+
+        self.desktop_config = desired;
     }
 
     /// 优雅关闭所有截图任务
@@ -289,61 +672,105 @@ impl Capture {
 
         self.cancellation_token.cancel();
 
-        if let Some(handles) = self.task_handles.take() {
-            let total = handles.len();
-            let mut completed = 0;
+        if !self.is_running() {
+            warn!("No tasks to shutdown");
+            return 0;
+        }
 
-            for handle in handles {
-                if handle.await.is_ok() {
-                    completed += 1;
-                }
-            }
+        let total = self.task_count();
+        let mut completed = 0;
 
-            info!(
-                "Capture shutdown complete: {}/{} tasks finished",
-                completed, total
-            );
-            completed
-        } else {
-            warn!("No tasks to shutdown");
-            0
+        for (_, task) in self.monitor_tasks.drain() {
+            if task.stop().await {
+                completed += 1;
+            }
+        }
+        if let Some(task) = self.window_task.take() {
+            if task.stop().await {
+                completed += 1;
+            }
+        }
+        for (_, task) in self.target_tasks.drain() {
+            if task.stop().await {
+                completed += 1;
+            }
+        }
+        if let Some(task) = self.desktop_task.take() {
+            if task.stop().await {
+                completed += 1;
+            }
+        }
+        if let Some(clock_task) = self.clock_task.take() {
+            let _ = clock_task.await;
         }
+        if let Some(focus_task) = self.focus_task.take() {
+            let _ = focus_task.await;
+        }
+
+        info!(
+            "Capture shutdown complete: {}/{} tasks finished",
+            completed, total
+        );
+        completed
     }
 
     /// 检查是否有任务正在运行
     pub fn is_running(&self) -> bool {
-        self.task_handles
-            .as_ref()
-            .map(|handles| !handles.is_empty())
-            .unwrap_or(false)
+        self.task_count() > 0
     }
 
     /// 获取已启动的任务数量
     pub fn task_count(&self) -> usize {
-        self.task_handles
-            .as_ref()
-            .map(|handles| handles.len())
-            .unwrap_or(0)
+        self.monitor_tasks.len()
+            + usize::from(self.window_task.is_some())
+            + self.target_tasks.len()
+            + usize::from(self.desktop_task.is_some())
+    }
+
+    /// 获取共享状态句柄,用于暂停/恢复/立即截图等外部控制
+    pub fn state(&self) -> CaptureState {
+        self.state.clone()
     }
 
     /// 获取目前连接的显示器ID
     pub fn get_all_monitors_id() -> Result<Vec<String>> {
-        let monitors = Monitor::all()?;
-        let monitor_ids: Vec<String> = monitors
-            .iter()
-            .map(|m| {
-                format!(
-                    "{}_{}_{}_{}_{}",
-                    m.name(),
-                    m.width(),
-                    m.height(),
-                    m.x(),
-                    m.y()
-                )
-            })
-            .collect();
-        Ok(monitor_ids)
+        Ok(MonitorInfo::all()?.into_iter().map(|m| m.id).collect())
+    }
+}
+
+/// 启用的窗口目标,按名称索引
+fn enabled_targets(targets: Vec<WindowTarget>) -> HashMap<String, WindowTarget> {
+    targets
+        .into_iter()
+        .filter(|target| target.enable)
+        .map(|target| (target.name.clone(), target))
+        .collect()
+}
+
+/// 比较运行中与期望的任务配置,得出需要启动、停止与更新的任务
+fn plan_reload<C: PartialEq>(
+    running: &HashMap<String, C>,
+    desired: &HashMap<String, C>,
+) -> ReloadSummary {
+    let mut summary = ReloadSummary::default();
+
+    for (monitor_id, config) in desired {
+        match running.get(monitor_id) {
+            None => summary.added.push(monitor_id.clone()),
+            Some(current) if current != config => summary.updated.push(monitor_id.clone()),
+            Some(_) => {}
+        }
+    }
+    for monitor_id in running.keys() {
+        if !desired.contains_key(monitor_id) {
+            summary.removed.push(monitor_id.clone());
+        }
     }
+
+    summary.added.sort();
+    summary.removed.sort();
+    summary.updated.sort();
+    summary
 }
 
 impl Drop for Capture {
@@ -354,3 +781,40 @@ impl Drop for Capture {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_reload() {
+        let monitor = |interval| MonitorConfig {
+            enable: true,
+            interval,
+            enforce_interval: 30000,
+            dhash_resolution: 16,
+            dhash_threshold: 10,
+            adaptive: None,
+            change_detection: None,
+            draw_cursor: false,
+            capture_full: true,
+            regions: Vec::new(),
+        };
+        let running = HashMap::from([
+            ("kept".to_string(), monitor(1000)),
+            ("changed".to_string(), monitor(1000)),
+            ("removed".to_string(), monitor(1000)),
+        ]);
+        let desired = HashMap::from([
+            ("kept".to_string(), monitor(1000)),
+            ("changed".to_string(), monitor(2000)),
+            ("added".to_string(), monitor(1000)),
+        ]);
+
+        let summary = plan_reload(&running, &desired);
+        assert_eq!(summary.added, vec!["added"]);
+        assert_eq!(summary.removed, vec!["removed"]);
+        assert_eq!(summary.updated, vec!["changed"]);
+        assert!(plan_reload(&desired, &desired).is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use image::{RgbaImage, imageops};
+
+/// 读取到的鼠标指针
+pub struct Cursor {
+    /// 指针尖端的全局桌面坐标
+    pub x: i32,
+    pub y: i32,
+    pub image: RgbaImage,
+    /// 指针尖端在指针图像中的位置
+    pub hotspot: (u32, u32),
+}
+
+impl Cursor {
+    /// 把指针绘制到截图上,`origin` 为截图左上角的全局坐标,超出截图的部分被裁掉
+    pub fn draw(&self, image: &mut RgbaImage, origin: (i32, i32)) {
+        let x = self.x as i64 - origin.0 as i64 - self.hotspot.0 as i64;
+        let y = self.y as i64 - origin.1 as i64 - self.hotspot.1 as i64;
+        imageops::overlay(image, &self.image, x, y);
+    }
+}
+
+/// 鼠标指针读取器,保持与显示服务器的连接
+///
+/// 目前只支持 X11(XFixes 扩展),其他平台创建时返回错误
+pub struct CursorReader {
+    #[cfg(target_os = "linux")]
+    connection: xcb::Connection,
+}
+
+#[cfg(target_os = "linux")]
+impl CursorReader {
+    pub fn new() -> Result<Self> {
+        use xcb::xfixes;
+
+        // 作为可选扩展连接,缺少 XFixes 时返回错误而不是 panic
+        let (connection, _) =
+            xcb::Connection::connect_with_extensions(None, &[], &[xcb::Extension::XFixes])?;
+        if !connection
+            .active_extensions()
+            .any(|ext| ext == xcb::Extension::XFixes)
+        {
+            anyhow::bail!("X server does not support the XFixes extension");
+        }
+        // 使用 XFixes 的请求前必须先协商版本
+        let cookie = connection.send_request(&xfixes::QueryVersion {
+            client_major_version: 4,
+            client_minor_version: 0,
+        });
+        connection.wait_for_reply(cookie)?;
+        Ok(Self { connection })
+    }
+
+    pub fn read(&self) -> Result<Cursor> {
+        let cookie = self
+            .connection
+            .send_request(&xcb::xfixes::GetCursorImage {});
+        let reply = self.connection.wait_for_reply(cookie)?;
+
+        let (width, height) = (reply.width() as u32, reply.height() as u32);
+        let pixels = reply
+            .cursor_image()
+            .iter()
+            .flat_map(|&argb| unpremultiply(argb))
+            .collect();
+        let image = RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("Invalid cursor image {}x{}", width, height))?;
+
+        Ok(Cursor {
+            x: reply.x() as i32,
+            y: reply.y() as i32,
+            image,
+            hotspot: (reply.xhot() as u32, reply.yhot() as u32),
+        })
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl CursorReader {
+    pub fn new() -> Result<Self> {
+        anyhow::bail!("Reading the mouse cursor is not supported on this platform")
+    }
+
+    pub fn read(&self) -> Result<Cursor> {
+        anyhow::bail!("Reading the mouse cursor is not supported on this platform")
+    }
+}
+
+/// XFixes 返回预乘 alpha 的 ARGB 像素,转换为普通 RGBA
+fn unpremultiply(argb: u32) -> [u8; 4] {
+    let [a, r, g, b] = argb.to_be_bytes();
+    if a == 0 {
+        return [0, 0, 0, 0];
+    }
+    let channel = |c: u8| ((c as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8;
+    [channel(r), channel(g), channel(b), a]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_unpremultiply() {
+        assert_eq!(unpremultiply(0xff10_2030), [0x10, 0x20, 0x30, 0xff]);
+        assert_eq!(unpremultiply(0x8040_0000), [0x80, 0, 0, 0x80]);
+        assert_eq!(unpremultiply(0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_draw_relative_to_origin() {
+        let cursor = Cursor {
+            x: 2600,
+            y: 110,
+            image: RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255])),
+            hotspot: (1, 1),
+        };
+        let mut image = RgbaImage::new(100, 100);
+        cursor.draw(&mut image, (2560, 100));
+
+        assert_eq!(image.get_pixel(39, 9), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(42, 12), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(43, 13), &Rgba([0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::capture::DESKTOP_SOURCE_ID;
+use crate::capture::monitor::{Dedup, SafeMonitor};
+use crate::capture::utils::{dHash, stitch};
+use crate::config::DesktopConfig;
+use crate::event::CaptureResult;
+
+/// 虚拟桌面拼接截图
+///
+/// 同一时刻依次截取所有显示器,按各自左上角的全局坐标拼接成一张图,
+/// 对拼接后的整图去重。截图 ID 固定为 `desktop`
+pub struct SafeDesktop {
+    monitors: Vec<SafeMonitor>,
+    dedup: Dedup,
+}
+
+impl SafeDesktop {
+    /// 初始化失败的显示器会被跳过,全部失败时返回错误
+    pub fn new(monitor_ids: &[String]) -> Result<Self> {
+        let monitors: Vec<SafeMonitor> = monitor_ids
+            .iter()
+            .filter_map(|id| {
+                SafeMonitor::new(id.clone())
+                    .inspect_err(|e| warn!("Skipping monitor {} for desktop capture: {}", id, e))
+                    .ok()
+            })
+            .collect();
+        if monitors.is_empty() {
+            return Err(anyhow!("No monitor available for desktop capture"));
+        }
+
+        info!("SafeDesktop created with {} monitors", monitors.len());
+        Ok(Self {
+            monitors,
+            dedup: Dedup::default(),
+        })
+    }
+
+    /// 参与拼接的显示器ID
+    pub fn monitor_ids(&self) -> Vec<&str> {
+        self.monitors.iter().map(SafeMonitor::id).collect()
+    }
+
+    /// 截取并拼接一次,需要保存时返回拼接后的截图
+    ///
+    /// 任一显示器截图失败时整体失败,避免保存缺了一块的桌面
+    pub fn capture_once(&mut self, config: &DesktopConfig) -> Result<Option<CaptureResult>> {
+        let now = Utc::now();
+        info!("Starting desktop capture, {}", now);
+
+        let parts = self
+            .monitors
+            .iter()
+            .map(|monitor| Ok((monitor.origin(), monitor.capture_image()?)))
+            .collect::<Result<Vec<_>>>()?;
+        let image = stitch(&parts).ok_or_else(|| anyhow!("No monitor image to stitch"))?;
+
+        let dhash = dHash(&image, config.dhash_resolution);
+        info!(
+            "Captured desktop {}x{} with dHash {}",
+            image.width(),
+            image.height(),
+            dhash
+        );
+        let save = self
+            .dedup
+            .check(now, dhash, config.enforce_interval, config.dhash_threshold)?;
+        Ok(save.then(|| {
+            CaptureResult::new(DESKTOP_SOURCE_ID.to_string(), image, now)
+                .with_dedup(self.dedup.last_similar, config.enforce_interval)
+        }))
+    }
+
+    /// 最近一次截图与上次保存截图的 dHash 汉明距离
+    pub fn last_distance(&self) -> Option<u32> {
+        self.dedup.last_distance
+    }
+
+    /// 清空去重状态,下一次截图必定保存
+    pub fn reset(&mut self) {
+        self.dedup = Dedup::default();
+    }
+}
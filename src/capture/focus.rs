@@ -0,0 +1,279 @@
+use anyhow::Result;
+use std::thread;
+use std::time::Duration as StdDuration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{Duration, Instant, sleep_until};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use xcap::Window;
+
+use crate::capture::state::CaptureState;
+use crate::config::FocusConfig;
+
+/// 监听线程检查通道是否已关闭的间隔
+const LISTENER_POLL_INTERVAL: StdDuration = StdDuration::from_millis(50);
+
+/// 无法订阅焦点事件时轮询焦点窗口的间隔
+const FALLBACK_POLL_INTERVAL: StdDuration = StdDuration::from_millis(500);
+
+/// 一次焦点切换通知
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FocusChange {
+    /// 每次通知递增,保证订阅者都能收到
+    pub generation: u64,
+    /// 新焦点窗口的 ID
+    pub window: u32,
+    pub trigger_monitors: bool,
+}
+
+/// 截图任务一侧的焦点切换接收端
+pub struct FocusEvents {
+    rx: watch::Receiver<FocusChange>,
+    /// 显示器与桌面任务只响应 `trigger_monitors` 的通知
+    monitors: bool,
+}
+
+impl FocusEvents {
+    pub fn new(rx: watch::Receiver<FocusChange>, monitors: bool) -> Self {
+        Self { rx, monitors }
+    }
+
+    /// 等待下一次与本任务相关的焦点切换
+    pub async fn changed(&mut self) {
+        loop {
+            if self.rx.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+            if !self.monitors || self.rx.borrow_and_update().trigger_monitors {
+                return;
+            }
+        }
+    }
+}
+
+/// 焦点监视任务
+///
+/// 收到焦点切换后等待 `settle_delay`,期间没有再次切换才通过 `CaptureState` 通知
+/// 截图任务。配置变化时重新启动监听
+pub async fn run_focus_watcher(
+    mut config_rx: watch::Receiver<FocusConfig>,
+    state: CaptureState,
+    cancel_token: CancellationToken,
+) {
+    info!("Focus watcher started");
+
+    'watch: loop {
+        let config = config_rx.borrow_and_update().clone();
+        if !config.enable {
+            tokio::select! {
+                Ok(()) = config_rx.changed() => continue,
+                _ = cancel_token.cancelled() => break,
+            }
+        }
+
+        // 接收端在本轮结束时丢弃,监听线程随之退出
+        let (tx, mut rx) = mpsc::channel(16);
+        spawn_listener(tx);
+        info!("Watching window focus changes: {}", config);
+
+        let settle_delay = Duration::from_millis(config.settle_delay);
+        let mut pending: Option<(u32, Instant)> = None;
+        loop {
+            let deadline = pending.map(|(_, deadline)| deadline);
+            tokio::select! {
+                Some(window) = rx.recv() => {
+                    debug!("Focus changed to window {}", window);
+                    pending = Some((window, Instant::now() + settle_delay));
+                }
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    if let Some((window, _)) = pending.take() {
+                        state.notify_focus_change(window, config.trigger_monitors);
+                    }
+                }
+                Ok(()) = config_rx.changed() => continue 'watch,
+                _ = cancel_token.cancelled() => break 'watch,
+            }
+        }
+    }
+
+    info!("Focus watcher terminated");
+}
+
+/// 在后台线程中监听焦点窗口,新焦点窗口的 ID 发送到 `tx`
+///
+/// 优先订阅 X11 的 `_NET_ACTIVE_WINDOW` 变化,不可用时退回到轮询
+fn spawn_listener(tx: mpsc::Sender<u32>) {
+    #[cfg(target_os = "linux")]
+    match x11::Listener::new() {
+        Ok(listener) => {
+            spawn_thread("focus-x11", move || listener.run(tx));
+            return;
+        }
+        Err(e) => warn!(
+            "Focus events are unavailable ({}), polling every {}ms",
+            e,
+            FALLBACK_POLL_INTERVAL.as_millis()
+        ),
+    }
+
+    spawn_thread("focus-poll", move || poll_focus(tx));
+}
+
+/// 监听线程在接收端关闭后自行退出,不阻塞进程退出
+fn spawn_thread(name: &str, f: impl FnOnce() + Send + 'static) {
+    if let Err(e) = thread::Builder::new().name(name.to_string()).spawn(f) {
+        warn!("Failed to start {} thread: {}", name, e);
+    }
+}
+
+/// 轮询 xcap 的窗口列表,直到接收端关闭
+fn poll_focus(tx: mpsc::Sender<u32>) {
+    let mut last = None;
+    while !tx.is_closed() {
+        let focused = Window::all().map(|windows| {
+            windows
+                .iter()
+                .find(|window| window.is_focused().unwrap_or(false))
+                .and_then(|window| window.id().ok())
+        });
+        match focused {
+            Ok(Some(window)) if last != Some(window) => {
+                last = Some(window);
+                if tx.blocking_send(window).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!(
+                    "Failed to poll focused window, focus trigger disabled: {}",
+                    e
+                );
+                break;
+            }
+        }
+        thread::sleep(FALLBACK_POLL_INTERVAL);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use super::*;
+    use xcb::{Xid, x};
+
+    /// 订阅根窗口 `_NET_ACTIVE_WINDOW` 属性变化的连接
+    pub(super) struct Listener {
+        connection: xcb::Connection,
+        root: x::Window,
+        active_window: x::Atom,
+    }
+
+    impl Listener {
+        pub(super) fn new() -> Result<Self> {
+            let (connection, screen) = xcb::Connection::connect(None)?;
+            let root = connection
+                .get_setup()
+                .roots()
+                .nth(screen as usize)
+                .ok_or_else(|| anyhow::anyhow!("X11 screen {} not found", screen))?
+                .root();
+
+            let cookie = connection.send_request(&x::InternAtom {
+                only_if_exists: true,
+                name: b"_NET_ACTIVE_WINDOW",
+            });
+            let active_window = connection.wait_for_reply(cookie)?.atom();
+            anyhow::ensure!(
+                active_window != x::ATOM_NONE,
+                "window manager does not support _NET_ACTIVE_WINDOW"
+            );
+
+            connection.send_and_check_request(&x::ChangeWindowAttributes {
+                window: root,
+                value_list: &[x::Cw::EventMask(x::EventMask::PROPERTY_CHANGE)],
+            })?;
+
+            Ok(Self {
+                connection,
+                root,
+                active_window,
+            })
+        }
+
+        fn current(&self) -> Result<Option<u32>> {
+            let cookie = self.connection.send_request(&x::GetProperty {
+                delete: false,
+                window: self.root,
+                property: self.active_window,
+                r#type: x::ATOM_WINDOW,
+                long_offset: 0,
+                long_length: 1,
+            });
+            let reply = self.connection.wait_for_reply(cookie)?;
+            Ok(reply
+                .value::<x::Window>()
+                .first()
+                .filter(|window| !window.is_none())
+                .map(|window| window.resource_id()))
+        }
+
+        /// 把焦点变化发送到 `tx`,直到接收端关闭
+        pub(super) fn run(self, tx: mpsc::Sender<u32>) {
+            let mut last = self.current().ok().flatten();
+            loop {
+                match self.connection.poll_for_event() {
+                    Ok(Some(xcb::Event::X(x::Event::PropertyNotify(event))))
+                        if event.atom() == self.active_window =>
+                    {
+                        let window = match self.current() {
+                            Ok(window) => window,
+                            Err(e) => {
+                                debug!("Failed to read active window: {}", e);
+                                continue;
+                            }
+                        };
+                        if let Some(window) = window.filter(|&window| last != Some(window)) {
+                            last = Some(window);
+                            if tx.blocking_send(window).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        if tx.is_closed() {
+                            break;
+                        }
+                        thread::sleep(LISTENER_POLL_INTERVAL);
+                    }
+                    Err(e) => {
+                        warn!("Focus listener stopped: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_monitor_tasks_skip_window_only_changes() {
+        let state = CaptureState::new();
+        let mut window = state.subscribe_focus(false);
+        let mut monitors = state.subscribe_focus(true);
+
+        state.notify_focus_change(1, false);
+        window.changed().await;
+        let woken = tokio::time::timeout(Duration::from_millis(20), monitors.changed()).await;
+        assert!(woken.is_err());
+
+        state.notify_focus_change(2, true);
+        window.changed().await;
+        monitors.changed().await;
+        assert_eq!(monitors.rx.borrow().window, 2);
+    }
+}
@@ -1,8 +1,18 @@
+pub mod adaptive;
 pub mod capture;
+pub mod cursor;
+pub mod desktop;
+pub mod focus;
 pub mod monitor;
+pub mod rules;
+pub mod scheduler;
+pub mod state;
+pub mod task;
 pub mod utils;
 pub mod window;
 
 pub use capture::*;
 pub use monitor::*;
+pub use rules::*;
+pub use state::*;
 pub use window::*;
@@ -1,18 +1,146 @@
 use anyhow::{Result, anyhow};
 use chrono::{DateTime, Utc};
+use image::RgbaImage;
+use serde::Serialize;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 use std::sync::LazyLock;
-use tracing::info;
+use tracing::{debug, info, warn};
 use xcap::Monitor;
 
-use crate::capture::utils::hamming_distance;
-use crate::event::CaptureResult;
+use crate::capture::cursor::{Cursor, CursorReader};
+use crate::capture::utils::{TileHashes, apply_masks, crop, dHash, hamming_distance, thumbnail};
+use crate::config::{ChangeDetectionConfig, MonitorConfig, RegionConfig};
+use crate::event::{CaptureResult, ChangedArea, CursorPosition};
 
 pub struct SafeMonitor {
     id: String,
     monitor: Monitor,
+    /// 显示器左上角的全局坐标与尺寸,来自显示器 ID
+    origin: (i32, i32),
+    size: (u32, u32),
+    /// 首次保存截图时连接,不支持的平台上为 `None`
+    cursor: OnceCell<Option<CursorReader>>,
 
+    /// 整屏截图的去重状态
+    full: Dedup,
+    /// 各裁剪区域的去重状态,按区域名称索引
+    regions: HashMap<String, Dedup>,
+    last_distance: Option<u32>,
+}
+
+/// 单路截图基于时间与图像相似度的去重状态
+#[derive(Debug, Default)]
+pub(crate) struct Dedup {
     last_capture_time: Option<DateTime<Utc>>,
     last_capture_dhash: Option<u64>,
+    /// 启用分块变化检测时,上次保存的截图的分块 dHash
+    last_tiles: Option<TileHashes>,
+    pub(crate) last_distance: Option<u32>,
+    /// 最近一次截图是否与上次保存的截图相似
+    pub(crate) last_similar: bool,
+}
+
+/// 一次分块变化检测的结果
+struct TileCheck {
+    save: bool,
+    changed_area: Option<ChangedArea>,
+    /// 与上次保存的截图逐块比较的距离,首次截图时为 `None`
+    distances: Option<Vec<u32>>,
+    tiles: TileHashes,
+}
+
+impl Dedup {
+    /// 判断本次截图是否需要保存,需要时记录为最近一次保存的截图
+    ///
+    /// 距上次保存不足 `enforce_interval` 且汉明距离小于 `dhash_threshold` 时跳过
+    pub(crate) fn check(
+        &mut self,
+        now: DateTime<Utc>,
+        dhash: u64,
+        enforce_interval: u64,
+        dhash_threshold: u32,
+    ) -> Result<bool> {
+        self.last_distance = self
+            .last_capture_dhash
+            .map(|last_hash| hamming_distance(dhash, last_hash));
+        let similar = self.last_distance.is_some_and(|d| d < dhash_threshold);
+        self.decide(now, dhash, similar, enforce_interval)
+    }
+
+    /// 按分块变化判断是否保存,整屏 dHash 距离仍会记录,供自适应间隔使用
+    fn check_tiles(
+        &mut self,
+        now: DateTime<Utc>,
+        dhash: u64,
+        tiles: TileHashes,
+        enforce_interval: u64,
+        detection: &ChangeDetectionConfig,
+    ) -> Result<TileCheck> {
+        self.last_distance = self
+            .last_capture_dhash
+            .map(|last_hash| hamming_distance(dhash, last_hash));
+        let distances = self
+            .last_tiles
+            .as_ref()
+            .and_then(|last| last.distances(&tiles));
+        let changed_area = distances
+            .as_ref()
+            .and_then(|d| tiles.changed_area(d, detection.tile_threshold));
+        // 无法比较(首次截图或分辨率变化)时视为有变化
+        let similar = distances.is_some()
+            && changed_area.is_none_or(|area| area.changed_tiles < detection.min_changed_tiles);
+
+        let save = self.decide(now, dhash, similar, enforce_interval)?;
+        if save {
+            self.last_tiles = Some(tiles.clone());
+        }
+        Ok(TileCheck {
+            save,
+            changed_area,
+            distances,
+            tiles,
+        })
+    }
+
+    /// 画面相似且距上次保存不足 `enforce_interval` 时跳过,否则记录为最近一次保存的截图
+    fn decide(
+        &mut self,
+        now: DateTime<Utc>,
+        dhash: u64,
+        similar: bool,
+        enforce_interval: u64,
+    ) -> Result<bool> {
+        self.last_similar = similar;
+        if let Some(last_time) = self.last_capture_time {
+            let delta = (now - last_time).num_milliseconds();
+            if delta < 0 {
+                self.last_capture_time = Some(now);
+                return Err(anyhow!("Clock went backwards, reset to {}", now));
+            }
+            let delta = delta as u64;
+            let time_too_soon = delta < enforce_interval;
+
+            info!(
+                "Time too soon: {}, hash too similar: {}",
+                time_too_soon, similar
+            );
+            if time_too_soon && similar {
+                return Ok(false);
+            }
+        }
+
+        self.last_capture_time = Some(now);
+        self.last_capture_dhash = Some(dhash);
+        Ok(true)
+    }
+}
+
+/// 区域截图的 ID
+pub fn region_capture_id(monitor_id: &str, region: &str) -> String {
+    format!("{}@{}", monitor_id, region)
 }
 
 // SAFETY: Monitor 的底层句柄在单个任务中独占使用,不会跨线程共享
@@ -24,6 +152,62 @@ static RE: LazyLock<regex::Regex> = LazyLock::new(|| {
         .expect("invalid regex")
 });
 
+/// 按 `name_width_height_x_y` 格式生成显示器 ID
+pub fn monitor_id(name: &str, width: u32, height: u32, x: i32, y: i32) -> String {
+    format!("{}_{}_{}_{}_{}", name, width, height, x, y)
+}
+
+/// 已连接显示器的属性,用于 `list-monitors`
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorInfo {
+    pub id: String,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+    pub scale_factor: f32,
+    pub rotation: f32,
+    /// 刷新率(Hz)
+    pub frequency: f32,
+    pub is_primary: bool,
+}
+
+impl MonitorInfo {
+    pub(crate) fn from_monitor(monitor: &Monitor) -> Result<Self> {
+        let name = monitor.name()?;
+        let (width, height, x, y) = (
+            monitor.width()?,
+            monitor.height()?,
+            monitor.x()?,
+            monitor.y()?,
+        );
+        Ok(Self {
+            id: monitor_id(&name, width, height, x, y),
+            name,
+            width,
+            height,
+            x,
+            y,
+            scale_factor: monitor.scale_factor()?,
+            rotation: monitor.rotation()?,
+            frequency: monitor.frequency()?,
+            is_primary: monitor.is_primary()?,
+        })
+    }
+
+    /// 所有已连接的显示器
+    pub fn all() -> Result<Vec<Self>> {
+        Monitor::all()?.iter().map(Self::from_monitor).collect()
+    }
+
+    /// 截取整个显示器并缩放为长边不超过 `max_size` 的缩略图
+    pub fn capture_thumbnail(&self, max_size: u32) -> Result<RgbaImage> {
+        let image = SafeMonitor::new(self.id.clone())?.capture_image()?;
+        Ok(thumbnail(&image, max_size))
+    }
+}
+
 impl SafeMonitor {
     pub fn new(monitor_id: String) -> Result<Self> {
         info!("Creating SafeMonitor for {}", monitor_id);
@@ -53,8 +237,12 @@ impl SafeMonitor {
         Ok(SafeMonitor {
             id: monitor_id,
             monitor: monitor,
-            last_capture_time: None,
-            last_capture_dhash: None,
+            origin: (x, y),
+            size: (width, height),
+            cursor: OnceCell::new(),
+            full: Dedup::default(),
+            regions: HashMap::new(),
+            last_distance: None,
         })
     }
 
@@ -70,57 +258,223 @@ impl SafeMonitor {
         Ok((name, width, height, x, y))
     }
 
-    pub fn capture_once(
-        &mut self,
-        enforce_interval: u64,
-        dhash_threshold: u32,
-        dhash_resolution: u32,
-    ) -> Result<Option<CaptureResult>> {
+    /// 截取整个显示器一次,并按配置裁剪出各个区域
+    ///
+    /// 整屏与每个区域分别去重,返回需要保存的截图。
+    /// 区域截图的 ID 格式为 `{monitor_id}@{region}`
+    pub fn capture_once(&mut self, config: &MonitorConfig) -> Result<Vec<CaptureResult>> {
         let now = Utc::now();
         info!("Starting capture in {}, {}", self.id, now);
 
-        let image = self
-            .monitor
-            .capture_image()
-            .map_err(|e| anyhow!("Failed to capture image: {}", e))?;
+        let image = self.capture_image()?;
 
-        let dhash = crate::capture::utils::dHash(&image, dhash_resolution);
-        info!("Captured image with dHash {}", dhash);
+        let mut results = Vec::new();
+        let mut distances = Vec::new();
 
-        if let Some(last_time) = self.last_capture_time {
-            if let Some(last_hash) = self.last_capture_dhash {
-                let delta = (now - last_time).num_milliseconds();
-                if delta < 0 {
-                    self.last_capture_time = Some(now);
-                    return Err(anyhow!("Clock went backwards, reset to {}", now));
-                }
-                let delta = delta as u64;
-                let time_too_soon = delta < enforce_interval;
+        // 已从配置中删除的区域不再保留去重状态
+        self.regions
+            .retain(|name, _| config.regions.iter().any(|region| &region.name == name));
+        for region in &config.regions {
+            if let Some(result) = self.capture_region(&image, region, config, now)? {
+                // 附带截图左上角相对显示器的偏移,用于绘制指针
+                results.push((result, (region.x, region.y)));
+            }
+            distances.extend(self.regions[&region.name].last_distance);
+        }
 
-                let hash_too_similar = hamming_distance(dhash, last_hash) < dhash_threshold;
+        if config.capture_full {
+            let dhash = dHash(&image, config.dhash_resolution);
+            info!("Captured image with dHash {}", dhash);
+            let (save, changed_area) = match &config.change_detection {
+                Some(detection) => {
+                    let check = self.check_tiles(&image, now, dhash, config, detection)?;
+                    (check.save, check.changed_area)
+                }
+                None => {
+                    let save = self.full.check(
+                        now,
+                        dhash,
+                        config.enforce_interval,
+                        config.dhash_threshold,
+                    )?;
+                    (save, None)
+                }
+            };
+            distances.extend(self.full.last_distance);
+            if save {
                 info!(
-                    "Time too soon: {}, hash too similar: {}",
-                    time_too_soon, hash_too_similar
+                    "Current capture in {} should save which dHash is {}",
+                    self.id, dhash
                 );
-                if time_too_soon && hash_too_similar {
-                    return Ok(None);
+                results.push((
+                    CaptureResult::new(self.id.clone(), image, now)
+                        .with_changed_area(changed_area)
+                        .with_dedup(self.full.last_similar, config.enforce_interval),
+                    (0, 0),
+                ));
+            }
+        }
+
+        // 自适应间隔按变化最大的一路计算
+        self.last_distance = distances.into_iter().max();
+
+        // 去重之后再读取指针,指针移动不会触发保存
+        let cursor = if results.is_empty() {
+            None
+        } else {
+            self.read_cursor()
+        };
+        let position = cursor.as_ref().map(|cursor| self.cursor_position(cursor));
+        Ok(results
+            .into_iter()
+            .map(|(mut result, (x, y))| {
+                if let (true, Some(cursor)) = (config.draw_cursor, &cursor) {
+                    let origin = (self.origin.0 + x as i32, self.origin.1 + y as i32);
+                    cursor.draw(&mut result.image, origin);
                 }
+                result.with_cursor(position)
+            })
+            .collect())
+    }
+
+    /// 读取当前鼠标指针,失败时只记录日志
+    fn read_cursor(&self) -> Option<Cursor> {
+        let reader = self.cursor.get_or_init(|| {
+            CursorReader::new()
+                .inspect_err(|e| warn!("Mouse cursor is unavailable for {}: {}", self.id, e))
+                .ok()
+        });
+        reader
+            .as_ref()?
+            .read()
+            .inspect_err(|e| debug!("Failed to read mouse cursor: {}", e))
+            .ok()
+    }
+
+    fn cursor_position(&self, cursor: &Cursor) -> CursorPosition {
+        let monitor_x = cursor.x - self.origin.0;
+        let monitor_y = cursor.y - self.origin.1;
+        CursorPosition {
+            x: cursor.x,
+            y: cursor.y,
+            monitor_x,
+            monitor_y,
+            on_monitor: (0..self.size.0 as i32).contains(&monitor_x)
+                && (0..self.size.1 as i32).contains(&monitor_y),
+        }
+    }
+
+    /// 遮罩后按分块检测变化,配置了 `heatmap_dir` 时保存有变化的热力图
+    fn check_tiles(
+        &mut self,
+        image: &RgbaImage,
+        now: DateTime<Utc>,
+        dhash: u64,
+        config: &MonitorConfig,
+        detection: &ChangeDetectionConfig,
+    ) -> Result<TileCheck> {
+        let tiles = if detection.masks.is_empty() {
+            TileHashes::new(image, detection.columns, detection.rows)?
+        } else {
+            let masks: Vec<_> = detection
+                .masks
+                .iter()
+                .map(|m| (m.x, m.y, m.width, m.height))
+                .collect();
+            let mut masked = image.clone();
+            apply_masks(&mut masked, &masks);
+            TileHashes::new(&masked, detection.columns, detection.rows)?
+        };
+
+        let check = self
+            .full
+            .check_tiles(now, dhash, tiles, config.enforce_interval, detection)?;
+
+        let changed = check
+            .distances
+            .as_ref()
+            .filter(|distances| distances.iter().any(|&d| d > 0));
+        if let (false, Some(distances)) = (detection.heatmap_dir.is_empty(), changed) {
+            let path = Path::new(&detection.heatmap_dir).join(format!(
+                "{}_{}_heatmap.png",
+                self.id,
+                now.format("%Y%m%d_%H%M%S%.3f")
+            ));
+            let heatmap = check
+                .tiles
+                .heatmap(image, distances, detection.tile_threshold);
+            let saved = fs::create_dir_all(&detection.heatmap_dir)
+                .map_err(anyhow::Error::from)
+                .and_then(|()| Ok(heatmap.save(&path)?));
+            if let Err(e) = saved {
+                warn!("Failed to save heatmap {}: {}", path.display(), e);
             }
         }
-        info!(
-            "Current capture in {} should save which dHash is {}",
-            self.id, dhash
-        );
 
-        self.last_capture_time = Some(now);
-        self.last_capture_dhash = Some(dhash);
+        Ok(check)
+    }
+
+    fn capture_region(
+        &mut self,
+        image: &RgbaImage,
+        region: &RegionConfig,
+        config: &MonitorConfig,
+        now: DateTime<Utc>,
+    ) -> Result<Option<CaptureResult>> {
+        let state = self.regions.entry(region.name.clone()).or_default();
+        let Some(cropped) = crop(image, region.x, region.y, region.width, region.height) else {
+            warn!(
+                "Region {} is outside of monitor {} ({}x{}), skipping",
+                region.name,
+                self.id,
+                image.width(),
+                image.height()
+            );
+            return Ok(None);
+        };
+
+        let dhash = dHash(
+            &cropped,
+            region.dhash_resolution.unwrap_or(config.dhash_resolution),
+        );
+        let save = state.check(
+            now,
+            dhash,
+            config.enforce_interval,
+            region.dhash_threshold.unwrap_or(config.dhash_threshold),
+        )?;
+        Ok(save.then(|| {
+            CaptureResult::new(region_capture_id(&self.id, &region.name), cropped, now)
+                .with_dedup(state.last_similar, config.enforce_interval)
+        }))
+    }
 
-        Ok(Some(CaptureResult::new(self.id.clone(), image, now)))
+    /// 截取整个显示器,不做去重
+    pub fn capture_image(&self) -> Result<RgbaImage> {
+        self.monitor
+            .capture_image()
+            .map_err(|e| anyhow!("Failed to capture image: {}", e))
     }
 
     pub fn id(&self) -> &str {
         &self.id
     }
+
+    /// 显示器左上角的全局坐标
+    pub fn origin(&self) -> (i32, i32) {
+        self.origin
+    }
+
+    /// 最近一次截图与上次保存截图的 dHash 汉明距离
+    pub fn last_distance(&self) -> Option<u32> {
+        self.last_distance
+    }
+
+    /// 清空去重状态,下一次截图必定保存
+    pub fn reset(&mut self) {
+        self.full = Dedup::default();
+        self.regions.clear();
+    }
 }
 
 #[cfg(test)]
@@ -136,4 +490,32 @@ mod tests {
         assert_eq!(x, 0);
         assert_eq!(y, 0);
     }
+
+    #[test]
+    fn test_dedup() {
+        let mut dedup = Dedup::default();
+        let start = Utc::now();
+        let after = |ms| start + chrono::Duration::milliseconds(ms);
+
+        assert!(dedup.check(start, 0, 30000, 10).unwrap());
+        // 画面相似且未到强制间隔
+        assert!(!dedup.check(after(1000), 0b11, 30000, 10).unwrap());
+        assert_eq!(dedup.last_distance, Some(2));
+        // 画面变化超过阈值
+        assert!(dedup.check(after(2000), u64::MAX, 30000, 10).unwrap());
+        // 到达强制间隔
+        assert!(dedup.check(after(40000), u64::MAX, 30000, 10).unwrap());
+        assert!(dedup.check(after(0), u64::MAX, 30000, 10).is_err());
+    }
+
+    #[test]
+    fn test_monitor_id_round_trip() {
+        let id = monitor_id("GS27QK", 2560, 1440, -2560, 0);
+        assert_eq!(id, "GS27QK_2560_1440_-2560_0");
+        let (name, width, height, x, y) = SafeMonitor::parse_id(&id).unwrap();
+        assert_eq!(
+            (name.as_str(), width, height, x, y),
+            ("GS27QK", 2560, 1440, -2560, 0)
+        );
+    }
 }
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::config::{WindowRule, WindowTarget, WindowsConfig};
+
+/// 编译后的窗口匹配规则
+#[derive(Debug, Clone)]
+pub struct WindowMatcher {
+    label: String,
+    app: Option<Regex>,
+    title: Option<Regex>,
+}
+
+impl WindowMatcher {
+    /// `label` 用于在日志与 `list-windows --test-rules` 中标识规则
+    pub fn new(label: String, rule: &WindowRule) -> Result<Self> {
+        let compile = |pattern: &str, field: &str| -> Result<Option<Regex>> {
+            if pattern.is_empty() {
+                return Ok(None);
+            }
+            Regex::new(pattern)
+                .map(Some)
+                .with_context(|| format!("规则 {} 的 {} 不是有效的正则表达式", label, field))
+        };
+        let app = compile(&rule.app, "app")?;
+        let title = compile(&rule.title, "title")?;
+        anyhow::ensure!(
+            app.is_some() || title.is_some(),
+            "规则 {} 至少需要配置 app 或 title",
+            label
+        );
+
+        Ok(Self { label, app, title })
+    }
+
+    /// `[[windows.targets]]` 中目标的匹配条件
+    pub fn for_target(target: &WindowTarget) -> Result<Self> {
+        Self::new(rule_label("targets", 0, &target.rule()), &target.rule())
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn matches(&self, app_name: &str, title: &str) -> bool {
+        self.app.as_ref().is_none_or(|re| re.is_match(app_name))
+            && self.title.as_ref().is_none_or(|re| re.is_match(title))
+    }
+}
+
+/// `[windows]` 配置段中的全部规则
+#[derive(Debug, Clone, Default)]
+pub struct WindowRules {
+    exclude: Vec<WindowMatcher>,
+    targets: Vec<WindowMatcher>,
+}
+
+impl WindowRules {
+    pub fn new(config: &WindowsConfig) -> Result<Self> {
+        let exclude = compile_rules("exclude", &config.exclude)?;
+        let targets = config
+            .targets
+            .iter()
+            .map(WindowMatcher::for_target)
+            .collect::<Result<_>>()?;
+        Ok(Self { exclude, targets })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exclude.is_empty() && self.targets.is_empty()
+    }
+
+    /// 窗口是否命中隐私规则,命中的窗口不截图
+    pub fn is_excluded(&self, app_name: &str, title: &str) -> bool {
+        self.exclude.iter().any(|m| m.matches(app_name, title))
+    }
+
+    /// 窗口命中的所有规则名称,隐私规则在前
+    pub fn matching(&self, app_name: &str, title: &str) -> Vec<String> {
+        self.exclude
+            .iter()
+            .chain(&self.targets)
+            .filter(|m| m.matches(app_name, title))
+            .map(|m| m.label().to_string())
+            .collect()
+    }
+}
+
+/// 未命名的规则以 `<kind>[<序号>]` 标识
+pub(crate) fn rule_label(kind: &str, index: usize, rule: &WindowRule) -> String {
+    if rule.name.is_empty() {
+        format!("{}[{}]", kind, index)
+    } else {
+        format!("{}:{}", kind, rule.name)
+    }
+}
+
+fn compile_rules(kind: &str, rules: &[WindowRule]) -> Result<Vec<WindowMatcher>> {
+    rules
+        .iter()
+        .enumerate()
+        .map(|(index, rule)| WindowMatcher::new(rule_label(kind, index, rule), rule))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, app: &str, title: &str) -> WindowRule {
+        WindowRule {
+            name: name.to_string(),
+            app: app.to_string(),
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_matcher_requires_all_patterns() {
+        let matcher =
+            WindowMatcher::new("bank".to_string(), &rule("", "(?i)firefox", "Bank")).unwrap();
+        assert!(matcher.matches("Firefox", "My Bank - Login"));
+        assert!(!matcher.matches("Firefox", "News"));
+        assert!(!matcher.matches("chromium", "My Bank"));
+
+        assert!(WindowMatcher::new("empty".to_string(), &rule("", "", "")).is_err());
+        assert!(WindowMatcher::new("bad".to_string(), &rule("", "(", "")).is_err());
+    }
+
+    #[test]
+    fn test_rules_report_labels() {
+        let config = WindowsConfig {
+            exclude: vec![rule("passwords", "KeePassXC", ""), rule("", "", "Private")],
+            targets: vec![WindowTarget {
+                name: "grafana".to_string(),
+                title: "Grafana".to_string(),
+                ..WindowTarget::default()
+            }],
+        };
+        let rules = WindowRules::new(&config).unwrap();
+
+        assert!(rules.is_excluded("KeePassXC", "Database"));
+        assert_eq!(
+            rules.matching("firefox", "Private Browsing"),
+            vec!["exclude[1]".to_string()]
+        );
+        assert_eq!(
+            rules.matching("KeePassXC", "Private"),
+            vec!["exclude:passwords".to_string(), "exclude[1]".to_string()]
+        );
+        assert_eq!(
+            rules.matching("firefox", "Private Grafana"),
+            vec!["exclude[1]".to_string(), "targets:grafana".to_string()]
+        );
+        assert!(rules.matching("code", "main.rs").is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::Utc;
+use tokio::sync::watch;
+use tokio::time::{Duration, sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::capture::state::CaptureState;
+use crate::config::{IdleConfig, ScheduleConfig};
+
+/// 共享时钟的一个节拍
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    /// 节拍时刻(Unix 毫秒),作为同一节拍截图的分组标记
+    pub at: i64,
+    /// 距下一个节拍的间隔(毫秒),已计入 AFK 调整
+    pub interval: u64,
+}
+
+/// 截图任务一侧的时钟接收端
+///
+/// 未启用同步时按任务自己的间隔等待,启用后等待共享时钟的下一个节拍
+#[derive(Debug, Clone)]
+pub struct Ticks {
+    rx: watch::Receiver<Option<Tick>>,
+}
+
+impl Ticks {
+    pub fn new(rx: watch::Receiver<Option<Tick>>) -> Self {
+        Self { rx }
+    }
+
+    /// 同步模式下返回时钟的间隔,否则返回 `interval`
+    pub fn interval(&self, interval: u64) -> u64 {
+        self.rx.borrow().map_or(interval, |tick| tick.interval)
+    }
+
+    /// 等待下一次截图的时机,由共享时钟唤醒时返回该节拍
+    ///
+    /// 截图耗时超过节拍间隔时跳过错过的节拍,不会连续补拍
+    pub async fn wait(&mut self, interval: u64) -> Option<Tick> {
+        if self.rx.borrow_and_update().is_none() {
+            sleep(Duration::from_millis(interval)).await;
+            return None;
+        }
+        match self.rx.changed().await {
+            Ok(()) => *self.rx.borrow_and_update(),
+            // 时钟已关闭,等待任务被取消
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
+
+/// 共享时钟任务
+///
+/// `synchronized` 关闭时广播 `None`,各任务回到自己的间隔;开启时按 `interval`
+/// (AFK 期间按 `idle_interval`)广播节拍
+pub async fn run_clock(
+    mut schedule_rx: watch::Receiver<ScheduleConfig>,
+    mut idle_rx: watch::Receiver<IdleConfig>,
+    state: CaptureState,
+    cancel_token: CancellationToken,
+) {
+    let mut afk_rx = state.subscribe_afk();
+    info!("Capture clock started");
+
+    loop {
+        let schedule = schedule_rx.borrow_and_update().clone();
+        if !schedule.synchronized {
+            state.set_tick(None);
+            tokio::select! {
+                Ok(()) = schedule_rx.changed() => continue,
+                _ = cancel_token.cancelled() => break,
+            }
+        }
+
+        let idle = idle_rx.borrow_and_update().clone();
+        let afk = idle.enable && state.is_afk();
+        let interval = if afk && idle.idle_interval > 0 {
+            idle.idle_interval
+        } else {
+            schedule.interval
+        };
+        let tick = Tick {
+            at: Utc::now().timestamp_millis(),
+            interval,
+        };
+        debug!("Capture clock tick {}", tick.at);
+        state.set_tick(Some(tick));
+
+        tokio::select! {
+            _ = sleep(Duration::from_millis(interval)) => {}
+            // AFK 状态或配置变化时立即重新调度
+            Ok(()) = afk_rx.changed() => {}
+            Ok(()) = idle_rx.changed() => {}
+            Ok(()) = schedule_rx.changed() => {}
+            _ = cancel_token.cancelled() => break,
+        }
+    }
+
+    state.set_tick(None);
+    info!("Capture clock terminated");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ticks_follow_clock() {
+        let (tick_tx, tick_rx) = watch::channel(None);
+        let mut ticks = Ticks::new(tick_rx);
+
+        // 未同步时按自己的间隔等待
+        assert_eq!(ticks.interval(500), 500);
+        assert_eq!(ticks.wait(1).await, None);
+
+        let tick = Tick {
+            at: 1,
+            interval: 1000,
+        };
+        tick_tx.send_replace(Some(tick));
+        assert_eq!(ticks.interval(500), 1000);
+
+        // 已发出的节拍不会再次唤醒,等待的是下一个节拍
+        let next = Tick { at: 2, ..tick };
+        let (woken, ()) = tokio::join!(ticks.wait(500), async {
+            tokio::task::yield_now().await;
+            tick_tx.send_replace(Some(next));
+        });
+        assert_eq!(woken, Some(next));
+    }
+}
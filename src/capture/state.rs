@@ -0,0 +1,361 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::WeakSender;
+use tokio::sync::watch;
+use tracing::{debug, info};
+
+use crate::capture::focus::{FocusChange, FocusEvents};
+use crate::capture::scheduler::{Tick, Ticks};
+use crate::event::{CaptureResult, WindowActivity};
+
+/// 截图任务的暂停状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum PauseState {
+    Running,
+    /// `until` 为 `None` 表示无限期暂停,直到手动恢复
+    Paused {
+        until: Option<DateTime<Utc>>,
+    },
+}
+
+/// 单个截图来源(显示器或窗口)的运行统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceStatus {
+    /// 任务是否仍在运行(连续错误过多或被取消后为 `false`)
+    pub running: bool,
+    /// 当前生效的检查间隔(毫秒),已计入自适应与 AFK 调整
+    pub interval: Option<u64>,
+    pub last_capture: Option<DateTime<Utc>>,
+    /// 最近一次与上次保存截图的 dHash 汉明距离
+    pub last_distance: Option<u32>,
+    pub captures: u64,
+    pub skips: u64,
+    pub consecutive_errors: u32,
+    pub last_error: Option<String>,
+}
+
+/// 存储端的写入统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageHealth {
+    pub saved: u64,
+    pub failed: u64,
+    pub last_saved: Option<DateTime<Utc>>,
+    /// 最近一次写入失败的原因,成功写入后清空
+    pub last_error: Option<String>,
+}
+
+impl StorageHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.last_error.is_none()
+    }
+}
+
+/// 运行中的 `Capture` 与外部控制端共享的状态
+///
+/// 克隆开销很小,所有克隆指向同一份状态
+#[derive(Clone)]
+pub struct CaptureState {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    started_at: DateTime<Utc>,
+    pause_tx: watch::Sender<PauseState>,
+    snapshot_tx: watch::Sender<u64>,
+    afk_tx: watch::Sender<bool>,
+    tick_tx: watch::Sender<Option<Tick>>,
+    focus_tx: watch::Sender<FocusChange>,
+    activity_tx: watch::Sender<Option<WindowActivity>>,
+    sources: Mutex<BTreeMap<String, SourceStatus>>,
+    storage: Mutex<StorageHealth>,
+    queue: Mutex<Option<WeakSender<CaptureResult>>>,
+}
+
+impl CaptureState {
+    pub fn new() -> Self {
+        let (pause_tx, _) = watch::channel(PauseState::Running);
+        let (snapshot_tx, _) = watch::channel(0);
+        let (afk_tx, _) = watch::channel(false);
+        let (tick_tx, _) = watch::channel(None);
+        let (focus_tx, _) = watch::channel(FocusChange::default());
+        let (activity_tx, _) = watch::channel(None);
+        Self {
+            inner: Arc::new(Inner {
+                started_at: Utc::now(),
+                pause_tx,
+                snapshot_tx,
+                afk_tx,
+                tick_tx,
+                focus_tx,
+                activity_tx,
+                sources: Mutex::new(BTreeMap::new()),
+                storage: Mutex::new(StorageHealth::default()),
+                queue: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// 暂停所有截图任务
+    ///
+    /// `duration` 为 `None` 时无限期暂停,恢复时间超出可表示的范围时返回错误
+    pub fn pause(&self, duration: Option<chrono::Duration>) -> Result<PauseState> {
+        let until = match duration {
+            Some(d) => Some(
+                Utc::now()
+                    .checked_add_signed(d)
+                    .ok_or_else(|| anyhow!("Pause duration too long"))?,
+            ),
+            None => None,
+        };
+        let state = PauseState::Paused { until };
+        match until {
+            Some(until) => info!("Capture paused until {}", until),
+            None => info!("Capture paused indefinitely"),
+        }
+        self.inner.pause_tx.send_replace(state);
+        Ok(state)
+    }
+
+    /// 恢复所有截图任务
+    pub fn resume(&self) {
+        info!("Capture resumed");
+        self.inner.pause_tx.send_replace(PauseState::Running);
+    }
+
+    /// 当前暂停状态,已过期的定时暂停视为运行中
+    pub fn pause_state(&self) -> PauseState {
+        match *self.inner.pause_tx.borrow() {
+            PauseState::Paused { until: Some(until) } if until <= Utc::now() => PauseState::Running,
+            state => state,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_state() != PauseState::Running
+    }
+
+    /// 订阅暂停状态变化,用于暂停与恢复立即生效
+    pub fn subscribe_pause(&self) -> watch::Receiver<PauseState> {
+        self.inner.pause_tx.subscribe()
+    }
+
+    /// 请求所有任务立即截图一次(忽略去重与暂停)
+    pub fn request_snapshot(&self) {
+        info!("Snapshot requested");
+        self.inner
+            .snapshot_tx
+            .send_modify(|generation| *generation += 1);
+    }
+
+    /// 订阅立即截图请求,每次请求都会触发 `changed()`
+    pub fn subscribe_snapshot(&self) -> watch::Receiver<u64> {
+        self.inner.snapshot_tx.subscribe()
+    }
+
+    /// 更新 AFK 状态,只有状态变化时才通知订阅者
+    pub fn set_afk(&self, afk: bool) {
+        let changed = self.inner.afk_tx.send_if_modified(|current| {
+            let changed = *current != afk;
+            *current = afk;
+            changed
+        });
+        if changed {
+            info!("User is {}", if afk { "AFK" } else { "active" });
+        }
+    }
+
+    pub fn is_afk(&self) -> bool {
+        *self.inner.afk_tx.borrow()
+    }
+
+    /// 订阅 AFK 状态变化,用于在用户回来时立即恢复截图
+    pub fn subscribe_afk(&self) -> watch::Receiver<bool> {
+        self.inner.afk_tx.subscribe()
+    }
+
+    /// 广播共享时钟的节拍,`None` 表示未启用同步,只有变化时才通知订阅者
+    pub fn set_tick(&self, tick: Option<Tick>) {
+        self.inner.tick_tx.send_if_modified(|current| {
+            let changed = *current != tick;
+            *current = tick;
+            changed
+        });
+    }
+
+    /// 订阅共享时钟,截图任务用它决定何时截图
+    pub fn subscribe_ticks(&self) -> Ticks {
+        Ticks::new(self.inner.tick_tx.subscribe())
+    }
+
+    /// 通知截图任务焦点窗口已切换,`trigger_monitors` 为 `false` 时只唤醒窗口任务
+    pub fn notify_focus_change(&self, window: u32, trigger_monitors: bool) {
+        debug!("Focus settled on window {}", window);
+        self.inner.focus_tx.send_modify(|change| {
+            change.generation += 1;
+            change.window = window;
+            change.trigger_monitors = trigger_monitors;
+        });
+    }
+
+    /// 订阅焦点切换,`monitors` 表示订阅者是显示器或桌面任务
+    pub fn subscribe_focus(&self, monitors: bool) -> FocusEvents {
+        FocusEvents::new(self.inner.focus_tx.subscribe(), monitors)
+    }
+
+    /// 发布窗口任务本次检查到的焦点窗口
+    pub fn publish_window_activity(&self, activity: WindowActivity) {
+        self.inner.activity_tx.send_replace(Some(activity));
+    }
+
+    /// 订阅焦点窗口,每次窗口任务检查都会触发 `changed()`
+    pub fn subscribe_window_activity(&self) -> watch::Receiver<Option<WindowActivity>> {
+        self.inner.activity_tx.subscribe()
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.inner.started_at
+    }
+
+    /// 更新某个来源的统计,来源不存在时自动创建
+    pub fn update_source(&self, source_id: &str, f: impl FnOnce(&mut SourceStatus)) {
+        let mut sources = self.inner.sources.lock().unwrap();
+        f(sources.entry(source_id.to_string()).or_default());
+    }
+
+    /// 所有来源的统计快照
+    pub fn sources(&self) -> BTreeMap<String, SourceStatus> {
+        self.inner.sources.lock().unwrap().clone()
+    }
+
+    pub fn record_storage_saved(&self) {
+        let mut storage = self.inner.storage.lock().unwrap();
+        storage.saved += 1;
+        storage.last_saved = Some(Utc::now());
+        storage.last_error = None;
+    }
+
+    pub fn record_storage_error(&self, error: impl ToString) {
+        let mut storage = self.inner.storage.lock().unwrap();
+        storage.failed += 1;
+        storage.last_error = Some(error.to_string());
+    }
+
+    pub fn storage(&self) -> StorageHealth {
+        self.inner.storage.lock().unwrap().clone()
+    }
+
+    /// 记录截图结果通道,用于统计队列深度
+    ///
+    /// 只保存弱引用,不会阻止通道在任务结束后关闭
+    pub fn set_queue(&self, sender: WeakSender<CaptureResult>) {
+        *self.inner.queue.lock().unwrap() = Some(sender);
+    }
+
+    /// 截图结果通道中等待处理的数量与容量
+    pub fn queue_depth(&self) -> Option<(usize, usize)> {
+        let queue = self.inner.queue.lock().unwrap();
+        let sender = queue.as_ref()?.upgrade()?;
+        Some((
+            sender.max_capacity() - sender.capacity(),
+            sender.max_capacity(),
+        ))
+    }
+}
+
+impl Default for CaptureState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_and_resume() {
+        let state = CaptureState::new();
+        assert!(!state.is_paused());
+
+        let mut rx = state.subscribe_pause();
+
+        state.pause(None).unwrap();
+        assert_eq!(state.pause_state(), PauseState::Paused { until: None });
+        assert!(rx.has_changed().unwrap());
+        rx.borrow_and_update();
+
+        state.resume();
+        assert!(!state.is_paused());
+        assert!(rx.has_changed().unwrap());
+    }
+
+    #[test]
+    fn test_timed_pause_expires() {
+        let state = CaptureState::new();
+        state.pause(Some(chrono::Duration::minutes(30))).unwrap();
+        assert!(state.is_paused());
+
+        state.pause(Some(chrono::Duration::seconds(-1))).unwrap();
+        assert!(!state.is_paused());
+
+        assert!(state.pause(Some(chrono::TimeDelta::MAX)).is_err());
+        assert!(!state.is_paused());
+    }
+
+    #[tokio::test]
+    async fn test_afk_notifies_only_on_change() {
+        let state = CaptureState::new();
+        let mut rx = state.subscribe_afk();
+
+        state.set_afk(false);
+        assert!(!rx.has_changed().unwrap());
+
+        state.set_afk(true);
+        assert!(rx.has_changed().unwrap());
+        assert!(*rx.borrow_and_update());
+        assert!(state.is_afk());
+    }
+
+    #[test]
+    fn test_source_and_storage_stats() {
+        let state = CaptureState::new();
+        state.update_source("DP-1_1920_1080_0_0", |s| s.captures += 1);
+        state.update_source("DP-1_1920_1080_0_0", |s| s.skips += 2);
+        let sources = state.sources();
+        assert_eq!(sources["DP-1_1920_1080_0_0"].captures, 1);
+        assert_eq!(sources["DP-1_1920_1080_0_0"].skips, 2);
+
+        state.record_storage_error("disk full");
+        assert!(!state.storage().is_healthy());
+        state.record_storage_saved();
+        assert!(state.storage().is_healthy());
+        assert_eq!(state.storage().failed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_queue_depth() {
+        let state = CaptureState::new();
+        assert!(state.queue_depth().is_none());
+
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        state.set_queue(tx.downgrade());
+        assert_eq!(state.queue_depth(), Some((0, 4)));
+
+        drop(tx);
+        drop(rx);
+        assert!(state.queue_depth().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_request_notifies_subscribers() {
+        let state = CaptureState::new();
+        let mut rx = state.subscribe_snapshot();
+        state.request_snapshot();
+        assert!(rx.changed().await.is_ok());
+        assert_eq!(*rx.borrow_and_update(), 1);
+    }
+}
@@ -0,0 +1,501 @@
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
+use tokio::time::{Duration, sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+use crate::capture::adaptive::AdaptiveInterval;
+use crate::capture::desktop::SafeDesktop;
+use crate::capture::focus::FocusEvents;
+use crate::capture::monitor::SafeMonitor;
+use crate::capture::rules::{WindowMatcher, WindowRules};
+use crate::capture::scheduler::Tick;
+use crate::capture::state::CaptureState;
+use crate::capture::window::{SafeWindow, TargetWindows};
+use crate::config::{
+    DesktopConfig, IdleConfig, MonitorConfig, WindowConfig, WindowRule, WindowTarget,
+};
+use crate::event::CaptureResult;
+
+/// 连续失败超过该次数后任务退出
+const MAX_CONSECUTIVE_ERRORS: u32 = 10;
+
+/// 由 [`run_capture_task`] 驱动的一路截图来源
+///
+/// 暂停、立即截图、共享时钟、焦点切换、AFK 与健康统计都由任务循环处理,
+/// 来源只负责截图本身与自己的去重状态
+pub(crate) trait CaptureSource: Send {
+    type Config: Clone + PartialEq + fmt::Display + Send + Sync;
+
+    /// 响应焦点切换的方式,传给 `CaptureState::subscribe_focus`;`None` 表示不响应
+    const FOCUS: Option<bool>;
+
+    /// 状态统计中的来源ID
+    fn source_id(&self) -> &str;
+
+    /// 日志中的名称
+    fn label(&self) -> &str;
+
+    /// 配置热更新,任务内的去重状态保留
+    fn update_config(&mut self, _config: &Self::Config) {}
+
+    /// 检查间隔(毫秒),出错后按它的 3 倍退避
+    fn interval(&self, config: &Self::Config) -> u64;
+
+    /// 下一次检查前的等待时间(毫秒),默认等于 `interval`
+    fn next_interval(&self, config: &Self::Config) -> u64 {
+        self.interval(config)
+    }
+
+    /// 清除去重状态,立即截图时使用
+    fn reset(&mut self);
+
+    /// 截图一次,返回通过去重检查的截图
+    fn capture(&mut self, config: &Self::Config) -> Result<Vec<CaptureResult>>;
+
+    fn last_distance(&self) -> Option<u32>;
+
+    /// 正常情况下也会出现的错误,不计入连续错误
+    fn is_expected(&self, _error: &anyhow::Error) -> bool {
+        false
+    }
+}
+
+/// 截图任务循环,直到取消或连续失败过多
+pub(crate) async fn run_capture_task<S: CaptureSource>(
+    mut source: S,
+    sender: Sender<CaptureResult>,
+    mut config_rx: watch::Receiver<S::Config>,
+    mut idle_rx: watch::Receiver<IdleConfig>,
+    state: CaptureState,
+    cancel_token: CancellationToken,
+) {
+    let label = source.label().to_string();
+    let source_id = source.source_id().to_string();
+    let mut consecutive_errors = 0;
+    let mut pause_rx = state.subscribe_pause();
+    let mut snapshot_rx = state.subscribe_snapshot();
+    let mut afk_rx = state.subscribe_afk();
+    let mut ticks = state.subscribe_ticks();
+    let mut focus = S::FOCUS.map(|monitors| state.subscribe_focus(monitors));
+    let mut forced = false;
+    let mut tick: Option<Tick> = None;
+    let mut config = config_rx.borrow_and_update().clone();
+
+    info!("Capture task for {} started", label);
+    state.update_source(&source_id, |s| s.running = true);
+
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Capture task for {} received cancellation signal", label);
+            break;
+        }
+
+        // 热更新: 只替换配置,保留去重状态
+        let latest = config_rx.borrow_and_update().clone();
+        if latest != config {
+            info!(
+                "Capture task for {} configuration updated: {}",
+                label, latest
+            );
+            source.update_config(&latest);
+            config = latest;
+        }
+
+        let idle = idle_rx.borrow_and_update().clone();
+        let afk = idle.enable && state.is_afk();
+
+        // 暂停期间跳过截图,除非收到立即截图请求
+        if !forced && state.is_paused() {
+            debug!("Capture for {} is paused, skipping", label);
+        } else if !forced && afk && idle.idle_interval == 0 {
+            debug!("Capture for {} is idle, skipping", label);
+        } else {
+            if forced {
+                source.reset();
+            }
+
+            match capture_and_send(&mut source, &sender, &config, tick.take()).await {
+                Ok(captured) => {
+                    consecutive_errors = 0;
+                    let distance = source.last_distance();
+                    state.update_source(&source_id, |s| {
+                        s.consecutive_errors = 0;
+                        s.last_distance = distance;
+                        if captured > 0 {
+                            s.captures += captured as u64;
+                            s.last_capture = Some(Utc::now());
+                        } else {
+                            s.skips += 1;
+                        }
+                    });
+                }
+                Err(e) if source.is_expected(&e) => {
+                    debug!("Capture for {} skipped: {}", label, e);
+                }
+                Err(e) => {
+                    error!("Capture error for {}: {}", label, e);
+                    consecutive_errors += 1;
+                    state.update_source(&source_id, |s| {
+                        s.consecutive_errors = consecutive_errors;
+                        s.last_error = Some(e.to_string());
+                    });
+
+                    if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                        error!(
+                            "Capture task for {} exceeded max consecutive errors ({}), terminating",
+                            label, MAX_CONSECUTIVE_ERRORS
+                        );
+                        break;
+                    }
+
+                    forced = false;
+                    tokio::select! {
+                        _ = sleep(Duration::from_millis(source.interval(&config) * 3)) => {}
+                        _ = cancel_token.cancelled() => {
+                            info!("Capture task for {} cancelled during error backoff", label);
+                            break;
+                        }
+                    }
+                    continue;
+                }
+            }
+        }
+
+        let interval = ticks.interval(effective_interval(
+            source.next_interval(&config),
+            &idle,
+            afk,
+        ));
+        state.update_source(&source_id, |s| s.interval = Some(interval));
+        (forced, tick) = tokio::select! {
+            next = ticks.wait(interval) => (false, next),
+            Ok(()) = snapshot_rx.changed() => (true, None),
+            // 暂停与恢复立即生效
+            Ok(()) = pause_rx.changed() => (false, None),
+            // AFK 状态或配置变化时立即重新调度
+            Ok(()) = afk_rx.changed() => (false, None),
+            Ok(()) = idle_rx.changed() => (false, None),
+            // 焦点切换后立即截图,仍按正常去重
+            () = focus_changed(&mut focus) => (false, None),
+            Ok(()) = config_rx.changed() => (false, None),
+            _ = cancel_token.cancelled() => {
+                info!("Capture task for {} cancelled during interval", label);
+                break;
+            }
+        };
+    }
+
+    state.update_source(&source_id, |s| s.running = false);
+    info!("Capture task for {} terminated", label);
+}
+
+/// 截图并发送结果,返回发送的截图数
+async fn capture_and_send<S: CaptureSource>(
+    source: &mut S,
+    sender: &Sender<CaptureResult>,
+    config: &S::Config,
+    tick: Option<Tick>,
+) -> Result<usize> {
+    let results = source.capture(config)?;
+    let count = results.len();
+    for capture_result in results {
+        sender
+            .send(capture_result.with_tick(tick.map(|tick| tick.at)))
+            .await
+            .map_err(|e| anyhow!("Failed to send capture result: {}", e))?;
+    }
+    Ok(count)
+}
+
+/// 不响应焦点切换的来源永远等待
+async fn focus_changed(focus: &mut Option<FocusEvents>) {
+    match focus {
+        Some(focus) => focus.changed().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 根据 AFK 状态计算下一次检查前的等待时间(毫秒)
+///
+/// `idle_interval` 为 0 时 AFK 期间不截图,仍按原间隔轮询以便及时恢复
+fn effective_interval(interval: u64, idle: &IdleConfig, afk: bool) -> u64 {
+    if afk && idle.idle_interval > 0 {
+        idle.idle_interval
+    } else {
+        interval
+    }
+}
+
+/// 单个显示器,可按画面变化自适应调整检查间隔
+pub(crate) struct MonitorSource {
+    monitor: SafeMonitor,
+    monitor_id: String,
+    label: String,
+    adaptive: Option<AdaptiveInterval>,
+}
+
+impl MonitorSource {
+    pub(crate) fn new(monitor: SafeMonitor, monitor_id: String, config: &MonitorConfig) -> Self {
+        Self {
+            monitor,
+            label: format!("monitor {}", monitor_id),
+            monitor_id,
+            adaptive: config.adaptive.clone().map(AdaptiveInterval::new),
+        }
+    }
+}
+
+impl CaptureSource for MonitorSource {
+    type Config = MonitorConfig;
+    const FOCUS: Option<bool> = Some(true);
+
+    fn source_id(&self) -> &str {
+        &self.monitor_id
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn update_config(&mut self, config: &MonitorConfig) {
+        self.adaptive = config.adaptive.clone().map(AdaptiveInterval::new);
+    }
+
+    fn interval(&self, config: &MonitorConfig) -> u64 {
+        config.interval
+    }
+
+    fn next_interval(&self, config: &MonitorConfig) -> u64 {
+        self.adaptive
+            .as_ref()
+            .map_or(config.interval, AdaptiveInterval::current)
+    }
+
+    fn reset(&mut self) {
+        self.monitor.reset();
+    }
+
+    fn capture(&mut self, config: &MonitorConfig) -> Result<Vec<CaptureResult>> {
+        let results = self.monitor.capture_once(config)?;
+        if let Some(adaptive) = self.adaptive.as_mut() {
+            adaptive.update(self.monitor.last_distance(), config.dhash_threshold);
+        }
+        Ok(results)
+    }
+
+    fn last_distance(&self) -> Option<u32> {
+        self.monitor.last_distance()
+    }
+}
+
+/// 虚拟桌面拼接
+pub(crate) struct DesktopSource {
+    desktop: SafeDesktop,
+}
+
+impl DesktopSource {
+    pub(crate) fn new(desktop: SafeDesktop) -> Self {
+        Self { desktop }
+    }
+}
+
+impl CaptureSource for DesktopSource {
+    type Config = DesktopConfig;
+    const FOCUS: Option<bool> = Some(true);
+
+    fn source_id(&self) -> &str {
+        crate::capture::DESKTOP_SOURCE_ID
+    }
+
+    fn label(&self) -> &str {
+        "desktop"
+    }
+
+    fn interval(&self, config: &DesktopConfig) -> u64 {
+        config.interval
+    }
+
+    fn reset(&mut self) {
+        self.desktop.reset();
+    }
+
+    fn capture(&mut self, config: &DesktopConfig) -> Result<Vec<CaptureResult>> {
+        Ok(self.desktop.capture_once(config)?.into_iter().collect())
+    }
+
+    fn last_distance(&self) -> Option<u32> {
+        self.desktop.last_distance()
+    }
+}
+
+/// 焦点窗口,每次检查都发布焦点窗口供 `currentwindow` 心跳使用
+pub(crate) struct WindowSource {
+    window: SafeWindow,
+    rules_rx: watch::Receiver<Arc<WindowRules>>,
+    state: CaptureState,
+}
+
+impl WindowSource {
+    pub(crate) fn new(rules_rx: watch::Receiver<Arc<WindowRules>>, state: CaptureState) -> Self {
+        Self {
+            window: SafeWindow::new(),
+            rules_rx,
+            state,
+        }
+    }
+}
+
+impl CaptureSource for WindowSource {
+    type Config = WindowConfig;
+    const FOCUS: Option<bool> = Some(false);
+
+    fn source_id(&self) -> &str {
+        crate::capture::WINDOW_SOURCE_ID
+    }
+
+    fn label(&self) -> &str {
+        "focused window"
+    }
+
+    fn interval(&self, config: &WindowConfig) -> u64 {
+        config.interval
+    }
+
+    fn reset(&mut self) {
+        self.window.reset();
+    }
+
+    fn capture(&mut self, config: &WindowConfig) -> Result<Vec<CaptureResult>> {
+        let rules = self.rules_rx.borrow().clone();
+        let result = self.window.capture_once(
+            config.enforce_interval,
+            config.dhash_threshold,
+            config.dhash_resolution,
+            config.enable_ocr,
+            &rules,
+        );
+        // 不论是否保存截图都发布焦点窗口,用于 currentwindow 心跳
+        if let Some(activity) = self.window.activity(config.interval) {
+            self.state.publish_window_activity(activity);
+        }
+        let result = result?;
+        if result.is_some()
+            && let Some((app, title)) = self.window.last_window_info()
+        {
+            debug!("Captured window: {} - {}", app, title);
+        }
+        Ok(result.into_iter().collect())
+    }
+
+    fn last_distance(&self) -> Option<u32> {
+        self.window.last_distance()
+    }
+
+    /// 没有焦点窗口是正常情况
+    fn is_expected(&self, error: &anyhow::Error) -> bool {
+        error.to_string().contains("No focused window found")
+    }
+}
+
+/// 按规则匹配的窗口目标
+pub(crate) struct TargetSource {
+    targets: TargetWindows,
+    source_id: String,
+    label: String,
+    rules_rx: watch::Receiver<Arc<WindowRules>>,
+    rule: WindowRule,
+    matcher: WindowMatcher,
+}
+
+impl TargetSource {
+    /// 规则无效时返回错误
+    pub(crate) fn new(
+        config: &WindowTarget,
+        rules_rx: watch::Receiver<Arc<WindowRules>>,
+    ) -> Result<Self> {
+        Ok(Self {
+            targets: TargetWindows::new(config.name.clone()),
+            source_id: crate::capture::target_source_id(&config.name),
+            label: format!("window target {}", config.name),
+            rules_rx,
+            rule: config.rule(),
+            matcher: WindowMatcher::for_target(config)?,
+        })
+    }
+}
+
+impl CaptureSource for TargetSource {
+    type Config = WindowTarget;
+    const FOCUS: Option<bool> = None;
+
+    fn source_id(&self) -> &str {
+        &self.source_id
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn update_config(&mut self, config: &WindowTarget) {
+        if config.rule() == self.rule {
+            return;
+        }
+        match WindowMatcher::for_target(config) {
+            Ok(matcher) => {
+                self.rule = config.rule();
+                self.matcher = matcher;
+            }
+            Err(e) => error!("Invalid {}, keeping old rule: {:#}", self.label, e),
+        }
+    }
+
+    fn interval(&self, config: &WindowTarget) -> u64 {
+        config.interval
+    }
+
+    fn reset(&mut self) {
+        self.targets.reset();
+    }
+
+    fn capture(&mut self, config: &WindowTarget) -> Result<Vec<CaptureResult>> {
+        let rules = self.rules_rx.borrow().clone();
+        self.targets.capture_once(
+            &self.matcher,
+            config.enforce_interval,
+            config.dhash_threshold,
+            config.dhash_resolution,
+            &rules,
+        )
+    }
+
+    fn last_distance(&self) -> Option<u32> {
+        self.targets.last_distance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_interval() {
+        let idle = IdleConfig {
+            enable: true,
+            idle_interval: 60000,
+            ..IdleConfig::default()
+        };
+        assert_eq!(effective_interval(1000, &idle, false), 1000);
+        assert_eq!(effective_interval(1000, &idle, true), 60000);
+
+        let idle = IdleConfig {
+            idle_interval: 0,
+            ..idle
+        };
+        assert_eq!(effective_interval(1000, &idle, true), 1000);
+    }
+}
@@ -1,4 +1,7 @@
-use image::{RgbaImage, imageops};
+use anyhow::{Result, anyhow};
+use image::{Rgba, RgbaImage, imageops};
+
+use crate::event::ChangedArea;
 
 pub fn dHash(image: &RgbaImage, resolution: u32) -> u64 {
     //TODO: really use resolution
@@ -24,6 +27,202 @@ pub fn dHash(image: &RgbaImage, resolution: u32) -> u64 {
     hash
 }
 
+/// 等比缩放到长边不超过 `max_size`,原图更小时不放大
+pub fn thumbnail(image: &RgbaImage, max_size: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let longest = width.max(height);
+    if longest <= max_size {
+        return image.clone();
+    }
+    let scale = max_size as f64 / longest as f64;
+    let width = ((width as f64 * scale).round() as u32).max(1);
+    let height = ((height as f64 * scale).round() as u32).max(1);
+    imageops::thumbnail(image, width, height)
+}
+
+/// 裁剪出与图像相交的部分,超出边界的部分被截掉,完全不相交时返回 `None`
+pub fn crop(image: &RgbaImage, x: u32, y: u32, width: u32, height: u32) -> Option<RgbaImage> {
+    let (image_width, image_height) = image.dimensions();
+    if x >= image_width || y >= image_height {
+        return None;
+    }
+    let width = width.min(image_width - x);
+    let height = height.min(image_height - y);
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some(imageops::crop_imm(image, x, y, width, height).to_image())
+}
+
+/// 把 `masks` 中的矩形 `(x, y, width, height)` 涂黑,超出图像的部分忽略
+pub fn apply_masks(image: &mut RgbaImage, masks: &[(u32, u32, u32, u32)]) {
+    let (image_width, image_height) = image.dimensions();
+    for &(x, y, width, height) in masks {
+        let x_end = x.saturating_add(width).min(image_width);
+        let y_end = y.saturating_add(height).min(image_height);
+        for py in y.min(y_end)..y_end {
+            for px in x.min(x_end)..x_end {
+                image.put_pixel(px, py, Rgba([0, 0, 0, 255]));
+            }
+        }
+    }
+}
+
+/// 按各图像左上角的全局坐标拼接成一张图,未覆盖的区域填充黑色
+///
+/// 画布左上角对应所有图像中最小的 x/y,`parts` 为空时返回 `None`
+pub fn stitch(parts: &[((i32, i32), RgbaImage)]) -> Option<RgbaImage> {
+    let left = parts.iter().map(|((x, _), _)| *x as i64).min()?;
+    let top = parts.iter().map(|((_, y), _)| *y as i64).min()?;
+    let right = parts
+        .iter()
+        .map(|((x, _), image)| *x as i64 + image.width() as i64)
+        .max()?;
+    let bottom = parts
+        .iter()
+        .map(|((_, y), image)| *y as i64 + image.height() as i64)
+        .max()?;
+
+    let width = u32::try_from(right - left).ok()?;
+    let height = u32::try_from(bottom - top).ok()?;
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    for ((x, y), image) in parts {
+        imageops::replace(&mut canvas, image, *x as i64 - left, *y as i64 - top);
+    }
+    Some(canvas)
+}
+
+/// 按网格切分后每个分块的 dHash
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileHashes {
+    columns: u32,
+    rows: u32,
+    width: u32,
+    height: u32,
+    hashes: Vec<u64>,
+}
+
+impl TileHashes {
+    /// 先把整张图缩放到每个分块 9x8 像素,再逐块计算 8x8 的 dHash
+    pub fn new(image: &RgbaImage, columns: u32, rows: u32) -> Result<Self> {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let (width, height) = columns
+            .checked_mul(9)
+            .zip(rows.checked_mul(8))
+            .ok_or_else(|| anyhow!("Tile grid {}x{} is too large", columns, rows))?;
+        let resized = imageops::resize(image, width, height, imageops::FilterType::Triangle);
+        let gray = imageops::grayscale(&resized);
+
+        let mut hashes = Vec::with_capacity(columns as usize * rows as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let mut hash = 0u64;
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let left = gray.get_pixel(column * 9 + x, row * 8 + y)[0];
+                        let right = gray.get_pixel(column * 9 + x + 1, row * 8 + y)[0];
+                        if left < right {
+                            hash |= 1 << (y * 8 + x);
+                        }
+                    }
+                }
+                hashes.push(hash);
+            }
+        }
+
+        Ok(Self {
+            columns,
+            rows,
+            width: image.width(),
+            height: image.height(),
+            hashes,
+        })
+    }
+
+    /// 与另一组分块逐块比较的汉明距离,网格或图像大小不同时无法比较
+    pub fn distances(&self, other: &Self) -> Option<Vec<u32>> {
+        if (self.columns, self.rows, self.width, self.height)
+            != (other.columns, other.rows, other.width, other.height)
+        {
+            return None;
+        }
+        Some(
+            self.hashes
+                .iter()
+                .zip(&other.hashes)
+                .map(|(a, b)| hamming_distance(*a, *b))
+                .collect(),
+        )
+    }
+
+    /// 第 `index` 个分块(按行优先)在原图中的矩形 `(x, y, width, height)`
+    pub fn tile_rect(&self, index: usize) -> (u32, u32, u32, u32) {
+        let column = index as u64 % self.columns as u64;
+        let row = index as u64 / self.columns as u64;
+        let span = |i: u64, count: u32, size: u32| {
+            let start = i * size as u64 / count as u64;
+            let end = (i + 1) * size as u64 / count as u64;
+            (start as u32, (end - start) as u32)
+        };
+        let (x, width) = span(column, self.columns, self.width);
+        let (y, height) = span(row, self.rows, self.height);
+        (x, y, width, height)
+    }
+
+    /// 距离达到 `threshold` 的分块的外接矩形,没有分块变化时返回 `None`
+    pub fn changed_area(&self, distances: &[u32], threshold: u32) -> Option<ChangedArea> {
+        let changed: Vec<usize> = distances
+            .iter()
+            .enumerate()
+            .filter(|(_, distance)| **distance >= threshold.max(1))
+            .map(|(index, _)| index)
+            .collect();
+        let rects: Vec<_> = changed.iter().map(|&index| self.tile_rect(index)).collect();
+
+        let x = rects.iter().map(|r| r.0).min()?;
+        let y = rects.iter().map(|r| r.1).min()?;
+        let x_end = rects.iter().map(|r| r.0 + r.2).max()?;
+        let y_end = rects.iter().map(|r| r.1 + r.3).max()?;
+        Some(ChangedArea {
+            x,
+            y,
+            width: x_end - x,
+            height: y_end - y,
+            changed_tiles: changed.len() as u32,
+            total_tiles: self.hashes.len() as u32,
+        })
+    }
+
+    /// 在截图上叠加分块变化热力图,颜色越红变化越大,达到阈值的分块加白框
+    pub fn heatmap(&self, image: &RgbaImage, distances: &[u32], threshold: u32) -> RgbaImage {
+        let mut overlay = image.clone();
+        for (index, &distance) in distances.iter().enumerate() {
+            if distance == 0 {
+                continue;
+            }
+            let (x, y, width, height) = self.tile_rect(index);
+            let alpha = distance.min(32) as f32 / 32.0 * 0.6;
+            let outlined = distance >= threshold.max(1);
+            for py in y..(y + height).min(overlay.height()) {
+                for px in x..(x + width).min(overlay.width()) {
+                    let pixel = overlay.get_pixel_mut(px, py);
+                    let border =
+                        px < x + 2 || py < y + 2 || px + 2 >= x + width || py + 2 >= y + height;
+                    if outlined && border {
+                        *pixel = Rgba([255, 255, 255, 255]);
+                        continue;
+                    }
+                    let [r, g, b, a] = pixel.0;
+                    let blend =
+                        |c: u8, target: f32| (c as f32 * (1.0 - alpha) + target * alpha) as u8;
+                    *pixel = Rgba([blend(r, 255.0), blend(g, 0.0), blend(b, 0.0), a]);
+                }
+            }
+        }
+        overlay
+    }
+}
+
 pub fn ssim(img1: &RgbaImage, img2: &RgbaImage) -> f64 {
     let gray1 = imageops::grayscale(img1);
     let gray2 = imageops::grayscale(img2);
@@ -64,3 +263,95 @@ pub fn ssim(img1: &RgbaImage, img2: &RgbaImage) -> f64 {
 pub fn hamming_distance(hash1: u64, hash2: u64) -> u32 {
     (hash1 ^ hash2).count_ones()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_keeps_aspect_ratio() {
+        let image = RgbaImage::new(2560, 1440);
+        assert_eq!(thumbnail(&image, 480).dimensions(), (480, 270));
+
+        let small = RgbaImage::new(100, 50);
+        assert_eq!(thumbnail(&small, 480).dimensions(), (100, 50));
+    }
+
+    #[test]
+    fn test_tile_changes() {
+        let mut before = RgbaImage::new(320, 180);
+        for (x, y, pixel) in before.enumerate_pixels_mut() {
+            let v = ((x * 7 + y * 13) % 256) as u8;
+            *pixel = Rgba([v, v, v, 255]);
+        }
+        let mut after = before.clone();
+        // 只修改右下角的一小块
+        for y in 150..180 {
+            for x in 290..320 {
+                after.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+
+        let tiles_before = TileHashes::new(&before, 4, 3).unwrap();
+        let tiles_after = TileHashes::new(&after, 4, 3).unwrap();
+        let distances = tiles_before.distances(&tiles_after).unwrap();
+        let area = tiles_after.changed_area(&distances, 4).unwrap();
+        assert_eq!(area.changed_tiles, 1);
+        assert_eq!(area.total_tiles, 12);
+        assert_eq!(
+            (area.x, area.y, area.width, area.height),
+            (240, 120, 80, 60)
+        );
+
+        // 遮住变化的区域后不再有变化
+        let mut masked = after.clone();
+        let mut masked_before = before.clone();
+        apply_masks(&mut masked, &[(280, 140, 40, 40)]);
+        apply_masks(&mut masked_before, &[(280, 140, 40, 40)]);
+        let distances = TileHashes::new(&masked_before, 4, 3)
+            .unwrap()
+            .distances(&TileHashes::new(&masked, 4, 3).unwrap())
+            .unwrap();
+        assert!(tiles_after.changed_area(&distances, 4).is_none());
+
+        assert!(
+            tiles_before
+                .distances(&TileHashes::new(&before, 2, 2).unwrap())
+                .is_none()
+        );
+        assert_eq!(
+            tiles_after.heatmap(&after, &distances, 4).dimensions(),
+            (320, 180)
+        );
+        assert!(TileHashes::new(&before, u32::MAX, 3).is_err());
+    }
+
+    #[test]
+    fn test_crop_clamps_to_image() {
+        let image = RgbaImage::new(1920, 1080);
+        assert_eq!(
+            crop(&image, 100, 200, 300, 400).unwrap().dimensions(),
+            (300, 400)
+        );
+        assert_eq!(
+            crop(&image, 1800, 1000, 300, 400).unwrap().dimensions(),
+            (120, 80)
+        );
+        assert!(crop(&image, 1920, 0, 10, 10).is_none());
+        assert!(crop(&image, 0, 0, 0, 10).is_none());
+    }
+
+    #[test]
+    fn test_stitch_by_position() {
+        let left = RgbaImage::from_pixel(40, 20, Rgba([255, 0, 0, 255]));
+        let right = RgbaImage::from_pixel(30, 30, Rgba([0, 255, 0, 255]));
+        let canvas = stitch(&[((0, 0), right), ((-40, 5), left)]).unwrap();
+
+        assert_eq!(canvas.dimensions(), (70, 30));
+        assert_eq!(canvas.get_pixel(0, 5), &Rgba([255, 0, 0, 255]));
+        assert_eq!(canvas.get_pixel(40, 0), &Rgba([0, 255, 0, 255]));
+        // 左侧显示器上方未覆盖的区域
+        assert_eq!(canvas.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        assert!(stitch(&[]).is_none());
+    }
+}
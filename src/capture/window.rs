@@ -1,9 +1,13 @@
 use anyhow::{Result, anyhow};
 use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use xcap::Window;
 
+use crate::capture::monitor::MonitorInfo;
+use crate::capture::rules::{WindowMatcher, WindowRules};
 use crate::capture::utils::hamming_distance;
-use crate::event::CaptureResult;
+use crate::event::{CaptureResult, WindowActivity};
 
 /// 安全的窗口捕获封装
 ///
@@ -12,6 +16,11 @@ pub struct SafeWindow {
     last_capture_time: Option<DateTime<Utc>>,
     last_capture_dhash: Option<u64>,
     last_window_info: Option<WindowInfo>,
+    last_distance: Option<u32>,
+    /// 最近一次检查到的焦点窗口,不论是否保存了截图;命中隐私规则的窗口不记录
+    last_focus: Option<(DateTime<Utc>, WindowInfo)>,
+    /// 最近一次保存的截图所属的窗口ID与截图ID
+    last_screenshot: Option<(u32, String)>,
 }
 
 /// 窗口信息快照
@@ -22,12 +31,69 @@ struct WindowInfo {
     app_name: String,
 }
 
+impl WindowInfo {
+    fn from_window(window: &Window) -> Result<Self> {
+        Ok(Self {
+            id: window.id()?,
+            title: window.title().unwrap_or_else(|_| "Unknown".to_string()),
+            app_name: window.app_name().unwrap_or_else(|_| "Unknown".to_string()),
+        })
+    }
+}
+
+/// 窗口的属性,用于 `list-windows`
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowDetails {
+    pub id: u32,
+    pub pid: u32,
+    pub app_name: String,
+    pub title: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// 窗口所在显示器的 ID,格式同 `list-monitors`
+    pub monitor: Option<String>,
+    pub is_minimized: bool,
+    pub is_focused: bool,
+}
+
+impl WindowDetails {
+    fn from_window(window: &Window) -> Result<Self> {
+        Ok(Self {
+            id: window.id()?,
+            pid: window.pid()?,
+            app_name: window.app_name()?,
+            title: window.title()?,
+            x: window.x()?,
+            y: window.y()?,
+            width: window.width()?,
+            height: window.height()?,
+            monitor: window
+                .current_monitor()
+                .ok()
+                .and_then(|monitor| MonitorInfo::from_monitor(&monitor).ok())
+                .map(|info| info.id),
+            is_minimized: window.is_minimized()?,
+            is_focused: window.is_focused()?,
+        })
+    }
+
+    /// 所有窗口,按 z 序从上到下排列
+    pub fn all() -> Result<Vec<Self>> {
+        Window::all()?.iter().map(Self::from_window).collect()
+    }
+}
+
 impl SafeWindow {
     pub fn new() -> Self {
         Self {
             last_capture_time: None,
             last_capture_dhash: None,
             last_window_info: None,
+            last_distance: None,
+            last_focus: None,
+            last_screenshot: None,
         }
     }
 
@@ -38,10 +104,11 @@ impl SafeWindow {
     /// - `dhash_threshold`: dhash 汉明距离阈值,小于此值认为图像相似
     /// - `dhash_resolution`: dhash 计算的分辨率
     /// - `enable_ocr`: 是否启用 OCR(预留,暂未实现)
+    /// - `rules`: 命中隐私规则的窗口不截图
     ///
     /// # 返回
     /// - `Ok(Some(CaptureResult))`: 成功捕获新截图
-    /// - `Ok(None)`: 由于去重策略,跳过此次捕获
+    /// - `Ok(None)`: 由于去重策略或隐私规则,跳过此次捕获
     /// - `Err`: 捕获失败
     pub fn capture_once(
         &mut self,
@@ -49,37 +116,75 @@ impl SafeWindow {
         dhash_threshold: u32,
         dhash_resolution: u32,
         _enable_ocr: bool, // 预留 OCR 功能
+        rules: &WindowRules,
     ) -> Result<Option<CaptureResult>> {
-        let now = Utc::now();
-
         // 获取当前焦点窗口
+        self.last_focus = None;
         let focused_window = Self::get_focused_window()?;
+        let window_info = WindowInfo::from_window(&focused_window)?;
 
-        // 获取窗口信息
-        let window_info = WindowInfo {
-            id: focused_window.id()?,
-            title: focused_window
-                .title()
-                .unwrap_or_else(|_| "Unknown".to_string()),
-            app_name: focused_window
-                .app_name()
-                .unwrap_or_else(|_| "Unknown".to_string()),
-        };
+        // 隐私规则优先于其他所有判断
+        if !self.observe_focus(&window_info, rules) {
+            return Ok(None);
+        }
+
+        // 生成窗口 ID (格式: "window_{app_name}_{window_id}")
+        let capture_id = format!("window_{}_{}", window_info.app_name, window_info.id);
+
+        self.capture_window(
+            &focused_window,
+            window_info,
+            capture_id,
+            enforce_interval,
+            dhash_threshold,
+            dhash_resolution,
+        )
+    }
+
+    /// 记录焦点窗口,命中隐私规则时不记录并返回 `false`,避免窗口标题经心跳上报
+    fn observe_focus(&mut self, window_info: &WindowInfo, rules: &WindowRules) -> bool {
+        if rules.is_excluded(&window_info.app_name, &window_info.title) {
+            tracing::debug!(
+                "Window {} matches an exclude rule, skipping",
+                window_info.app_name
+            );
+            return false;
+        }
+        self.last_focus = Some((Utc::now(), window_info.clone()));
+        true
+    }
+
+    /// 捕获指定窗口并按时间、窗口与图像相似度去重
+    fn capture_window(
+        &mut self,
+        window: &Window,
+        window_info: WindowInfo,
+        capture_id: String,
+        enforce_interval: u64,
+        dhash_threshold: u32,
+        dhash_resolution: u32,
+    ) -> Result<Option<CaptureResult>> {
+        let now = Utc::now();
+        let window_id = window_info.id;
 
         // 检查窗口是否可以截图
-        if focused_window.is_minimized().unwrap_or(false) {
+        if window.is_minimized().unwrap_or(false) {
             return Ok(None); // 最小化窗口无法截图
         }
 
         // 捕获窗口图像
-        let image = focused_window
+        let image = window
             .capture_image()
             .map_err(|e| anyhow!("Failed to capture window image: {:?}", e))?;
 
         // 计算图像 hash
         let dhash = crate::capture::utils::dHash(&image, dhash_resolution);
+        self.last_distance = self
+            .last_capture_dhash
+            .map(|last_hash| hamming_distance(dhash, last_hash));
 
         // 去重检查
+        let mut similar = false;
         if let Some(last_time) = self.last_capture_time {
             if let (Some(last_hash), Some(last_info)) =
                 (self.last_capture_dhash, &self.last_window_info)
@@ -101,7 +206,8 @@ impl SafeWindow {
                     let hash_too_similar = hamming_distance(dhash, last_hash) < dhash_threshold;
 
                     // 如果是同一个窗口,时间太近且图像相似,则跳过
-                    if same_window && time_too_soon && hash_too_similar {
+                    similar = same_window && hash_too_similar;
+                    if similar && time_too_soon {
                         return Ok(None);
                     }
                 }
@@ -111,10 +217,7 @@ impl SafeWindow {
         // 更新状态
         self.last_capture_time = Some(now);
         self.last_capture_dhash = Some(dhash);
-        self.last_window_info = Some(window_info.clone());
-
-        // 生成窗口 ID (格式: "window_{app_name}_{window_id}")
-        let capture_id = format!("window_{}_{}", window_info.app_name, window_info.id);
+        self.last_window_info = Some(window_info);
 
         // TODO: OCR 功能预留位置
         // if enable_ocr {
@@ -122,7 +225,10 @@ impl SafeWindow {
         //     // 将 OCR 结果附加到 CaptureResult 或事件元数据中
         // }
 
-        Ok(Some(CaptureResult::new(capture_id, image, now)))
+        let result =
+            CaptureResult::new(capture_id, image, now).with_dedup(similar, enforce_interval);
+        self.last_screenshot = Some((window_id, result.screenshot_id()));
+        Ok(Some(result))
     }
 
     /// 获取当前焦点窗口
@@ -140,6 +246,37 @@ impl SafeWindow {
         Err(anyhow!("No focused window found"))
     }
 
+    /// 最近一次截图与上次保存截图的 dHash 汉明距离
+    pub fn last_distance(&self) -> Option<u32> {
+        self.last_distance
+    }
+
+    /// 清空去重状态,下一次截图必定保存
+    pub fn reset(&mut self) {
+        self.last_capture_time = None;
+        self.last_capture_dhash = None;
+        self.last_window_info = None;
+    }
+
+    /// 最近一次检查到的焦点窗口,附带该窗口最近保存的截图ID
+    ///
+    /// `interval` 为窗口任务的检查间隔,没有焦点窗口时返回 `None`
+    pub fn activity(&self, interval: u64) -> Option<WindowActivity> {
+        let (timestamp, info) = self.last_focus.as_ref()?;
+        let screenshot = self
+            .last_screenshot
+            .as_ref()
+            .filter(|(window_id, _)| *window_id == info.id)
+            .map(|(_, id)| id.clone());
+        Some(WindowActivity {
+            timestamp: *timestamp,
+            app: info.app_name.clone(),
+            title: info.title.clone(),
+            screenshot,
+            interval,
+        })
+    }
+
     /// 获取上次捕获的窗口信息(用于调试)
     pub fn last_window_info(&self) -> Option<(String, String)> {
         self.last_window_info
@@ -154,9 +291,97 @@ impl Default for SafeWindow {
     }
 }
 
+/// 按规则匹配的窗口截图封装
+///
+/// 不要求窗口有焦点;匹配到的每个窗口使用独立的去重状态,窗口关闭后丢弃
+pub struct TargetWindows {
+    name: String,
+    windows: HashMap<u32, SafeWindow>,
+    last_distance: Option<u32>,
+}
+
+impl TargetWindows {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            windows: HashMap::new(),
+            last_distance: None,
+        }
+    }
+
+    /// 截取所有匹配 `matcher` 的窗口,跳过命中隐私规则的窗口
+    ///
+    /// 返回通过去重检查的截图,截图 ID 格式为 `target_{name}_{window_id}`
+    pub fn capture_once(
+        &mut self,
+        matcher: &WindowMatcher,
+        enforce_interval: u64,
+        dhash_threshold: u32,
+        dhash_resolution: u32,
+        rules: &WindowRules,
+    ) -> Result<Vec<CaptureResult>> {
+        let windows = Window::all().map_err(|e| anyhow!("Failed to get window list: {:?}", e))?;
+
+        let mut matched = HashSet::new();
+        let mut results = Vec::new();
+        for window in windows {
+            let Ok(window_info) = WindowInfo::from_window(&window) else {
+                continue;
+            };
+            if !matcher.matches(&window_info.app_name, &window_info.title) {
+                continue;
+            }
+            if rules.is_excluded(&window_info.app_name, &window_info.title) {
+                tracing::debug!(
+                    "Window {} matches target {} and an exclude rule, skipping",
+                    window_info.app_name,
+                    self.name
+                );
+                continue;
+            }
+
+            matched.insert(window_info.id);
+            let capture_id = format!("target_{}_{}", self.name, window_info.id);
+            let state = self.windows.entry(window_info.id).or_default();
+            match state.capture_window(
+                &window,
+                window_info,
+                capture_id,
+                enforce_interval,
+                dhash_threshold,
+                dhash_resolution,
+            ) {
+                Ok(result) => results.extend(result),
+                // 单个窗口失败(例如截图时已关闭)不影响其他窗口
+                Err(e) => tracing::warn!("Target {} capture error: {}", self.name, e),
+            }
+            if let Some(distance) = state.last_distance() {
+                self.last_distance = Some(distance);
+            }
+        }
+
+        if matched.is_empty() {
+            tracing::debug!("No window matches target {}", self.name);
+        }
+        self.windows.retain(|id, _| matched.contains(id));
+        Ok(results)
+    }
+
+    /// 最近一次检查的窗口与其上次保存截图的 dHash 汉明距离
+    pub fn last_distance(&self) -> Option<u32> {
+        self.last_distance
+    }
+
+    /// 清空所有窗口的去重状态,下一次截图必定保存
+    pub fn reset(&mut self) {
+        self.windows.values_mut().for_each(SafeWindow::reset);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{WindowRule, WindowsConfig};
 
     #[test]
     fn test_safe_window_creation() {
@@ -177,4 +402,29 @@ mod tests {
         let capture_id = format!("window_{}_{}", info.app_name, info.id);
         assert_eq!(capture_id, "window_TestApp_12345");
     }
+
+    #[test]
+    fn test_excluded_window_has_no_activity() {
+        let rules = WindowRules::new(&WindowsConfig {
+            exclude: vec![WindowRule {
+                name: "passwords".to_string(),
+                app: "KeePassXC".to_string(),
+                title: String::new(),
+            }],
+            targets: Vec::new(),
+        })
+        .unwrap();
+        let info = |app: &str| WindowInfo {
+            id: 1,
+            title: "Database".to_string(),
+            app_name: app.to_string(),
+        };
+
+        let mut window = SafeWindow::new();
+        assert!(!window.observe_focus(&info("KeePassXC"), &rules));
+        assert!(window.activity(1000).is_none());
+
+        assert!(window.observe_focus(&info("firefox"), &rules));
+        assert_eq!(window.activity(1000).unwrap().app, "firefox");
+    }
 }
@@ -1,11 +1,30 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Local, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
 use std::path::{Path, PathBuf};
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tracing_subscriber::EnvFilter;
 
-use crate::capture::Capture;
-use crate::config::Config;
+use crate::activitywatch::{
+    ActivityAnnotator, AfkWatcher, AwClient, SavedScreenshot, ScreenshotEvents, WindowHeartbeats,
+};
+use crate::capture::{Capture, MonitorInfo, PauseState, WindowDetails, WindowRules};
+use crate::config::diagnostics::{
+    Diagnostic, check_attached_monitors, check_storage_writable, check_syntax,
+    check_unknown_fields, locate,
+};
+use crate::config::layered::env_vars;
+use crate::config::migrate;
+use crate::config::template::render_config;
+use crate::config::{Config, ConfigSource, ConfigWatcher};
+use crate::control::{self, Request};
+#[cfg(unix)]
+use crate::control::{ControlServer, Response, StatusReport};
+use crate::export::{TimelapseFormat, TimelapseOptions, timelapse};
+use crate::serve::Viewer;
+use crate::storage::{CaptureStore, Storage, StorageIndex};
 
 #[derive(Parser)]
 #[command(name = "aw-watcher-screenshot")]
@@ -18,14 +37,41 @@ pub struct Cli {
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// 列出所有可识别的显示器ID
-    ListMonitors,
+    /// 列出所有可识别的显示器
+    ListMonitors {
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
+
+        /// 为每个显示器保存缩略图到指定目录，用于区分同型号显示器
+        #[arg(long, value_name = "DIR")]
+        preview: Option<PathBuf>,
+
+        /// 配置文件路径，用于标注显示器是否已配置（默认依次查找 $AW_SCREENSHOT_CONFIG、XDG 配置目录）
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+
+    /// 列出所有窗口，用于编写窗口规则
+    ListWindows {
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
+
+        /// 显示每个窗口命中的窗口规则
+        #[arg(long)]
+        test_rules: bool,
+
+        /// 配置文件路径，用于读取窗口规则（默认依次查找 $AW_SCREENSHOT_CONFIG、XDG 配置目录）
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
 
     /// 开始截图
     Capture {
-        /// 配置文件路径
-        #[arg(short, long, default_value = "config/config.toml")]
-        config: PathBuf,
+        /// 配置文件路径（默认依次查找 $AW_SCREENSHOT_CONFIG、XDG 配置目录）
+        #[arg(short, long)]
+        config: Option<PathBuf>,
 
         /// 最大截图次数（0表示无限制）
         #[arg(short, long, default_value = "0")]
@@ -38,7 +84,192 @@ pub enum Commands {
         /// 本地存储路径（设置此项将自动启用本地存储）
         #[arg(short = 's', long)]
         storage_path: Option<PathBuf>,
+
+        /// 控制 socket 路径（默认 $XDG_RUNTIME_DIR/aw-watcher-screenshot.sock）
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// 配置文件相关命令
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
     },
+
+    /// 暂停运行中的截图
+    Pause {
+        /// 暂停时长，如 30m、1h30m、90s（不指定则暂停到手动恢复）
+        #[arg(long = "for", value_name = "DURATION", value_parser = parse_duration_arg)]
+        duration: Option<std::time::Duration>,
+
+        /// 控制 socket 路径
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// 恢复运行中的截图
+    Resume {
+        /// 控制 socket 路径
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// 查看运行中实例的状态
+    Status {
+        /// 控制 socket 路径
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// 让运行中的实例立即截图一次
+    SnapshotNow {
+        /// 控制 socket 路径
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// 启动本地网页查看器，按时间轴浏览已保存的截图
+    Serve {
+        /// 配置文件路径（默认依次查找 $AW_SCREENSHOT_CONFIG、XDG 配置目录）
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// 监听地址
+        #[arg(short, long, default_value = "127.0.0.1:5610")]
+        bind: String,
+
+        /// 读取截图的存储
+        #[arg(long, value_enum, default_value_t = StorageSource::Local)]
+        from: StorageSource,
+
+        /// 本地存储路径（覆盖配置中的 storage.local.path）
+        #[arg(short = 's', long)]
+        storage_path: Option<PathBuf>,
+    },
+
+    /// 导出已保存的截图
+    Export {
+        #[command(subcommand)]
+        command: ExportCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExportCommand {
+    /// 把一段时间内某一来源的截图导出为延时动画（.gif 或 .mjpeg）
+    Timelapse {
+        /// 配置文件路径（默认依次查找 $AW_SCREENSHOT_CONFIG、XDG 配置目录）
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// 截图来源ID，或 window 表示所有焦点窗口截图
+        #[arg(long)]
+        source: String,
+
+        /// 开始时间（本地时间），如 2024-06-01 或 "2024-06-01 09:00"
+        #[arg(long, value_parser = parse_time_arg)]
+        start: DateTime<Utc>,
+
+        /// 结束时间（不含），格式同 --start
+        #[arg(long, value_parser = parse_time_arg)]
+        end: DateTime<Utc>,
+
+        /// 输出文件，按扩展名选择格式
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// 输出宽度（像素），高度按首帧比例计算
+        #[arg(long, default_value = "1280")]
+        width: u32,
+
+        /// 每秒帧数
+        #[arg(long, default_value = "10")]
+        fps: u32,
+
+        /// 最多帧数，截图更多时均匀抽取（0表示不限制）
+        #[arg(long, default_value = "1200")]
+        max_frames: usize,
+
+        /// 在每帧左下角叠加截图时间与应用名
+        #[arg(long)]
+        overlay: bool,
+
+        /// 叠加文字使用的字体文件（默认查找系统字体）
+        #[arg(long)]
+        font: Option<PathBuf>,
+
+        /// 读取截图的存储
+        #[arg(long, value_enum, default_value_t = StorageSource::Local)]
+        from: StorageSource,
+
+        /// 本地存储路径（覆盖配置中的 storage.local.path）
+        #[arg(short = 's', long)]
+        storage_path: Option<PathBuf>,
+    },
+}
+
+/// 读取已保存截图的存储
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StorageSource {
+    /// 本地存储目录
+    Local,
+    /// 配置中的 S3 存储桶
+    S3,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// 显示使用的配置文件
+    Show {
+        /// 配置文件路径（默认依次查找 $AW_SCREENSHOT_CONFIG、XDG 配置目录）
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// 显示叠加默认值与环境变量覆盖后最终生效的配置
+        #[arg(long)]
+        resolved: bool,
+    },
+
+    /// 检查配置文件并列出所有问题，有错误时以非零状态退出
+    Validate {
+        /// 配置文件路径（默认依次查找 $AW_SCREENSHOT_CONFIG、XDG 配置目录）
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// 存在警告时同样以非零状态退出
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+
+    /// 把旧版本的配置文件升级到当前格式（保留注释）
+    Migrate {
+        /// 配置文件路径（默认依次查找 $AW_SCREENSHOT_CONFIG、XDG 配置目录）
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// 只显示升级后的内容，不写入文件
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 根据检测到的显示器生成配置文件
+    Init {
+        /// 输出路径（默认 ~/.config/aw-watcher-screenshot/config.toml）
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// 覆盖已存在的配置文件
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+fn parse_duration_arg(input: &str) -> Result<std::time::Duration, String> {
+    control::parse_duration(input).map_err(|e| e.to_string())
+}
+
+fn parse_time_arg(input: &str) -> Result<DateTime<Utc>, String> {
+    timelapse::parse_local_time(input).map_err(|e| e.to_string())
 }
 
 /// CLI 入口函数
@@ -46,46 +277,628 @@ pub async fn run() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::ListMonitors => {
-            list_monitors()?;
+        Commands::ListMonitors {
+            json,
+            preview,
+            config,
+        } => {
+            list_monitors(json, preview, config.as_deref())?;
+        }
+        Commands::ListWindows {
+            json,
+            test_rules,
+            config,
+        } => {
+            list_windows(json, test_rules, config.as_deref())?;
         }
         Commands::Capture {
             config,
             max_count,
             log_level,
             storage_path,
+            socket,
         } => {
-            start_capture(config, max_count, log_level, storage_path).await?;
+            start_capture(config, max_count, log_level, storage_path, socket).await?;
         }
+        Commands::Config { command } => match command {
+            ConfigCommand::Show { config, resolved } => {
+                show_config(config.as_deref(), resolved)?;
+            }
+            ConfigCommand::Validate {
+                config,
+                deny_warnings,
+            } => {
+                validate_config(config.as_deref(), deny_warnings)?;
+            }
+            ConfigCommand::Migrate { config, dry_run } => {
+                migrate_config(config.as_deref(), dry_run)?;
+            }
+            ConfigCommand::Init { output, force } => {
+                init_config(output, force)?;
+            }
+        },
+        Commands::Pause { duration, socket } => {
+            let request = Request::Pause {
+                duration_secs: duration.map(|d| d.as_secs()),
+            };
+            send_control(socket, request).await?;
+        }
+        Commands::Resume { socket } => {
+            send_control(socket, Request::Resume).await?;
+        }
+        Commands::Status { socket } => {
+            send_control(socket, Request::Status).await?;
+        }
+        Commands::SnapshotNow { socket } => {
+            send_control(socket, Request::SnapshotNow).await?;
+        }
+        Commands::Serve {
+            config,
+            bind,
+            from,
+            storage_path,
+        } => {
+            serve(config.as_deref(), &bind, from, storage_path).await?;
+        }
+        Commands::Export { command } => match command {
+            ExportCommand::Timelapse {
+                config,
+                source,
+                start,
+                end,
+                output,
+                width,
+                fps,
+                max_frames,
+                overlay,
+                font,
+                from,
+                storage_path,
+            } => {
+                let range = TimeRange { start, end };
+                let options = TimelapseOptions {
+                    width,
+                    fps,
+                    overlay: overlay
+                        .then(|| timelapse::load_font(font.as_deref()))
+                        .transpose()?,
+                };
+                let storage = open_storage(config.as_deref(), from, storage_path)?;
+                export_timelapse(storage, &source, range, max_frames, options, &output).await?;
+            }
+        },
     }
 
     Ok(())
 }
 
-/// 列出所有可识别的显示器ID
-fn list_monitors() -> Result<()> {
-    println!("正在扫描显示器...\n");
+/// 预览缩略图的最大边长
+const PREVIEW_SIZE: u32 = 480;
 
-    let monitor_ids = Capture::get_all_monitors_id()?;
+/// `list-monitors` 的一行输出
+#[derive(Serialize)]
+struct MonitorListing {
+    #[serde(flatten)]
+    info: MonitorInfo,
+    /// 当前配置中是否有该显示器,未找到配置文件时为 `None`
+    configured: Option<bool>,
+    enabled: Option<bool>,
+    preview: Option<PathBuf>,
+}
 
-    if monitor_ids.is_empty() {
+/// 列出所有可识别的显示器
+fn list_monitors(json: bool, preview: Option<PathBuf>, config_path: Option<&Path>) -> Result<()> {
+    if !json {
+        println!("正在扫描显示器...\n");
+    }
+
+    // 只有找到配置文件时才标注匹配情况,内置默认配置没有真实的显示器 ID
+    let config = match Config::resolve(config_path) {
+        Ok(resolved) => resolved.source.path().is_some().then_some(resolved.config),
+        Err(e) => {
+            eprintln!("无法加载配置，跳过匹配检查: {:#}", e);
+            None
+        }
+    };
+
+    if let Some(dir) = &preview {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut listings = Vec::new();
+    for info in MonitorInfo::all()? {
+        let monitor_config = config.as_ref().map(|c| c.monitors.get(&info.id));
+        let preview = preview.as_ref().and_then(|dir| {
+            let path = dir.join(format!("{}.png", info.id));
+            match info
+                .capture_thumbnail(PREVIEW_SIZE)
+                .and_then(|image| Ok(image.save(&path)?))
+            {
+                Ok(()) => Some(path),
+                Err(e) => {
+                    eprintln!("无法保存 {} 的预览: {:#}", info.id, e);
+                    None
+                }
+            }
+        });
+        listings.push(MonitorListing {
+            configured: monitor_config.map(|m| m.is_some()),
+            enabled: monitor_config.map(|m| m.is_some_and(|m| m.enable)),
+            info,
+            preview,
+        });
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&listings)?);
+        return Ok(());
+    }
+
+    if listings.is_empty() {
         println!("未检测到任何显示器");
+        return Ok(());
+    }
+
+    println!("检测到 {} 个显示器:\n", listings.len());
+    let rows: Vec<Vec<String>> = listings
+        .iter()
+        .map(|listing| {
+            let info = &listing.info;
+            vec![
+                info.id.clone(),
+                info.name.clone(),
+                format!("{}x{}", info.width, info.height),
+                format!("{},{}", info.x, info.y),
+                format!("{}", info.scale_factor),
+                format!("{}°", info.rotation),
+                format!("{}Hz", info.frequency),
+                if info.is_primary { "是" } else { "" }.to_string(),
+                match (listing.configured, listing.enabled) {
+                    (None, _) => "-",
+                    (Some(false), _) => "未配置",
+                    (Some(true), Some(true)) => "已启用",
+                    (Some(true), _) => "已禁用",
+                }
+                .to_string(),
+            ]
+        })
+        .collect();
+    print_table(
+        &[
+            "ID",
+            "名称",
+            "分辨率",
+            "位置",
+            "缩放",
+            "旋转",
+            "刷新率",
+            "主屏",
+            "配置",
+        ],
+        &rows,
+    );
+
+    if let Some(config) = &config {
+        let mut missing: Vec<&String> = config
+            .monitors
+            .keys()
+            .filter(|id| !listings.iter().any(|l| &l.info.id == *id))
+            .collect();
+        missing.sort();
+        for id in missing {
+            println!("\n配置中的显示器 {} 当前未连接", id);
+        }
+    }
+
+    for listing in &listings {
+        if let Some(path) = &listing.preview {
+            println!("预览: {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// 表格中窗口标题的最大字符数
+const TITLE_WIDTH: usize = 40;
+
+/// `list-windows` 的一行输出
+#[derive(Serialize)]
+struct WindowListing {
+    #[serde(flatten)]
+    details: WindowDetails,
+    /// 命中的窗口规则,未指定 `--test-rules` 时不输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rules: Option<Vec<String>>,
+}
+
+/// 列出所有窗口,可选地检查窗口规则的匹配情况
+fn list_windows(json: bool, test_rules: bool, config_path: Option<&Path>) -> Result<()> {
+    let rules = if test_rules {
+        let resolved = Config::resolve(config_path)?;
+        if !json {
+            println!("配置来源: {}", resolved.source);
+        }
+        let rules = WindowRules::new(&resolved.config.windows)?;
+        if !json && rules.is_empty() {
+            println!("配置中没有窗口规则");
+        }
+        Some(rules)
     } else {
-        println!("检测到 {} 个显示器:\n", monitor_ids.len());
-        for (idx, id) in monitor_ids.iter().enumerate() {
-            println!("  [{}] {}", idx + 1, id);
+        None
+    };
+
+    if !json {
+        println!("正在扫描窗口...\n");
+    }
+
+    let listings: Vec<WindowListing> = WindowDetails::all()?
+        .into_iter()
+        .map(|details| WindowListing {
+            rules: rules
+                .as_ref()
+                .map(|rules| rules.matching(&details.app_name, &details.title)),
+            details,
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&listings)?);
+        return Ok(());
+    }
+
+    if listings.is_empty() {
+        println!("未检测到任何窗口");
+        return Ok(());
+    }
+
+    println!("检测到 {} 个窗口:\n", listings.len());
+    let flag = |value: bool| if value { "是" } else { "" }.to_string();
+    let rows: Vec<Vec<String>> = listings
+        .iter()
+        .map(|listing| {
+            let details = &listing.details;
+            let mut row = vec![
+                details.id.to_string(),
+                details.app_name.clone(),
+                truncate(&details.title, TITLE_WIDTH),
+                details.pid.to_string(),
+                format!("{}x{}", details.width, details.height),
+                format!("{},{}", details.x, details.y),
+                details.monitor.clone().unwrap_or_else(|| "-".to_string()),
+                flag(details.is_minimized),
+                flag(details.is_focused),
+            ];
+            if let Some(rules) = &listing.rules {
+                row.push(rules.join(", "));
+            }
+            row
+        })
+        .collect();
+
+    let mut headers = vec![
+        "ID",
+        "应用",
+        "标题",
+        "PID",
+        "大小",
+        "位置",
+        "显示器",
+        "最小化",
+        "焦点",
+    ];
+    if rules.is_some() {
+        headers.push("规则");
+    }
+    print_table(&headers, &rows);
+
+    Ok(())
+}
+
+/// 超过 `max` 个字符时截断并加省略号
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// 按显示宽度对齐打印表格,中文字符按两列计算
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let width = |s: &str| {
+        s.chars()
+            .map(|c| if c.is_ascii() { 1 } else { 2 })
+            .sum::<usize>()
+    };
+    let mut widths: Vec<usize> = headers.iter().map(|h| width(h)).collect();
+    for row in rows {
+        for (column, cell) in row.iter().enumerate() {
+            widths[column] = widths[column].max(width(cell));
         }
     }
 
+    let print_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &w)| format!("{}{}", cell, " ".repeat(w - width(cell))))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(headers.to_vec());
+    for row in rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+}
+
+/// 显示使用的配置文件,`resolved` 时显示最终生效的配置
+fn show_config(config_path: Option<&Path>, resolved: bool) -> Result<()> {
+    if !resolved {
+        let source = ConfigSource::locate(config_path)?;
+        println!("# 配置来源: {}", source);
+        match source.path() {
+            Some(path) => print!("{}", std::fs::read_to_string(path)?),
+            None => print!("{}", toml::to_string_pretty(&Config::default())?),
+        }
+        return Ok(());
+    }
+
+    let resolved = Config::resolve(config_path)?;
+    println!("# 配置来源: {}", resolved.source);
+    for name in &resolved.overrides {
+        println!("# 环境变量覆盖: {}", name);
+    }
+    print!("{}", toml::to_string_pretty(&resolved.config.redacted())?);
+
     Ok(())
 }
 
+/// 检查配置文件并列出所有问题
+fn validate_config(config_path: Option<&Path>, deny_warnings: bool) -> Result<()> {
+    let source = ConfigSource::locate(config_path)?;
+    let content = source.path().map(std::fs::read_to_string).transpose()?;
+    println!("检查配置: {}", source);
+
+    // 语法或类型错误时无法继续检查配置内容
+    let mut diagnostics = content.as_deref().map(check_syntax).unwrap_or_default();
+    if diagnostics.is_empty() {
+        match Config::merge_layers(content.as_deref(), env_vars()) {
+            Ok(merged) => {
+                let config = merged.config;
+                diagnostics.extend(check_unknown_fields(&merged.unknown_fields));
+                diagnostics.extend(config.diagnose());
+                diagnostics.extend(check_storage_writable(&config));
+                match Capture::get_all_monitors_id() {
+                    Ok(attached) => diagnostics.extend(check_attached_monitors(&config, &attached)),
+                    Err(e) => diagnostics.push(Diagnostic::warning(
+                        &[],
+                        format!("无法获取显示器列表，跳过显示器检查: {}", e),
+                    )),
+                }
+            }
+            Err(e) => diagnostics.push(Diagnostic::error(&[], format!("{:#}", e))),
+        }
+        if let Some(content) = &content {
+            locate(content, &mut diagnostics);
+        }
+    }
+
+    for diagnostic in &diagnostics {
+        match (source.path(), diagnostic.location) {
+            (Some(path), Some(_)) => println!("{}:{}", path.display(), diagnostic),
+            _ => println!("{}", diagnostic),
+        }
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+    if diagnostics.is_empty() {
+        println!("配置有效");
+    } else {
+        println!("\n{} 个错误，{} 个警告", errors, warnings);
+    }
+
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        anyhow::bail!("配置校验未通过");
+    }
+
+    Ok(())
+}
+
+/// 把配置文件升级到当前格式,原文件备份为 `<文件名>.v<旧版本>.bak`
+fn migrate_config(config_path: Option<&Path>, dry_run: bool) -> Result<()> {
+    let source = ConfigSource::locate(config_path)?;
+    let Some(path) = source.path() else {
+        anyhow::bail!("未找到配置文件，无需升级");
+    };
+
+    let content = std::fs::read_to_string(path)?;
+    let migration = migrate::migrate(&content)?;
+    if migration.is_noop() {
+        println!("配置文件已是最新版本 ({})", migration.to);
+        return Ok(());
+    }
+
+    println!(
+        "{}: 版本 {} -> {}",
+        path.display(),
+        migration.from,
+        migration.to
+    );
+    for change in &migration.changes {
+        println!("  - {}", change);
+    }
+
+    if dry_run {
+        println!();
+        print!("{}", migration.content);
+        return Ok(());
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", migration.from));
+    std::fs::copy(path, &backup)?;
+    std::fs::write(path, &migration.content)?;
+    println!("已升级，原文件备份为 {}", Path::new(&backup).display());
+
+    Ok(())
+}
+
+/// 根据检测到的显示器生成配置文件
+fn init_config(output: Option<PathBuf>, force: bool) -> Result<()> {
+    let path = match output {
+        Some(path) => path,
+        None => Config::default_config_path()?,
+    };
+    if path.exists() && !force {
+        anyhow::bail!("配置文件已存在: {}（使用 --force 覆盖）", path.display());
+    }
+
+    println!("正在扫描显示器...");
+    let monitor_ids = Capture::get_all_monitors_id().unwrap_or_else(|e| {
+        eprintln!("无法获取显示器列表: {}", e);
+        Vec::new()
+    });
+    if monitor_ids.is_empty() {
+        println!("未检测到任何显示器，将使用默认显示器配置，请稍后手动修改");
+    } else {
+        for id in &monitor_ids {
+            println!("  {}", id);
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, render_config(&monitor_ids))?;
+    println!("已生成配置文件: {}", path.display());
+
+    Ok(())
+}
+
+/// 向运行中的实例发送控制命令并打印结果
+#[cfg(unix)]
+async fn send_control(socket: Option<PathBuf>, request: Request) -> Result<()> {
+    let socket = socket.unwrap_or_else(control::default_socket_path);
+
+    match control::send_request(&socket, &request).await? {
+        Response::Ok { message } => println!("{}", message),
+        Response::Status(report) => print_status(&report),
+        Response::Error { message } => anyhow::bail!("控制命令执行失败: {}", message),
+    }
+
+    Ok(())
+}
+
+/// 控制 socket 基于 Unix domain socket,其他平台不支持控制命令
+#[cfg(not(unix))]
+async fn send_control(_socket: Option<PathBuf>, _request: Request) -> Result<()> {
+    anyhow::bail!("pause、resume、status 与 snapshot-now 依赖 Unix 控制 socket，当前平台不支持")
+}
+
+/// 打印运行中实例的状态
+#[cfg(unix)]
+fn print_status(report: &StatusReport) {
+    let format_time = |time: &DateTime<Utc>| time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+
+    println!("启动时间: {}", format_time(&report.started_at));
+    match report.pause {
+        PauseState::Running => println!("状态: 运行中"),
+        PauseState::Paused { until: None } => println!("状态: 已暂停（直到手动恢复）"),
+        PauseState::Paused { until: Some(until) } => {
+            println!("状态: 已暂停（{} 自动恢复）", format_time(&until))
+        }
+    }
+    if report.afk {
+        println!("用户: 离开 (AFK)");
+    }
+
+    match report.queue {
+        Some((depth, capacity)) => println!("队列: {}/{}", depth, capacity),
+        None => println!("队列: 未启动"),
+    }
+
+    println!("\n截图来源 ({}):", report.sources.len());
+    for (id, source) in &report.sources {
+        println!(
+            "  {} [{}]",
+            id,
+            if source.running {
+                "运行中"
+            } else {
+                "已停止"
+            }
+        );
+        println!(
+            "    检查间隔: {}",
+            source
+                .interval
+                .map(|ms| format!("{}ms", ms))
+                .unwrap_or_else(|| "-".to_string())
+        );
+        println!(
+            "    上次截图: {}",
+            source
+                .last_capture
+                .as_ref()
+                .map(|t| format_time(t).to_string())
+                .unwrap_or_else(|| "-".to_string())
+        );
+        println!(
+            "    上次哈希距离: {}",
+            source
+                .last_distance
+                .map(|d| d.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        );
+        println!(
+            "    截图/跳过: {}/{}  连续错误: {}",
+            source.captures, source.skips, source.consecutive_errors
+        );
+        if let Some(error) = &source.last_error {
+            println!("    最近错误: {}", error);
+        }
+    }
+
+    let storage = &report.storage;
+    println!(
+        "\n存储: {}  已保存 {}，失败 {}",
+        if storage.is_healthy() {
+            "正常"
+        } else {
+            "异常"
+        },
+        storage.saved,
+        storage.failed
+    );
+    if let Some(error) = &storage.last_error {
+        println!("  最近错误: {}", error);
+    }
+
+    match &report.activitywatch {
+        Some(aw) if aw.is_connected() => println!(
+            "ActivityWatch: 已连接 {} (v{})",
+            aw.url,
+            aw.version.as_deref().unwrap_or("?")
+        ),
+        Some(aw) => println!(
+            "ActivityWatch: 无法连接 {}: {}",
+            aw.url,
+            aw.error.as_deref().unwrap_or("未知错误")
+        ),
+        None => println!("ActivityWatch: 未配置"),
+    }
+}
+
 /// 开始截图任务
 async fn start_capture(
-    config_path: PathBuf,
+    config_path: Option<PathBuf>,
     max_count: usize,
     log_level: Option<String>,
     storage_path: Option<PathBuf>,
+    socket: Option<PathBuf>,
 ) -> Result<()> {
     // 初始化日志
     let log_level = log_level.as_deref().unwrap_or("info");
@@ -94,22 +907,49 @@ async fn start_capture(
 
     tracing_subscriber::fmt().with_env_filter(env_filter).init();
 
-    // 加载配置
-    let mut config = Config::load_from(&config_path)?;
+    // 按 --config、$AW_SCREENSHOT_CONFIG、XDG 路径、内置默认值的顺序加载配置
+    let resolved = Config::resolve(config_path.as_deref())?;
+    println!("配置来源: {}", resolved.source);
+    for name in &resolved.overrides {
+        println!("  环境变量覆盖: {}", name);
+    }
+    let mut config = resolved.config;
 
     // 如果指定了存储路径，覆盖配置
-    if let Some(path) = storage_path {
-        config.storage.local.enable = true;
-        config.storage.local.path = path.to_string_lossy().to_string();
+    let apply_overrides = |config: &mut Config| {
+        if let Some(path) = &storage_path {
+            config.storage.local.enable = true;
+            config.storage.local.path = path.to_string_lossy().to_string();
+        }
+    };
+    apply_overrides(&mut config);
+    if storage_path.is_some() {
         println!("已启用本地存储，路径: {}", config.storage.local.path);
     }
 
+    // 启动时解析 S3 凭证,尽早发现配置错误
+    if config.storage.s3.enable {
+        let credentials = config.storage.s3.credentials()?;
+        let bucket = config.storage.s3.bucket(&credentials)?;
+        println!(
+            "S3 存储桶: {}，凭证来源: {}",
+            bucket.name, credentials.source
+        );
+    }
+
     // 确保保存目录存在
-    let save_path = Path::new(&config.storage.local.path);
-    std::fs::create_dir_all(save_path)?;
+    if config.storage.local.enable {
+        std::fs::create_dir_all(&config.storage.local.path)?;
+    }
 
     // 创建统一捕获管理器
-    let mut capture = Capture::new(config.monitors.clone(), None);
+    let mut capture = Capture::new(config.monitors.clone(), Some(config.window.clone()))
+        .with_idle_config(config.idle.clone())
+        .with_window_targets(config.windows.targets.clone())
+        .with_desktop(config.desktop.clone())
+        .with_schedule(config.schedule.clone())
+        .with_focus(config.focus.clone())
+        .with_window_rules(WindowRules::new(&config.windows)?);
 
     // 创建通道接收截图结果
     let (tx, mut rx) = mpsc::channel(100);
@@ -118,6 +958,61 @@ async fn start_capture(
     let task_count = capture.start_capture(tx);
     println!("启动了 {} 个截图任务", task_count);
 
+    // 启动控制 socket,它的取消令牌同时用于停止下面的后台任务
+    let control_cancel = CancellationToken::new();
+    #[cfg(unix)]
+    let control_handle = {
+        let socket = socket.unwrap_or_else(control::default_socket_path);
+        let control_server = ControlServer::bind(&socket, capture.state())
+            .await?
+            .with_activitywatch(AwClient::new(config.activitywatch_url())?);
+        println!("控制 socket: {}", socket.display());
+        tokio::spawn(control_server.run(control_cancel.clone()))
+    };
+    #[cfg(not(unix))]
+    if socket.is_some() {
+        eprintln!("控制 socket 依赖 Unix domain socket，当前平台不支持，已忽略 --socket");
+    }
+
+    // 启动 AFK 状态同步,idle 配置修改后重启
+    let afk_state = capture.state();
+    let spawn_afk_watcher = |config: &Config| -> Result<_> {
+        let watcher = AfkWatcher::new(
+            AwClient::new(config.activitywatch_url())?,
+            config.idle.clone(),
+        );
+        let cancel_token = control_cancel.child_token();
+        let handle = tokio::spawn(watcher.run(afk_state.clone(), cancel_token.clone()));
+        Ok((handle, cancel_token))
+    };
+    let mut afk_handle = if config.idle.enable {
+        Some(spawn_afk_watcher(&config)?)
+    } else {
+        None
+    };
+
+    // 启动焦点窗口心跳,可随配置热更新启停
+    let heartbeat_state = capture.state();
+    let spawn_heartbeats = |config: &Config| -> Result<_> {
+        let heartbeats = WindowHeartbeats::new(AwClient::new(config.activitywatch_url())?);
+        let cancel_token = control_cancel.child_token();
+        let handle = tokio::spawn(heartbeats.run(heartbeat_state.clone(), cancel_token.clone()));
+        Ok((handle, cancel_token))
+    };
+    let heartbeat_enabled = |config: &Config| config.window.enable && config.window.heartbeat;
+    let mut heartbeat_handle = if heartbeat_enabled(&config) {
+        Some(spawn_heartbeats(&config)?)
+    } else {
+        None
+    };
+
+    // 监视配置文件变化(以及 SIGHUP),使用内置默认配置时无需监视
+    let (reload_tx, mut reload_rx) = mpsc::channel(4);
+    let watcher_handle = resolved
+        .source
+        .path()
+        .map(|path| tokio::spawn(ConfigWatcher::new(path).run(reload_tx, control_cancel.clone())));
+
     // 确定截图限制
     let count_limit = if max_count == 0 {
         usize::MAX
@@ -126,8 +1021,64 @@ async fn start_capture(
     };
 
     // 处理截图结果
-    let save_path_clone = config.storage.local.path.clone();
-    let handle = tokio::spawn(async move {
+    let store = CaptureStore::from_config(&config.storage)?;
+    let state = capture.state();
+    // 截图事件在单独的任务中上报,aw-server 无响应时不阻塞保存
+    let screenshot_events = ScreenshotEvents::new(
+        AwClient::new(config.activitywatch_url())?,
+        config.activitywatch.screenshot_events,
+    );
+    let (events_tx, events_rx) = mpsc::channel(100);
+    let events_handle = tokio::spawn(screenshot_events.run(events_rx));
+    println!("截图事件上报: {}", config.activitywatch.screenshot_events);
+    let mut annotator = if config.activitywatch.annotate {
+        println!("已启用 ActivityWatch 活动标注");
+        Some(ActivityAnnotator::new(
+            AwClient::new(config.activitywatch_url())?,
+            &config.activitywatch,
+        ))
+    } else {
+        None
+    };
+
+    // 活动标注在保存之后进行,查询 aw-server 不阻塞保存,标注结果补写进元数据后再上报事件
+    let (saved_tx, mut saved_rx) = mpsc::channel::<SavedScreenshot>(100);
+    let annotate_store = store.clone();
+    let annotate_handle = tokio::spawn(async move {
+        while let Some(mut saved) = saved_rx.recv().await {
+            if let Some(annotator) = &mut annotator {
+                let metadata = &mut saved.metadata;
+                metadata.activity = annotator.annotate(metadata.timestamp).await;
+                if let Some(activity) = &metadata.activity {
+                    println!("活动标注: {}", metadata.id);
+                    if let Some(app) = &activity.app {
+                        println!(
+                            "  活动窗口: {} - {}",
+                            app,
+                            activity.title.as_deref().unwrap_or("")
+                        );
+                    }
+                    if !activity.category.is_empty() {
+                        println!("  分类: {}", activity.category.join(" > "));
+                    }
+                    if let Some(url) = &activity.url {
+                        println!("  网址: {}", url);
+                    }
+                    if activity.afk == Some(true) {
+                        println!("  AFK 中");
+                    }
+                    if let Err(e) = annotate_store.update_metadata(metadata).await {
+                        eprintln!("写入活动标注失败: {:#}", e);
+                    }
+                }
+            }
+            if events_tx.send(saved).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut handle = tokio::spawn(async move {
         let mut count = 0;
         while let Some(result) = rx.recv().await {
             count += 1;
@@ -142,19 +1093,44 @@ async fn start_capture(
                 "{} 收到截图: {} at {}",
                 progress, result.monitor_id, result.timestamp
             );
+            if let Some(area) = &result.changed_area {
+                println!(
+                    "  变化区域: {}x{} @ ({}, {})，{}/{} 个分块",
+                    area.width, area.height, area.x, area.y, area.changed_tiles, area.total_tiles
+                );
+            }
+            if let Some(tick) = result.tick {
+                println!("  同步节拍: {}", tick);
+            }
+            if let Some(cursor) = &result.cursor {
+                println!(
+                    "  鼠标指针: ({}, {})，显示器内 ({}, {})",
+                    cursor.x, cursor.y, cursor.monitor_x, cursor.monitor_y
+                );
+            }
 
-            // 保存图片
-            let filename = format!(
-                "{}_{}.png",
-                result.monitor_id,
-                result.timestamp.format("%Y%m%d_%H%M%S")
-            );
-            let filepath = Path::new(&save_path_clone).join(filename);
-
-            if let Err(e) = result.image.save(&filepath) {
-                eprintln!("保存图片失败: {}", e);
-            } else {
-                println!("  -> 已保存到: {}", filepath.display());
+            // 保存图片与元数据
+            match store.save(&result).await {
+                Ok(saved) => {
+                    for location in saved.locations {
+                        println!("  -> 已保存到: {}", location);
+                    }
+                    state.record_storage_saved();
+                    let saved = SavedScreenshot {
+                        metadata: saved.metadata,
+                        enforce_interval: result.enforce_interval,
+                    };
+                    if saved_tx.try_send(saved).is_err() {
+                        eprintln!(
+                            "活动标注与事件上报积压，跳过截图 {} 的标注与事件",
+                            result.screenshot_id()
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!("保存图片失败: {:#}", e);
+                    state.record_storage_error(format!("{:#}", e));
+                }
             }
 
             if count >= count_limit {
@@ -164,12 +1140,187 @@ async fn start_capture(
         }
     });
 
-    // 等待截图任务完成
-    handle.await?;
+    // 等待截图任务完成,期间应用配置热更新
+    loop {
+        tokio::select! {
+            result = &mut handle => {
+                result?;
+                break;
+            }
+            Some(mut new_config) = reload_rx.recv() => {
+                apply_overrides(&mut new_config);
+                if new_config.storage != config.storage
+                    || new_config.activitywatch != config.activitywatch
+                {
+                    println!("存储与 ActivityWatch 配置的修改需要重启后生效");
+                }
+
+                let window_rules = match WindowRules::new(&new_config.windows) {
+                    Ok(rules) => rules,
+                    Err(e) => {
+                        eprintln!("窗口规则无效，忽略本次配置修改: {:#}", e);
+                        continue;
+                    }
+                };
+                if capture.update_schedule(new_config.schedule.clone()) {
+                    println!("共享时钟已更新: {}", new_config.schedule);
+                }
+                if capture.update_focus(new_config.focus.clone()) {
+                    println!("焦点切换触发已更新: {}", new_config.focus);
+                }
+                let summary = capture
+                    .reload(
+                        new_config.monitors.clone(),
+                        Some(new_config.window.clone()),
+                        new_config.windows.targets.clone(),
+                        new_config.idle.clone(),
+                        window_rules,
+                        new_config.desktop.clone(),
+                    )
+                    .await;
+                if summary.is_empty() {
+                    println!("配置已重新加载，截图任务无变化");
+                } else {
+                    println!(
+                        "配置已重新加载: 新增 {:?}，停止 {:?}，更新 {:?}",
+                        summary.added, summary.removed, summary.updated
+                    );
+                }
+
+                if new_config.idle != config.idle {
+                    if let Some((handle, cancel_token)) = afk_handle.take() {
+                        cancel_token.cancel();
+                        if let Err(e) = handle.await {
+                            eprintln!("AFK 状态同步任务异常退出: {}", e);
+                        }
+                    }
+                    if new_config.idle.enable {
+                        afk_handle = Some(spawn_afk_watcher(&new_config)?);
+                    }
+                }
+                match (heartbeat_enabled(&new_config), heartbeat_handle.take()) {
+                    (true, None) => heartbeat_handle = Some(spawn_heartbeats(&new_config)?),
+                    (false, Some((handle, cancel_token))) => {
+                        cancel_token.cancel();
+                        if let Err(e) = handle.await {
+                            eprintln!("窗口心跳任务异常退出: {}", e);
+                        }
+                    }
+                    (_, running) => heartbeat_handle = running,
+                }
+                config = new_config;
+            }
+        }
+    }
 
     // 优雅关闭
+    control_cancel.cancel();
+    #[cfg(unix)]
+    control_handle.await?;
+    if let Some(watcher_handle) = watcher_handle {
+        watcher_handle.await?;
+    }
+    if let Some((afk_handle, _)) = afk_handle {
+        afk_handle.await?;
+    }
+    if let Some((heartbeat_handle, _)) = heartbeat_handle {
+        heartbeat_handle.await?;
+    }
+    annotate_handle.await?;
+    events_handle.await?;
     capture.shutdown().await;
     println!("程序已退出");
 
     Ok(())
 }
+
+/// 启动网页查看器，按 Ctrl+C 退出
+async fn serve(
+    config_path: Option<&Path>,
+    bind: &str,
+    from: StorageSource,
+    storage_path: Option<PathBuf>,
+) -> Result<()> {
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+
+    let storage = open_storage(config_path, from, storage_path)?;
+    let viewer = Viewer::bind(bind, storage).await?;
+    println!("查看器: http://{}", viewer.local_addr()?);
+
+    let cancel_token = CancellationToken::new();
+    let handle = tokio::spawn(viewer.run(cancel_token.clone()));
+    tokio::signal::ctrl_c().await?;
+    cancel_token.cancel();
+    handle.await?;
+    println!("查看器已退出");
+
+    Ok(())
+}
+
+/// 按配置打开读取截图的存储
+fn open_storage(
+    config_path: Option<&Path>,
+    from: StorageSource,
+    storage_path: Option<PathBuf>,
+) -> Result<Storage> {
+    let resolved = Config::resolve(config_path)?;
+    println!("配置来源: {}", resolved.source);
+    let storage_config = resolved.config.storage;
+    let storage = match from {
+        StorageSource::Local => Storage::local(
+            storage_path.unwrap_or_else(|| PathBuf::from(&storage_config.local.path)),
+        ),
+        StorageSource::S3 => Storage::s3(&storage_config.s3)?,
+    };
+    println!("截图存储: {}", storage);
+    Ok(storage)
+}
+
+/// 导出的时间范围 `[start, end)`
+struct TimeRange {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+/// 导出延时动画
+async fn export_timelapse(
+    storage: Storage,
+    source: &str,
+    range: TimeRange,
+    max_frames: usize,
+    options: TimelapseOptions,
+    output: &Path,
+) -> Result<()> {
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+
+    // 尽早检查输出格式,避免列出存储后才报错
+    TimelapseFormat::from_path(output)?;
+    let index = StorageIndex::load(&storage).await?;
+    let entries = timelapse::select_frames(&index, source, range.start, range.end, max_frames);
+    if entries.is_empty() {
+        anyhow::bail!(
+            "No captures of {} between {} and {}",
+            source,
+            range.start.with_timezone(&Local),
+            range.end.with_timezone(&Local)
+        );
+    }
+    println!(
+        "导出 {} 张截图: {} 至 {}",
+        entries.len(),
+        entries[0]
+            .timestamp
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        entries[entries.len() - 1]
+            .timestamp
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+    );
+
+    let count = timelapse::write_timelapse(&storage, entries, options, output).await?;
+    println!("已导出 {} 帧到 {}", count, output.display());
+    Ok(())
+}
@@ -1,54 +1,250 @@
 use anyhow::{Context, Result};
+use image::ImageFormat;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
 use std::fs;
 use std::path::Path;
 
+use crate::config::credentials::Secret;
+use crate::config::diagnostics::Diagnostic;
+use crate::config::layered::env_vars;
+
+/// 当前配置文件格式版本,旧版本可用 `config migrate` 升级
+pub const CONFIG_VERSION: u32 = 2;
+
+/// 所有字段都有默认值,配置文件中可以只写需要修改的部分
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    /// 配置文件格式版本,缺省视为引入版本号之前的 1
+    #[serde(default = "legacy_config_version")]
+    pub config_version: u32,
     pub activitywatch: ActivityWatchConfig,
     pub storage: StorageConfig,
     pub monitors: HashMap<String, MonitorConfig>,
     pub window: WindowConfig,
+    pub windows: WindowsConfig,
+    pub desktop: DesktopConfig,
+    pub schedule: ScheduleConfig,
+    pub focus: FocusConfig,
+    pub idle: IdleConfig,
     pub logging: LoggingConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn legacy_config_version() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ActivityWatchConfig {
     pub host: String,
     pub port: u16,
+    /// 保存截图后如何向 aw-server 上报截图事件
+    pub screenshot_events: ScreenshotEventMode,
+    /// 查询 aw-server,为截图标注活动窗口、分类、网址与 AFK 状态
+    pub annotate: bool,
+    /// 事件结束早于截图多久(毫秒)仍视为截图时的活动,容忍 watcher 心跳的延迟
+    pub annotate_max_age: u64,
+    /// 截图时间相差不超过该值(毫秒)时复用上一次查询的结果
+    pub annotate_cache: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for ActivityWatchConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 5600,
+            screenshot_events: ScreenshotEventMode::Off,
+            annotate: false,
+            annotate_max_age: 5000,
+            annotate_cache: 1000,
+        }
+    }
+}
+
+/// 截图事件的上报方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreenshotEventMode {
+    /// 不上报
+    #[default]
+    Off,
+    /// 每张截图一个事件
+    Event,
+    /// 以心跳上报,同一来源连续相似的截图合并为一个持续事件
+    Heartbeat,
+}
+
+impl fmt::Display for ScreenshotEventMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Event => "event",
+            Self::Heartbeat => "heartbeat",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StorageConfig {
     pub s3: S3Config,
     pub local: LocalConfig,
+    pub thumbnail: ThumbnailConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct S3Config {
     pub enable: bool,
     pub bucket: String,
     pub region: String,
     pub endpoint: String,
-    pub access_key: String,
-    pub secret_key: String,
+    /// 明文密钥,建议留空并改用环境变量、凭证文件或 `credential_process`
+    pub access_key: Secret,
+    pub secret_key: Secret,
+    /// AWS shared-credentials 格式的凭证文件,权限必须为 600 或更严格
+    pub credentials_file: String,
+    /// 凭证文件中的 profile,为空时使用 `$AWS_PROFILE` 或 `default`
+    pub profile: String,
+    /// 输出 AWS `credential_process` JSON 的外部命令
+    pub credential_process: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            bucket: "aw-screenshots".to_string(),
+            region: "us-east-1".to_string(),
+            endpoint: "https://s3.amazonaws.com".to_string(),
+            access_key: Secret::default(),
+            secret_key: Secret::default(),
+            credentials_file: String::new(),
+            profile: String::new(),
+            credential_process: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LocalConfig {
     pub enable: bool,
     pub path: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for LocalConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            path: "/tmp/aw-screenshots".to_string(),
+        }
+    }
+}
+
+/// 与截图一起保存的缩略图
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThumbnailConfig {
+    pub enable: bool,
+    /// 缩略图长边的最大像素数
+    pub max_size: u32,
+    pub format: ThumbnailFormat,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            max_size: 320,
+            format: ThumbnailFormat::Jpeg,
+        }
+    }
+}
+
+/// 缩略图的编码格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    #[default]
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ThumbnailFormat {
+    pub fn image_format(self) -> ImageFormat {
+        match self {
+            Self::Jpeg => ImageFormat::Jpeg,
+            Self::Png => ImageFormat::Png,
+            Self::Webp => ImageFormat::WebP,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        self.image_format().to_mime_type()
+    }
+}
+
+impl fmt::Display for ThumbnailFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Jpeg => "jpeg",
+            Self::Png => "png",
+            Self::Webp => "webp",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MonitorConfig {
     pub enable: bool,
     pub interval: u64,
     pub enforce_interval: u64,
     pub dhash_resolution: u32,
     pub dhash_threshold: u32,
+    /// 自适应检查间隔,未配置时固定使用 `interval`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adaptive: Option<AdaptiveConfig>,
+    /// 分块变化检测,未配置时按整屏 dHash 判断画面是否变化
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_detection: Option<ChangeDetectionConfig>,
+    /// 是否在截图中绘制鼠标指针(目前只支持 X11),指针位置总会尽量记录
+    pub draw_cursor: bool,
+    /// 是否保存整屏截图,只关心 `regions` 时可关闭
+    pub capture_full: bool,
+    /// 从整屏截图中裁剪出的区域,每个区域单独去重并产生独立的截图
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<RegionConfig>,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            interval: 1000,
+            enforce_interval: 30000,
+            dhash_resolution: 16,
+            dhash_threshold: 10,
+            adaptive: None,
+            change_detection: None,
+            draw_cursor: false,
+            capture_full: true,
+            regions: Vec::new(),
+        }
+    }
 }
 
 impl fmt::Display for MonitorConfig {
@@ -57,11 +253,141 @@ impl fmt::Display for MonitorConfig {
             f,
             "interval={}ms, enforce={}ms, resolution={}, threshold={}",
             self.interval, self.enforce_interval, self.dhash_resolution, self.dhash_threshold
+        )?;
+        if let Some(adaptive) = &self.adaptive {
+            write!(f, ", adaptive=({})", adaptive)?;
+        }
+        if let Some(detection) = &self.change_detection {
+            write!(f, ", tiles=({})", detection)?;
+        }
+        if self.draw_cursor {
+            write!(f, ", cursor")?;
+        }
+        if !self.capture_full {
+            write!(f, ", full=false")?;
+        }
+        if !self.regions.is_empty() {
+            let names: Vec<&str> = self.regions.iter().map(|r| r.name.as_str()).collect();
+            write!(f, ", regions=[{}]", names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// 自适应检查间隔
+///
+/// 连续画面相似时间隔按 `backoff_factor` 逐步增大到 `max_interval`,
+/// 哈希距离超过阈值时立即回到 `min_interval`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveConfig {
+    pub min_interval: u64,
+    pub max_interval: u64,
+    pub backoff_factor: f64,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            min_interval: 500,
+            max_interval: 10000,
+            backoff_factor: 1.5,
+        }
+    }
+}
+
+impl fmt::Display for AdaptiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min={}ms, max={}ms, backoff={}",
+            self.min_interval, self.max_interval, self.backoff_factor
         )
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 分块变化检测网格的最大行列数
+pub const MAX_TILE_GRID: u32 = 64;
+
+/// 分块变化检测
+///
+/// 把整屏截图切成网格分别计算 dHash,变化的分块数达到 `min_changed_tiles` 时保存截图,
+/// 避免闪烁的光标或时钟使整屏 dHash 超过阈值,而小范围的真实修改又被忽略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChangeDetectionConfig {
+    /// 网格列数与行数(1-64)
+    pub columns: u32,
+    pub rows: u32,
+    /// 单个分块的 dHash 汉明距离达到该值视为变化(0-64)
+    pub tile_threshold: u32,
+    pub min_changed_tiles: u32,
+    /// 计算 dHash 前涂黑的区域,坐标同 `regions`
+    pub masks: Vec<MaskConfig>,
+    /// 保存变化热力图的目录,用于调整阈值,留空不保存
+    pub heatmap_dir: String,
+}
+
+impl Default for ChangeDetectionConfig {
+    fn default() -> Self {
+        Self {
+            columns: 16,
+            rows: 9,
+            tile_threshold: 8,
+            min_changed_tiles: 1,
+            masks: Vec::new(),
+            heatmap_dir: String::new(),
+        }
+    }
+}
+
+impl fmt::Display for ChangeDetectionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}, tile_threshold={}, min_changed={}, masks={}",
+            self.columns,
+            self.rows,
+            self.tile_threshold,
+            self.min_changed_tiles,
+            self.masks.len()
+        )
+    }
+}
+
+/// 变化检测时忽略的矩形区域
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaskConfig {
+    /// 可选的名称,仅用于说明
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 显示器上的命名裁剪区域
+///
+/// 坐标与大小以显示器截图的像素为单位,相对于截图左上角
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegionConfig {
+    /// 区域名称,同一显示器内唯一,截图 ID 为 `{monitor_id}@{name}`
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// 未配置时使用所在显示器的设置
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dhash_resolution: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dhash_threshold: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WindowConfig {
     pub enable: bool,
     pub interval: u64,
@@ -69,34 +395,290 @@ pub struct WindowConfig {
     pub dhash_resolution: u32,
     pub dhash_threshold: u32,
     pub enable_ocr: bool,
+    /// 是否把焦点窗口以 `currentwindow` 心跳发送到 aw-server
+    pub heartbeat: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            interval: 1000,
+            enforce_interval: 30000,
+            dhash_resolution: 16,
+            dhash_threshold: 10,
+            enable_ocr: false,
+            heartbeat: false,
+        }
+    }
 }
 
 impl fmt::Display for WindowConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "interval={}ms, enforce={}ms, resolution={}, threshold={}, ocr={}",
+            "interval={}ms, enforce={}ms, resolution={}, threshold={}, ocr={}, heartbeat={}",
             self.interval,
             self.enforce_interval,
             self.dhash_resolution,
             self.dhash_threshold,
-            self.enable_ocr
+            self.enable_ocr,
+            self.heartbeat
+        )
+    }
+}
+
+/// 虚拟桌面拼接截图
+///
+/// 同一时刻截取所有启用的显示器,按各自的 x/y 位置拼接成一张图后整体去重
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopConfig {
+    pub enable: bool,
+    pub interval: u64,
+    pub enforce_interval: u64,
+    pub dhash_resolution: u32,
+    pub dhash_threshold: u32,
+    /// 只保存拼接后的截图,不再单独启动各显示器的截图任务
+    pub replace_monitors: bool,
+}
+
+impl Default for DesktopConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            interval: 1000,
+            enforce_interval: 30000,
+            dhash_resolution: 16,
+            dhash_threshold: 10,
+            replace_monitors: false,
+        }
+    }
+}
+
+impl fmt::Display for DesktopConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "interval={}ms, enforce={}ms, resolution={}, threshold={}",
+            self.interval, self.enforce_interval, self.dhash_resolution, self.dhash_threshold
+        )?;
+        if self.replace_monitors {
+            write!(f, ", replace_monitors")?;
+        }
+        Ok(())
+    }
+}
+
+/// 共享截图时钟
+///
+/// 启用 `synchronized` 后由一个时钟驱动所有截图来源,各来源自己的 `interval` 不再生效
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    pub synchronized: bool,
+    /// 时钟的节拍间隔(毫秒)
+    pub interval: u64,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            synchronized: false,
+            interval: 1000,
+        }
+    }
+}
+
+impl fmt::Display for ScheduleConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "synchronized={}, interval={}ms",
+            self.synchronized, self.interval
+        )
+    }
+}
+
+/// 窗口焦点切换时立即截图
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FocusConfig {
+    pub enable: bool,
+    /// 切换后等待新窗口渲染的时间(毫秒),期间再次切换会重新计时
+    pub settle_delay: u64,
+    /// 是否同时触发显示器与桌面截图,关闭时只触发焦点窗口截图
+    pub trigger_monitors: bool,
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            settle_delay: 300,
+            trigger_monitors: true,
+        }
+    }
+}
+
+impl fmt::Display for FocusConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "settle_delay={}ms, trigger_monitors={}",
+            self.settle_delay, self.trigger_monitors
+        )
+    }
+}
+
+/// 按应用名与窗口标题匹配窗口的规则
+///
+/// `app` 与 `title` 均为正则表达式,留空表示不限制,同时配置时需同时满足
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowRule {
+    /// 规则名称,用于日志与 `list-windows --test-rules`
+    pub name: String,
+    pub app: String,
+    pub title: String,
+}
+
+impl fmt::Display for WindowRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "app={:?}, title={:?}", self.app, self.title)
+    }
+}
+
+/// 按规则匹配并持续截图的窗口,即使窗口没有焦点
+///
+/// 每个目标作为独立任务运行,匹配到的每个窗口各自去重
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowTarget {
+    /// 目标名称,必填且唯一,用作任务与截图 ID
+    pub name: String,
+    pub app: String,
+    pub title: String,
+    pub enable: bool,
+    pub interval: u64,
+    pub enforce_interval: u64,
+    pub dhash_resolution: u32,
+    pub dhash_threshold: u32,
+}
+
+impl WindowTarget {
+    /// 目标的匹配条件
+    pub fn rule(&self) -> WindowRule {
+        WindowRule {
+            name: self.name.clone(),
+            app: self.app.clone(),
+            title: self.title.clone(),
+        }
+    }
+}
+
+impl Default for WindowTarget {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            app: String::new(),
+            title: String::new(),
+            enable: true,
+            interval: 10000,
+            enforce_interval: 300000,
+            dhash_resolution: 16,
+            dhash_threshold: 10,
+        }
+    }
+}
+
+impl fmt::Display for WindowTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, interval={}ms, enforce={}ms, resolution={}, threshold={}",
+            self.rule(),
+            self.interval,
+            self.enforce_interval,
+            self.dhash_resolution,
+            self.dhash_threshold
+        )
+    }
+}
+
+/// 按规则处理特定窗口
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowsConfig {
+    /// 隐私规则:匹配的窗口永远不会被截图,优先于 `targets`
+    pub exclude: Vec<WindowRule>,
+    pub targets: Vec<WindowTarget>,
+}
+
+/// AFK 期间的截图策略,AFK 状态来自 aw-server 的 afkstatus bucket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleConfig {
+    pub enable: bool,
+    /// afkstatus bucket id,留空则自动选择最近更新的 afkstatus bucket
+    pub bucket: String,
+    /// 查询 AFK 状态的间隔(毫秒)
+    pub poll_interval: u64,
+    /// AFK 期间的检查间隔(毫秒),0 表示 AFK 期间不截图
+    pub idle_interval: u64,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            bucket: String::new(),
+            poll_interval: 5000,
+            idle_interval: 60000,
+        }
+    }
+}
+
+impl fmt::Display for IdleConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "poll={}ms, idle_interval={}ms, bucket={}",
+            self.poll_interval,
+            self.idle_interval,
+            if self.bucket.is_empty() {
+                "auto"
+            } else {
+                &self.bucket
+            }
         )
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LoggingConfig {
     pub level: String,
 }
 
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+        }
+    }
+}
+
 impl Config {
     /// 从指定路径加载配置
     pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self> {
         let content = fs::read_to_string(path.as_ref()).context("Failed to read config file")?;
-        let config: Config = toml::from_str(&content).context("Failed to parse config file")?;
-        config.validate()?;
-        Ok(config)
+        Self::from_toml(&content)
+    }
+
+    /// 从 TOML 文本解析配置,叠加 `AW_SCREENSHOT_*` 环境变量覆盖后校验
+    pub fn from_toml(content: &str) -> Result<Self> {
+        Self::from_layers(Some(content), env_vars()).map(|(config, _)| config)
     }
 
     /// 获取默认配置文件路径
@@ -107,35 +689,28 @@ impl Config {
         Ok(config_dir.join("config.toml"))
     }
 
+    /// 校验配置,存在错误时返回所有错误信息(警告不影响加载)
     pub fn validate(&self) -> Result<()> {
-        if self.monitors.is_empty() {
-            anyhow::bail!("至少需要配置一个显示器");
-        }
-
-        let mut enable_monitor = 0;
-        for (name, monitor) in &self.monitors {
-            if monitor.interval == 0 {
-                anyhow::bail!("显示器 {} 的 interval 必须大于 0", name);
-            }
-            if monitor.dhash_threshold > 255 {
-                anyhow::bail!("显示器 {} 的 dhash_threshold 必须在 0-255 之间", name);
-            }
-            if monitor.enable {
-                enable_monitor += 1;
-            }
-        }
-
-        if enable_monitor == 0 {
-            anyhow::bail!("至少需要启用一个显示器");
-        }
-
-        if !self.storage.s3.enable && !self.storage.local.enable {
-            anyhow::bail!("至少需要启用一个存储");
+        let errors: Vec<String> = self
+            .diagnose()
+            .into_iter()
+            .filter(Diagnostic::is_error)
+            .map(|d| d.message)
+            .collect();
+        if !errors.is_empty() {
+            anyhow::bail!("{}", errors.join("; "));
         }
 
         Ok(())
     }
 
+    /// 密钥替换为 `REDACTED` 的副本,用于展示
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        config.storage.s3 = self.storage.s3.redacted();
+        config
+    }
+
     pub fn activitywatch_url(&self) -> String {
         format!(
             "http://{}:{}",
@@ -147,48 +722,20 @@ impl Config {
 impl Default for Config {
     fn default() -> Self {
         let mut monitors = HashMap::new();
-        monitors.insert(
-            "default".to_string(),
-            MonitorConfig {
-                enable: true,
-                interval: 1000,
-                enforce_interval: 30000,
-                dhash_resolution: 16,
-                dhash_threshold: 10,
-            },
-        );
+        monitors.insert("default".to_string(), MonitorConfig::default());
 
         Self {
-            activitywatch: ActivityWatchConfig {
-                host: "localhost".to_string(),
-                port: 5600,
-            },
-            storage: StorageConfig {
-                s3: S3Config {
-                    enable: false,
-                    bucket: "aw-screenshots".to_string(),
-                    region: "us-east-1".to_string(),
-                    endpoint: "https://s3.amazonaws.com".to_string(),
-                    access_key: String::new(),
-                    secret_key: String::new(),
-                },
-                local: LocalConfig {
-                    enable: true,
-                    path: "/tmp/aw-screenshots".to_string(),
-                },
-            },
+            config_version: CONFIG_VERSION,
+            activitywatch: ActivityWatchConfig::default(),
+            storage: StorageConfig::default(),
             monitors,
-            window: WindowConfig {
-                enable: false,
-                interval: 1000,
-                enforce_interval: 30000,
-                dhash_resolution: 16,
-                dhash_threshold: 10,
-                enable_ocr: false,
-            },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-            },
+            window: WindowConfig::default(),
+            windows: WindowsConfig::default(),
+            desktop: DesktopConfig::default(),
+            schedule: ScheduleConfig::default(),
+            focus: FocusConfig::default(),
+            idle: IdleConfig::default(),
+            logging: LoggingConfig::default(),
         }
     }
 }
@@ -234,6 +781,27 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_validate_adaptive() {
+        let mut config = Config::default();
+        config.monitors.get_mut("default").unwrap().adaptive = Some(AdaptiveConfig {
+            min_interval: 500,
+            max_interval: 10000,
+            backoff_factor: 1.5,
+        });
+        assert!(config.validate().is_ok());
+
+        let adaptive = config
+            .monitors
+            .get_mut("default")
+            .unwrap()
+            .adaptive
+            .as_mut()
+            .unwrap();
+        adaptive.max_interval = 100;
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_validate_no_storage() {
         let mut config = Config::default();
@@ -242,6 +810,14 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_validate_idle_poll_interval() {
+        let mut config = Config::default();
+        config.idle.enable = true;
+        config.idle.poll_interval = 0;
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_activitywatch_url() {
         let config = Config::default();
@@ -0,0 +1,407 @@
+//! S3 凭证解析
+//!
+//! 按以下顺序查找,使用第一个可用的来源:
+//! 1. `storage.s3.credential_process` 外部命令
+//! 2. `storage.s3.credentials_file` 凭证文件(AWS shared-credentials 格式,权限必须为 600 或更严格)
+//! 3. 配置中的明文 `access_key`/`secret_key`(也可由 `AW_SCREENSHOT_STORAGE__S3__*` 环境变量覆盖)
+//! 4. `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY` 环境变量
+//! 5. `$AWS_SHARED_CREDENTIALS_FILE` 或 `~/.aws/credentials` 中的 profile
+
+use anyhow::{Context, Result, anyhow, bail};
+use ini::Ini;
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::warn;
+
+use crate::config::S3Config;
+
+/// 日志与 `config show` 中替代密钥的文本
+pub const REDACTED: &str = "<redacted>";
+
+/// 不会出现在 `Debug` 输出中的敏感字符串
+///
+/// 序列化时输出原值,保证配置可以完整写回
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 非空时替换为 `REDACTED`
+    pub fn redacted(&self) -> Self {
+        if self.is_empty() {
+            Self::default()
+        } else {
+            Self::new(REDACTED)
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            write!(f, "\"\"")
+        } else {
+            write!(f, "{:?}", REDACTED)
+        }
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+/// 凭证来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CredentialSource {
+    Process,
+    File { path: PathBuf, profile: String },
+    Inline,
+    Env,
+    SharedProfile { path: PathBuf, profile: String },
+}
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Process => write!(f, "credential_process"),
+            Self::File { path, profile } => write!(f, "{} [{}]", path.display(), profile),
+            Self::Inline => write!(f, "配置文件明文密钥"),
+            Self::Env => write!(f, "AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY 环境变量"),
+            Self::SharedProfile { path, profile } => {
+                write!(f, "{} [{}]", path.display(), profile)
+            }
+        }
+    }
+}
+
+/// 解析得到的 S3 凭证
+#[derive(Debug, Clone)]
+pub struct S3Credentials {
+    pub access_key: Secret,
+    pub secret_key: Secret,
+    pub session_token: Option<Secret>,
+    pub source: CredentialSource,
+}
+
+/// `credential_process` 输出的 JSON
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProcessOutput {
+    version: u32,
+    access_key_id: String,
+    secret_access_key: Secret,
+    session_token: Option<Secret>,
+}
+
+impl S3Config {
+    /// 按模块文档中的顺序查找 S3 凭证
+    pub fn credentials(&self) -> Result<S3Credentials> {
+        self.credentials_with(|name| env::var(name).ok(), dirs::home_dir())
+    }
+
+    fn credentials_with(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+        home: Option<PathBuf>,
+    ) -> Result<S3Credentials> {
+        let profile = if self.profile.is_empty() {
+            env("AWS_PROFILE").unwrap_or_else(|| "default".to_string())
+        } else {
+            self.profile.clone()
+        };
+
+        if !self.credential_process.is_empty() {
+            return run_credential_process(&self.credential_process);
+        }
+
+        if !self.credentials_file.is_empty() {
+            let path = expand_home(&self.credentials_file, home.as_deref());
+            check_permissions(&path)?;
+            let source = CredentialSource::File {
+                path: path.clone(),
+                profile: profile.clone(),
+            };
+            return load_profile(&path, &profile, source);
+        }
+
+        if !self.access_key.is_empty() || !self.secret_key.is_empty() {
+            if self.access_key.is_empty() || self.secret_key.is_empty() {
+                bail!("storage.s3 的 access_key 与 secret_key 必须同时配置");
+            }
+            warn!(
+                "S3 secret key is stored in plain text, consider credentials_file or credential_process"
+            );
+            return Ok(S3Credentials {
+                access_key: self.access_key.clone(),
+                secret_key: self.secret_key.clone(),
+                session_token: None,
+                source: CredentialSource::Inline,
+            });
+        }
+
+        if let (Some(access_key), Some(secret_key)) =
+            (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY"))
+        {
+            return Ok(S3Credentials {
+                access_key: Secret::new(access_key),
+                secret_key: Secret::new(secret_key),
+                session_token: env("AWS_SESSION_TOKEN").map(Secret::new),
+                source: CredentialSource::Env,
+            });
+        }
+
+        let shared = env("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .or_else(|| home.map(|home| home.join(".aws").join("credentials")));
+        if let Some(path) = shared.filter(|path| path.exists()) {
+            if let Err(e) = check_permissions(&path) {
+                warn!("{:#}", e);
+            }
+            let source = CredentialSource::SharedProfile {
+                path: path.clone(),
+                profile: profile.clone(),
+            };
+            return load_profile(&path, &profile, source);
+        }
+
+        bail!(
+            "未找到 S3 凭证,请设置 AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY、\
+             storage.s3.credentials_file 或 storage.s3.credential_process"
+        )
+    }
+
+    /// 使用解析得到的凭证创建存储桶客户端
+    pub fn bucket(&self, credentials: &S3Credentials) -> Result<Box<Bucket>> {
+        let credentials = Credentials::new(
+            Some(credentials.access_key.expose()),
+            Some(credentials.secret_key.expose()),
+            None,
+            credentials.session_token.as_ref().map(Secret::expose),
+            None,
+        )?;
+        let region = Region::Custom {
+            region: self.region.clone(),
+            endpoint: self.endpoint.clone(),
+        };
+        Ok(Bucket::new(&self.bucket, region, credentials)?.with_path_style())
+    }
+
+    /// 密钥替换为 `REDACTED` 的副本,用于展示
+    pub fn redacted(&self) -> Self {
+        Self {
+            access_key: self.access_key.redacted(),
+            secret_key: self.secret_key.redacted(),
+            ..self.clone()
+        }
+    }
+}
+
+fn expand_home(path: &str, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// 凭证文件不能被其他用户读写
+fn check_permissions(path: &Path) -> Result<()> {
+    let mode = fs::metadata(path)
+        .with_context(|| format!("无法读取凭证文件 {}", path.display()))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        bail!(
+            "凭证文件 {} 的权限 {:o} 过于宽松,请执行 chmod 600",
+            path.display(),
+            mode & 0o777
+        );
+    }
+    Ok(())
+}
+
+/// 从 AWS shared-credentials 格式的文件中读取 profile
+///
+/// profile 中也可以只配置 `credential_process`,此时来源为 `Process`,否则为 `source`
+fn load_profile(path: &Path, profile: &str, source: CredentialSource) -> Result<S3Credentials> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("无法读取凭证文件 {}", path.display()))?;
+    let ini = Ini::load_from_str_noescape(&content)
+        .with_context(|| format!("凭证文件 {} 格式错误", path.display()))?;
+    let section = ini
+        .section(Some(profile))
+        .ok_or_else(|| anyhow!("凭证文件 {} 中没有 profile [{}]", path.display(), profile))?;
+
+    if let Some(command) = section.get("credential_process") {
+        return run_credential_process(command);
+    }
+
+    let get = |key: &str| {
+        section
+            .get(key)
+            .map(Secret::from)
+            .ok_or_else(|| anyhow!("凭证文件 {} 的 [{}] 缺少 {}", path.display(), profile, key))
+    };
+    Ok(S3Credentials {
+        access_key: get("aws_access_key_id")?,
+        secret_key: get("aws_secret_access_key")?,
+        session_token: section.get("aws_session_token").map(Secret::from),
+        source,
+    })
+}
+
+/// 执行 `credential_process`,解析 AWS 约定的 JSON 输出
+fn run_credential_process(command: &str) -> Result<S3Credentials> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .context("无法执行 credential_process")?;
+    if !output.status.success() {
+        bail!(
+            "credential_process 执行失败 ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let parsed: ProcessOutput =
+        serde_json::from_slice(&output.stdout).context("credential_process 输出格式错误")?;
+    if parsed.version != 1 {
+        bail!("不支持的 credential_process 输出版本: {}", parsed.version);
+    }
+    Ok(S3Credentials {
+        access_key: Secret::new(parsed.access_key_id),
+        secret_key: parsed.secret_access_key,
+        session_token: parsed.session_token,
+        source: CredentialSource::Process,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn s3_config() -> S3Config {
+        Config::default().storage.s3
+    }
+
+    fn write_credentials(name: &str, content: &str, mode: u32) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "aw-watcher-screenshot-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_secret_is_redacted_in_debug() {
+        let mut config = s3_config();
+        config.secret_key = Secret::new("7RNiSmeZvWy4HVDtbA92");
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("7RNiSmeZvWy4HVDtbA92"));
+        assert!(debug.contains(REDACTED));
+        assert_eq!(config.redacted().secret_key.expose(), REDACTED);
+    }
+
+    #[test]
+    fn test_credentials_file_profile_and_permissions() {
+        let content = "[default]\naws_access_key_id = AKIADEFAULT\naws_secret_access_key = s3cr3t\n\n\
+                       [backup]\naws_access_key_id = AKIABACKUP\naws_secret_access_key = other\n";
+        let private = write_credentials("credentials-private", content, 0o600);
+        let mut config = s3_config();
+        config.credentials_file = private.to_string_lossy().to_string();
+        config.profile = "backup".to_string();
+
+        let credentials = config.credentials_with(|_| None, None).unwrap();
+        assert_eq!(credentials.access_key.expose(), "AKIABACKUP");
+        assert_eq!(credentials.secret_key.expose(), "other");
+        assert!(matches!(credentials.source, CredentialSource::File { .. }));
+
+        let public = write_credentials("credentials-public", content, 0o644);
+        config.credentials_file = public.to_string_lossy().to_string();
+        assert!(config.credentials_with(|_| None, None).is_err());
+
+        fs::remove_file(private).unwrap();
+        fs::remove_file(public).unwrap();
+    }
+
+    #[test]
+    fn test_env_and_shared_profile() {
+        let config = s3_config();
+        let env_credentials = config
+            .credentials_with(
+                |name| match name {
+                    "AWS_ACCESS_KEY_ID" => Some("AKIAENV".to_string()),
+                    "AWS_SECRET_ACCESS_KEY" => Some("envsecret".to_string()),
+                    _ => None,
+                },
+                None,
+            )
+            .unwrap();
+        assert_eq!(env_credentials.source, CredentialSource::Env);
+        assert_eq!(env_credentials.access_key.expose(), "AKIAENV");
+
+        let shared = write_credentials(
+            "shared-credentials",
+            "[work]\naws_access_key_id = AKIAWORK\naws_secret_access_key = worksecret\n",
+            0o600,
+        );
+        let shared_path = shared.to_string_lossy().to_string();
+        let shared_credentials = config
+            .credentials_with(
+                |name| match name {
+                    "AWS_SHARED_CREDENTIALS_FILE" => Some(shared_path.clone()),
+                    "AWS_PROFILE" => Some("work".to_string()),
+                    _ => None,
+                },
+                None,
+            )
+            .unwrap();
+        assert_eq!(shared_credentials.access_key.expose(), "AKIAWORK");
+        assert!(matches!(
+            shared_credentials.source,
+            CredentialSource::SharedProfile { ref profile, .. } if profile == "work"
+        ));
+        fs::remove_file(shared).unwrap();
+
+        assert!(config.credentials_with(|_| None, None).is_err());
+    }
+
+    #[test]
+    fn test_credential_process() {
+        let mut config = s3_config();
+        config.credential_process = r#"echo '{"Version": 1, "AccessKeyId": "AKIAPROC", "SecretAccessKey": "procsecret", "SessionToken": "token"}'"#.to_string();
+        let credentials = config.credentials_with(|_| None, None).unwrap();
+        assert_eq!(credentials.source, CredentialSource::Process);
+        assert_eq!(credentials.secret_key.expose(), "procsecret");
+        assert_eq!(credentials.session_token.unwrap().expose(), "token");
+
+        config.credential_process = "exit 3".to_string();
+        assert!(config.credentials_with(|_| None, None).is_err());
+    }
+}
@@ -0,0 +1,723 @@
+//! 配置诊断:一次列出所有问题,并定位到配置文件中的行列
+
+use regex::Regex;
+use reqwest::Url;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+use crate::config::{CONFIG_VERSION, Config, MAX_TILE_GRID, WindowRule, WindowTarget};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "错误"),
+            Self::Warning => write!(f, "警告"),
+        }
+    }
+}
+
+/// 单个配置问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 出问题的配置项路径,如 `["monitors", "DP-1_1920_1080_0_0", "interval"]`
+    pub path: Vec<String>,
+    pub message: String,
+    /// 配置文件中的行号与列号(从 1 开始)
+    pub location: Option<(usize, usize)>,
+}
+
+impl Diagnostic {
+    pub fn error(path: &[&str], message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, path, message)
+    }
+
+    pub fn warning(path: &[&str], message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, path, message)
+    }
+
+    fn new(severity: Severity, path: &[&str], message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            path: path.iter().map(|s| s.to_string()).collect(),
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// 点分隔的配置项名称
+    pub fn field(&self) -> String {
+        self.path.join(".")
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, column)) = self.location {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)?;
+        if !self.path.is_empty() {
+            write!(f, " ({})", self.field())?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// 检查配置内容,返回所有问题(不访问文件系统与显示器)
+    pub fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        if self.config_version > CONFIG_VERSION {
+            diagnostics.push(Diagnostic::error(
+                &["config_version"],
+                format!(
+                    "配置文件版本 {} 高于当前程序支持的版本 {}",
+                    self.config_version, CONFIG_VERSION
+                ),
+            ));
+        } else if self.config_version < CONFIG_VERSION {
+            diagnostics.push(Diagnostic::warning(
+                &["config_version"],
+                format!(
+                    "配置文件版本 {} 已过时,可运行 config migrate 升级到版本 {}",
+                    self.config_version, CONFIG_VERSION
+                ),
+            ));
+        }
+
+        if self.monitors.is_empty() {
+            diagnostics.push(Diagnostic::error(&["monitors"], "至少需要配置一个显示器"));
+        }
+
+        let mut names: Vec<&String> = self.monitors.keys().collect();
+        names.sort();
+        for name in names {
+            let monitor = &self.monitors[name];
+            let field = |key: &'static str| ["monitors", name.as_str(), key];
+            if monitor.interval == 0 {
+                diagnostics.push(Diagnostic::error(
+                    &field("interval"),
+                    format!("显示器 {} 的 interval 必须大于 0", name),
+                ));
+            }
+            if monitor.enforce_interval < monitor.interval {
+                diagnostics.push(Diagnostic::warning(
+                    &field("enforce_interval"),
+                    format!(
+                        "显示器 {} 的 enforce_interval ({}) 小于 interval ({}),不会生效",
+                        name, monitor.enforce_interval, monitor.interval
+                    ),
+                ));
+            }
+            if monitor.dhash_threshold > 255 {
+                diagnostics.push(Diagnostic::error(
+                    &field("dhash_threshold"),
+                    format!("显示器 {} 的 dhash_threshold 必须在 0-255 之间", name),
+                ));
+            }
+            if let Some(adaptive) = &monitor.adaptive {
+                let field = |key: &'static str| ["monitors", name.as_str(), "adaptive", key];
+                if adaptive.min_interval == 0 {
+                    diagnostics.push(Diagnostic::error(
+                        &field("min_interval"),
+                        format!("显示器 {} 的 adaptive.min_interval 必须大于 0", name),
+                    ));
+                }
+                if adaptive.max_interval < adaptive.min_interval {
+                    diagnostics.push(Diagnostic::error(
+                        &field("max_interval"),
+                        format!(
+                            "显示器 {} 的 adaptive.max_interval 不能小于 min_interval",
+                            name
+                        ),
+                    ));
+                }
+                if adaptive.backoff_factor.is_nan() || adaptive.backoff_factor < 1.0 {
+                    diagnostics.push(Diagnostic::error(
+                        &field("backoff_factor"),
+                        format!("显示器 {} 的 adaptive.backoff_factor 必须不小于 1.0", name),
+                    ));
+                }
+            }
+            if let Some(detection) = &monitor.change_detection {
+                let field =
+                    |key: &'static str| ["monitors", name.as_str(), "change_detection", key];
+                if detection.columns == 0 || detection.rows == 0 {
+                    diagnostics.push(Diagnostic::error(
+                        &field("columns"),
+                        format!("显示器 {} 的 change_detection 网格行列数必须大于 0", name),
+                    ));
+                }
+                if detection.columns > MAX_TILE_GRID || detection.rows > MAX_TILE_GRID {
+                    diagnostics.push(Diagnostic::error(
+                        &field("columns"),
+                        format!(
+                            "显示器 {} 的 change_detection 网格行列数不能超过 {}",
+                            name, MAX_TILE_GRID
+                        ),
+                    ));
+                }
+                if detection.tile_threshold > 64 {
+                    diagnostics.push(Diagnostic::error(
+                        &field("tile_threshold"),
+                        format!(
+                            "显示器 {} 的 change_detection.tile_threshold 必须在 0-64 之间",
+                            name
+                        ),
+                    ));
+                }
+                if detection.min_changed_tiles > detection.columns.saturating_mul(detection.rows) {
+                    diagnostics.push(Diagnostic::warning(
+                        &field("min_changed_tiles"),
+                        format!(
+                            "显示器 {} 的 change_detection.min_changed_tiles 超过分块总数,只会按 enforce_interval 保存",
+                            name
+                        ),
+                    ));
+                }
+                for (position, mask) in detection.masks.iter().enumerate() {
+                    if mask.width == 0 || mask.height == 0 {
+                        let index = position.to_string();
+                        diagnostics.push(Diagnostic::error(
+                            &["monitors", name.as_str(), "change_detection", "masks", index.as_str(), "width"],
+                            format!(
+                                "显示器 {} 的 change_detection.masks[{}] 的 width 与 height 必须大于 0",
+                                name, index
+                            ),
+                        ));
+                    }
+                }
+            }
+            if !monitor.capture_full && monitor.regions.is_empty() {
+                diagnostics.push(Diagnostic::warning(
+                    &["monitors", name.as_str(), "capture_full"],
+                    format!(
+                        "显示器 {} 关闭了 capture_full 且没有配置 regions,不会产生截图",
+                        name
+                    ),
+                ));
+            }
+            for (position, region) in monitor.regions.iter().enumerate() {
+                let index = position.to_string();
+                let field =
+                    |key: &'static str| ["monitors", name.as_str(), "regions", index.as_str(), key];
+                let label = format!("显示器 {} 的 regions[{}]", name, index);
+                if !is_valid_name(&region.name) {
+                    diagnostics.push(Diagnostic::error(
+                        &field("name"),
+                        format!(
+                            "{} 的 name {:?} 无效: 需为非空的字母、数字、`_` 或 `-`",
+                            label, region.name
+                        ),
+                    ));
+                } else if monitor.regions[..position]
+                    .iter()
+                    .any(|other| other.name == region.name)
+                {
+                    diagnostics.push(Diagnostic::error(
+                        &field("name"),
+                        format!("{} 的 name {:?} 与前面的区域重复", label, region.name),
+                    ));
+                }
+                if region.width == 0 || region.height == 0 {
+                    diagnostics.push(Diagnostic::error(
+                        &field("width"),
+                        format!("{} 的 width 与 height 必须大于 0", label),
+                    ));
+                }
+                if region.dhash_threshold.is_some_and(|t| t > 255) {
+                    diagnostics.push(Diagnostic::error(
+                        &field("dhash_threshold"),
+                        format!("{} 的 dhash_threshold 必须在 0-255 之间", label),
+                    ));
+                }
+            }
+        }
+
+        if !self.monitors.is_empty() && !self.monitors.values().any(|m| m.enable) {
+            diagnostics.push(Diagnostic::error(&["monitors"], "至少需要启用一个显示器"));
+        }
+
+        if self.window.enforce_interval < self.window.interval {
+            diagnostics.push(Diagnostic::warning(
+                &["window", "enforce_interval"],
+                format!(
+                    "window 的 enforce_interval ({}) 小于 interval ({}),不会生效",
+                    self.window.enforce_interval, self.window.interval
+                ),
+            ));
+        }
+
+        if self.window.heartbeat && !self.window.enable {
+            diagnostics.push(Diagnostic::warning(
+                &["window", "heartbeat"],
+                "window 的 heartbeat 需要同时启用 window,否则不会发送心跳",
+            ));
+        }
+
+        if self.desktop.enable {
+            let desktop = &self.desktop;
+            if desktop.interval == 0 {
+                diagnostics.push(Diagnostic::error(
+                    &["desktop", "interval"],
+                    "desktop 的 interval 必须大于 0",
+                ));
+            }
+            if desktop.enforce_interval < desktop.interval {
+                diagnostics.push(Diagnostic::warning(
+                    &["desktop", "enforce_interval"],
+                    format!(
+                        "desktop 的 enforce_interval ({}) 小于 interval ({}),不会生效",
+                        desktop.enforce_interval, desktop.interval
+                    ),
+                ));
+            }
+            if desktop.dhash_threshold > 255 {
+                diagnostics.push(Diagnostic::error(
+                    &["desktop", "dhash_threshold"],
+                    "desktop 的 dhash_threshold 必须在 0-255 之间",
+                ));
+            }
+        }
+
+        if self.schedule.synchronized && self.schedule.interval == 0 {
+            diagnostics.push(Diagnostic::error(
+                &["schedule", "interval"],
+                "schedule 的 interval 必须大于 0",
+            ));
+        }
+
+        check_window_rules("exclude", &self.windows.exclude, &mut diagnostics);
+        let target_rules: Vec<WindowRule> = self
+            .windows
+            .targets
+            .iter()
+            .map(WindowTarget::rule)
+            .collect();
+        check_window_rules("targets", &target_rules, &mut diagnostics);
+        for (position, target) in self.windows.targets.iter().enumerate() {
+            let index = position.to_string();
+            let field = |key: &'static str| ["windows", "targets", index.as_str(), key];
+            let label = format!("windows.targets[{}]", index);
+            if !is_valid_name(&target.name) {
+                diagnostics.push(Diagnostic::error(
+                    &field("name"),
+                    format!(
+                        "{} 的 name {:?} 无效: 需为非空的字母、数字、`_` 或 `-`",
+                        label, target.name
+                    ),
+                ));
+            } else if self.windows.targets[..position]
+                .iter()
+                .any(|other| other.name == target.name)
+            {
+                diagnostics.push(Diagnostic::error(
+                    &field("name"),
+                    format!("{} 的 name {:?} 与前面的目标重复", label, target.name),
+                ));
+            }
+            if target.interval == 0 {
+                diagnostics.push(Diagnostic::error(
+                    &field("interval"),
+                    format!("{} 的 interval 必须大于 0", label),
+                ));
+            }
+            if target.enforce_interval < target.interval {
+                diagnostics.push(Diagnostic::warning(
+                    &field("enforce_interval"),
+                    format!(
+                        "{} 的 enforce_interval ({}) 小于 interval ({}),不会生效",
+                        label, target.enforce_interval, target.interval
+                    ),
+                ));
+            }
+            if target.dhash_threshold > 255 {
+                diagnostics.push(Diagnostic::error(
+                    &field("dhash_threshold"),
+                    format!("{} 的 dhash_threshold 必须在 0-255 之间", label),
+                ));
+            }
+        }
+
+        if !self.storage.s3.enable && !self.storage.local.enable {
+            diagnostics.push(Diagnostic::error(&["storage"], "至少需要启用一个存储"));
+        }
+
+        if self.storage.s3.enable {
+            let s3 = &self.storage.s3;
+            if let Err(message) = check_http_url(&s3.endpoint) {
+                diagnostics.push(Diagnostic::error(
+                    &["storage", "s3", "endpoint"],
+                    format!("S3 endpoint {:?} 无效: {}", s3.endpoint, message),
+                ));
+            }
+            if s3.region.is_empty() {
+                diagnostics.push(Diagnostic::error(
+                    &["storage", "s3", "region"],
+                    "S3 region 不能为空",
+                ));
+            }
+            if !is_valid_bucket_name(&s3.bucket) {
+                diagnostics.push(Diagnostic::error(
+                    &["storage", "s3", "bucket"],
+                    format!(
+                        "S3 bucket {:?} 无效: 需为 3-63 个小写字母、数字、`.` 或 `-`",
+                        s3.bucket
+                    ),
+                ));
+            }
+        }
+
+        if self.storage.local.enable && self.storage.local.path.is_empty() {
+            diagnostics.push(Diagnostic::error(
+                &["storage", "local", "path"],
+                "本地存储路径不能为空",
+            ));
+        }
+
+        if self.storage.thumbnail.enable && self.storage.thumbnail.max_size == 0 {
+            diagnostics.push(Diagnostic::error(
+                &["storage", "thumbnail", "max_size"],
+                "缩略图的 max_size 必须大于 0",
+            ));
+        }
+
+        let aw = &self.activitywatch;
+        if aw.host.is_empty() || aw.host.contains(['/', ' ']) {
+            diagnostics.push(Diagnostic::error(
+                &["activitywatch", "host"],
+                format!("ActivityWatch host {:?} 无效,只需填写主机名或 IP", aw.host),
+            ));
+        } else if let Err(message) = check_http_url(&self.activitywatch_url()) {
+            diagnostics.push(Diagnostic::error(
+                &["activitywatch", "host"],
+                format!("ActivityWatch 地址无效: {}", message),
+            ));
+        }
+        if aw.port == 0 {
+            diagnostics.push(Diagnostic::error(
+                &["activitywatch", "port"],
+                "ActivityWatch port 必须大于 0",
+            ));
+        }
+
+        if self.idle.enable && self.idle.poll_interval == 0 {
+            diagnostics.push(Diagnostic::error(
+                &["idle", "poll_interval"],
+                "idle 的 poll_interval 必须大于 0",
+            ));
+        }
+
+        diagnostics
+    }
+}
+
+/// 检查 `[[windows.<kind>]]` 规则:至少配置一个条件,且都是有效的正则表达式
+fn check_window_rules(kind: &str, rules: &[WindowRule], diagnostics: &mut Vec<Diagnostic>) {
+    for (index, rule) in rules.iter().enumerate() {
+        let index = index.to_string();
+        if rule.app.is_empty() && rule.title.is_empty() {
+            diagnostics.push(Diagnostic::error(
+                &["windows", kind, &index],
+                format!("windows.{}[{}] 至少需要配置 app 或 title", kind, index),
+            ));
+        }
+        for (field, pattern) in [("app", &rule.app), ("title", &rule.title)] {
+            if let Err(e) = Regex::new(pattern) {
+                diagnostics.push(Diagnostic::error(
+                    &["windows", kind, &index, field],
+                    format!(
+                        "windows.{}[{}].{} 不是有效的正则表达式: {}",
+                        kind, index, field, e
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// 用于截图 ID 的名称只允许字母、数字、`_` 与 `-`
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn check_http_url(input: &str) -> Result<(), String> {
+    let url = Url::parse(input).map_err(|e| e.to_string())?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("不支持的协议 {}", url.scheme()));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err("缺少主机名".to_string());
+    }
+    Ok(())
+}
+
+fn is_valid_bucket_name(name: &str) -> bool {
+    (3..=63).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-')
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+/// 把无法识别的配置项转为警告
+pub fn check_unknown_fields(unknown_fields: &[Vec<String>]) -> Vec<Diagnostic> {
+    unknown_fields
+        .iter()
+        .map(|path| Diagnostic {
+            severity: Severity::Warning,
+            path: path.clone(),
+            message: format!("未知配置项 {},将被忽略", path.join(".")),
+            location: None,
+        })
+        .collect()
+}
+
+/// 检查启用的显示器是否已连接
+pub fn check_attached_monitors(config: &Config, attached: &[String]) -> Vec<Diagnostic> {
+    let mut names: Vec<&String> = config
+        .monitors
+        .iter()
+        .filter(|(_, monitor)| monitor.enable)
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+    names
+        .into_iter()
+        .filter(|name| !attached.contains(name))
+        .map(|name| {
+            Diagnostic::warning(
+                &["monitors", name.as_str()],
+                format!("显示器 {} 当前未连接", name),
+            )
+        })
+        .collect()
+}
+
+/// 检查本地存储路径是否可写,目录不存在时检查最近的已存在上级目录
+pub fn check_storage_writable(config: &Config) -> Vec<Diagnostic> {
+    if !config.storage.local.enable || config.storage.local.path.is_empty() {
+        return Vec::new();
+    }
+
+    let path = Path::new(&config.storage.local.path);
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        return Vec::new();
+    };
+    let field = ["storage", "local", "path"];
+    if !existing.is_dir() {
+        return vec![Diagnostic::error(
+            &field,
+            format!("本地存储路径 {} 不是目录", existing.display()),
+        )];
+    }
+
+    let probe = existing.join(format!(".aw-watcher-screenshot-{}", std::process::id()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Vec::new()
+        }
+        Err(e) => vec![Diagnostic::error(
+            &field,
+            format!("本地存储路径 {} 不可写: {}", existing.display(), e),
+        )],
+    }
+}
+
+/// 检查 TOML 文本:语法错误全部列出,否则按类型反序列化一次并报告第一个类型错误
+///
+/// 返回的诊断已填好行列号
+pub fn check_syntax(content: &str) -> Vec<Diagnostic> {
+    let (_, errors) = DeTable::parse_recoverable(content);
+    let errors = if errors.is_empty() {
+        toml::from_str::<Config>(content)
+            .err()
+            .into_iter()
+            .collect()
+    } else {
+        errors
+    };
+
+    errors
+        .into_iter()
+        .map(|e| Diagnostic {
+            severity: Severity::Error,
+            path: Vec::new(),
+            message: e.message().trim().to_string(),
+            location: e.span().map(|span| line_column(content, span.start)),
+        })
+        .collect()
+}
+
+/// 根据配置项路径为诊断填上行列号,找不到该项时使用最近的上级配置段
+pub fn locate(content: &str, diagnostics: &mut [Diagnostic]) {
+    let Ok(document) = DeTable::parse(content) else {
+        return;
+    };
+    for diagnostic in diagnostics.iter_mut().filter(|d| d.location.is_none()) {
+        diagnostic.location = find_span(document.get_ref(), &diagnostic.path)
+            .map(|start| line_column(content, start));
+    }
+}
+
+fn find_span(table: &DeTable<'_>, path: &[String]) -> Option<usize> {
+    let (first, rest) = path.split_first()?;
+    let (key, value) = table.iter().find(|(key, _)| key.get_ref() == first)?;
+    Some(find_value_span(value, rest).unwrap_or_else(|| span_start(key)))
+}
+
+/// 数组元素以序号作为路径,如 `["windows", "exclude", "0", "app"]`
+fn find_value_span(value: &Spanned<DeValue<'_>>, path: &[String]) -> Option<usize> {
+    match value.get_ref() {
+        DeValue::Table(child) => find_span(child, path),
+        DeValue::Array(items) => {
+            let (first, rest) = path.split_first()?;
+            let item = items.get(first.parse::<usize>().ok()?)?;
+            Some(find_value_span(item, rest).unwrap_or_else(|| span_start(item)))
+        }
+        _ => None,
+    }
+}
+
+fn span_start<T>(spanned: &Spanned<T>) -> usize {
+    spanned.span().start
+}
+
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnose_reports_all_problems() {
+        let mut config = Config::default();
+        let monitor = config.monitors.get_mut("default").unwrap();
+        monitor.interval = 0;
+        monitor.dhash_threshold = 300;
+        config.storage.s3.enable = true;
+        config.storage.s3.endpoint = "s3.amazonaws.com".to_string();
+        config.storage.s3.bucket = "My_Bucket".to_string();
+        config.activitywatch.host = "http://localhost".to_string();
+
+        let fields: Vec<String> = config.diagnose().iter().map(Diagnostic::field).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "monitors.default.interval",
+                "monitors.default.dhash_threshold",
+                "storage.s3.endpoint",
+                "storage.s3.bucket",
+                "activitywatch.host",
+            ]
+        );
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_enforce_interval_warning() {
+        let mut config = Config::default();
+        config.monitors.get_mut("default").unwrap().enforce_interval = 500;
+        let diagnostics = config.diagnose();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!diagnostics[0].is_error());
+        // 警告不影响加载
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_locate_fields() {
+        let content = fs::read_to_string("config/config.example.toml").unwrap();
+        let (mut config, _) = Config::from_layers(Some(&content), Vec::new()).unwrap();
+        let name = config.monitors.keys().next().unwrap().clone();
+        config.monitors.get_mut(&name).unwrap().interval = 0;
+
+        let mut diagnostics = config.diagnose();
+        locate(&content, &mut diagnostics);
+        let interval = diagnostics
+            .iter()
+            .find(|d| d.path.last().map(String::as_str) == Some("interval"))
+            .unwrap();
+        let (line, column) = interval.location.unwrap();
+        let line_text = content.lines().nth(line - 1).unwrap();
+        assert!(line_text[column - 1..].starts_with("interval"));
+    }
+
+    #[test]
+    fn test_window_rules() {
+        let content = "config_version = 2\n\n[[windows.exclude]]\napp = \"KeePassXC\"\n\n\
+                       [[windows.exclude]]\nname = \"broken\"\ntitle = \"(\"\n";
+        let config = Config::merge_layers(Some(content), Vec::new())
+            .unwrap()
+            .config;
+
+        let mut diagnostics = config.diagnose();
+        locate(content, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].field(), "windows.exclude.1.title");
+        assert_eq!(diagnostics[0].location.map(|(line, _)| line), Some(8));
+    }
+
+    #[test]
+    fn test_window_targets() {
+        let mut config = Config::default();
+        let target = |name: &str| WindowTarget {
+            name: name.to_string(),
+            app: "grafana".to_string(),
+            ..WindowTarget::default()
+        };
+        config.windows.targets = vec![target("dashboard"), target("dashboard"), target("a b")];
+
+        let fields: Vec<String> = config.diagnose().iter().map(Diagnostic::field).collect();
+        assert_eq!(
+            fields,
+            vec!["windows.targets.1.name", "windows.targets.2.name"]
+        );
+    }
+
+    #[test]
+    fn test_check_syntax() {
+        let errors = check_syntax("[activitywatch]\nhost = \nport = 5600\n");
+        assert_eq!(errors[0].location.map(|(line, _)| line), Some(2));
+
+        let content = fs::read_to_string("config/config.example.toml")
+            .unwrap()
+            .replace("port = 5600", "port = \"5600\"");
+        let errors = check_syntax(&content);
+        assert_eq!(errors.len(), 1);
+        let line = errors[0].location.unwrap().0;
+        assert_eq!(content.lines().nth(line - 1).unwrap(), "port = \"5600\"");
+    }
+
+    #[test]
+    fn test_attached_monitors() {
+        let config = Config::default();
+        assert!(check_attached_monitors(&config, &["default".to_string()]).is_empty());
+        assert_eq!(check_attached_monitors(&config, &[]).len(), 1);
+    }
+}
@@ -0,0 +1,438 @@
+//! 分层配置:配置文件(或内置默认值)之上叠加 `AW_SCREENSHOT_*` 环境变量
+//!
+//! 配置文件按 `--config`、`$AW_SCREENSHOT_CONFIG`、XDG 配置目录的顺序查找,
+//! 都不存在时使用 `Config::default()`。环境变量用 `__` 分隔层级,例如
+//! `AW_SCREENSHOT_STORAGE__S3__SECRET_KEY` 对应 `storage.s3.secret_key`
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::de::IntoDeserializer;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+use tracing::warn;
+
+use crate::config::Config;
+
+/// 指定配置文件路径的环境变量
+pub const CONFIG_PATH_ENV: &str = "AW_SCREENSHOT_CONFIG";
+
+/// 配置覆盖环境变量的前缀
+pub const ENV_PREFIX: &str = "AW_SCREENSHOT_";
+
+/// 最终使用的配置文件来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// `--config` 命令行参数
+    Cli(PathBuf),
+    /// `$AW_SCREENSHOT_CONFIG` 环境变量
+    Env(PathBuf),
+    /// XDG 配置目录下的 `aw-watcher-screenshot/config.toml`
+    Xdg(PathBuf),
+    /// 未找到配置文件,使用内置默认值
+    Default,
+}
+
+impl ConfigSource {
+    /// 按 `--config`、`$AW_SCREENSHOT_CONFIG`、XDG 路径的顺序查找配置文件
+    ///
+    /// 显式指定的路径不存在时报错,XDG 路径不存在时退回内置默认值
+    pub fn locate(cli_path: Option<&Path>) -> Result<Self> {
+        if let Some(path) = cli_path {
+            if !path.exists() {
+                bail!("配置文件不存在: {}", path.display());
+            }
+            return Ok(Self::Cli(path.to_path_buf()));
+        }
+
+        if let Some(path) = env::var_os(CONFIG_PATH_ENV).filter(|p| !p.is_empty()) {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                bail!(
+                    "{} 指定的配置文件不存在: {}",
+                    CONFIG_PATH_ENV,
+                    path.display()
+                );
+            }
+            return Ok(Self::Env(path));
+        }
+
+        if let Ok(path) = Config::default_config_path()
+            && path.exists()
+        {
+            return Ok(Self::Xdg(path));
+        }
+
+        Ok(Self::Default)
+    }
+
+    /// 配置文件路径,使用内置默认值时为 `None`
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Cli(path) | Self::Env(path) | Self::Xdg(path) => Some(path),
+            Self::Default => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cli(path) => write!(f, "{} (--config)", path.display()),
+            Self::Env(path) => write!(f, "{} (${})", path.display(), CONFIG_PATH_ENV),
+            Self::Xdg(path) => write!(f, "{} (XDG)", path.display()),
+            Self::Default => write!(f, "内置默认配置"),
+        }
+    }
+}
+
+/// 解析完成的配置及其来源
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    pub config: Config,
+    pub source: ConfigSource,
+    /// 生效的环境变量覆盖(变量名),按字母序排列
+    pub overrides: Vec<String>,
+}
+
+/// 合并各层后尚未校验的配置
+#[derive(Debug, Clone)]
+pub struct MergedConfig {
+    pub config: Config,
+    pub overrides: Vec<String>,
+    /// 无法识别、已被忽略的配置项路径
+    pub unknown_fields: Vec<Vec<String>>,
+}
+
+impl Config {
+    /// 按分层顺序解析最终生效的配置
+    pub fn resolve(cli_path: Option<&Path>) -> Result<ResolvedConfig> {
+        let source = ConfigSource::locate(cli_path)?;
+        let (config, overrides) = match source.path() {
+            Some(path) => (Self::load_from(path)?, env_override_names()),
+            None => Self::from_layers(None, env_vars())?,
+        };
+        Ok(ResolvedConfig {
+            config,
+            source,
+            overrides,
+        })
+    }
+
+    /// 以 TOML 文本(`None` 时为内置默认值)为基础叠加 `vars` 中的覆盖项,
+    /// 返回校验后的配置与生效的覆盖变量名
+    pub fn from_layers(
+        content: Option<&str>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(Self, Vec<String>)> {
+        let merged = Self::merge_layers(content, vars)?;
+        for path in &merged.unknown_fields {
+            warn!("Unknown config field {} ignored", path.join("."));
+        }
+        for diagnostic in merged.config.diagnose().iter().filter(|d| !d.is_error()) {
+            warn!("Config warning: {}", diagnostic);
+        }
+        merged.config.validate()?;
+        Ok((merged.config, merged.overrides))
+    }
+
+    /// 与 `from_layers` 相同但不校验,供 `config validate` 收集全部问题
+    pub fn merge_layers(
+        content: Option<&str>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<MergedConfig> {
+        let mut table = match content {
+            Some(content) => {
+                toml::from_str::<Table>(content).context("Failed to parse config file")?
+            }
+            None => Table::try_from(Config::default())?,
+        };
+        let overrides = apply_env_overrides(&mut table, vars)?;
+        let mut unknown_fields = Vec::new();
+        let config: Config = serde_ignored::deserialize(table.into_deserializer(), |path| {
+            unknown_fields.push(ignored_path(&path))
+        })
+        .context("Failed to parse config file")?;
+        Ok(MergedConfig {
+            config,
+            overrides,
+            unknown_fields,
+        })
+    }
+}
+
+fn ignored_path(path: &serde_ignored::Path<'_>) -> Vec<String> {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => Vec::new(),
+        Path::Seq { parent, index } => {
+            let mut segments = ignored_path(parent);
+            segments.push(index.to_string());
+            segments
+        }
+        Path::Map { parent, key } => {
+            let mut segments = ignored_path(parent);
+            segments.push(key.clone());
+            segments
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => ignored_path(parent),
+    }
+}
+
+/// 当前进程中生效的配置覆盖变量名
+pub fn env_override_names() -> Vec<String> {
+    override_vars(env_vars())
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// 当前进程中 UTF-8 编码的环境变量
+pub(crate) fn env_vars() -> impl Iterator<Item = (String, String)> {
+    env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+}
+
+/// 把 `AW_SCREENSHOT_*` 环境变量写入配置表
+///
+/// 值的类型参照已有值(或内置默认值中的同名字段),因此数字形式的密钥仍按字符串处理。
+/// `monitors` 下的显示器 ID 按不区分大小写匹配已有条目
+fn apply_env_overrides(
+    table: &mut Table,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<String>> {
+    let defaults = Table::try_from(Config::default())?;
+    let default_monitor = defaults
+        .get("monitors")
+        .and_then(|m| m.get("default"))
+        .and_then(Value::as_table)
+        .cloned()
+        .unwrap_or_default();
+
+    let mut applied = Vec::new();
+    for (key, raw) in override_vars(vars) {
+        let path: Vec<String> = key[ENV_PREFIX.len()..]
+            .split("__")
+            .map(|segment| segment.to_ascii_lowercase())
+            .collect();
+        if path.iter().any(|segment| segment.is_empty()) {
+            bail!("无效的配置覆盖变量: {}", key);
+        }
+
+        // 显示器 ID 由用户定义,字段类型参照默认显示器
+        let reference = if path[0] == "monitors" {
+            if path.len() < 3 {
+                bail!("配置覆盖变量 {} 需要指定显示器 ID 与字段", key);
+            }
+            lookup(&default_monitor, &path[2..])
+        } else {
+            let reference = lookup(&defaults, &path);
+            if reference.is_none() {
+                bail!("未知的配置覆盖变量: {}", key);
+            }
+            reference
+        };
+
+        let value = parse_override(&raw, reference)
+            .with_context(|| format!("配置覆盖变量 {} 的值无效", key))?;
+        set_path(table, &path, value).with_context(|| format!("无法应用配置覆盖变量 {}", key))?;
+        applied.push(key);
+    }
+
+    Ok(applied)
+}
+
+/// 筛选出配置覆盖变量并按变量名排序
+fn override_vars(vars: impl IntoIterator<Item = (String, String)>) -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = vars
+        .into_iter()
+        .filter(|(key, _)| key.starts_with(ENV_PREFIX) && key != CONFIG_PATH_ENV)
+        .collect();
+    vars.sort();
+    vars
+}
+
+/// 不区分大小写地查找表中的键
+fn find_key(table: &Table, segment: &str) -> Option<String> {
+    table
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case(segment))
+        .cloned()
+}
+
+fn lookup<'a>(table: &'a Table, path: &[String]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    let value = table.get(&find_key(table, first)?)?;
+    if rest.is_empty() {
+        return Some(value);
+    }
+    lookup(value.as_table()?, rest)
+}
+
+fn set_path(table: &mut Table, path: &[String], value: Value) -> Result<()> {
+    let (first, rest) = path.split_first().expect("override path is never empty");
+    let key = find_key(table, first).unwrap_or_else(|| first.clone());
+    if rest.is_empty() {
+        table.insert(key, value);
+        return Ok(());
+    }
+
+    let child = table
+        .entry(key.clone())
+        .or_insert_with(|| Value::Table(Table::new()));
+    let child = child
+        .as_table_mut()
+        .ok_or_else(|| anyhow!("{} 不是配置段", key))?;
+    set_path(child, rest, value)
+}
+
+/// 按参照值的类型解析环境变量,没有参照时依次尝试整数、浮点、布尔,最后按字符串处理
+fn parse_override(raw: &str, reference: Option<&Value>) -> Result<Value> {
+    Ok(match reference {
+        Some(Value::String(_)) => Value::String(raw.to_string()),
+        Some(Value::Integer(_)) => Value::Integer(raw.trim().parse()?),
+        Some(Value::Float(_)) => Value::Float(raw.trim().parse()?),
+        Some(Value::Boolean(_)) => Value::Boolean(raw.trim().parse()?),
+        Some(other) => bail!("不支持覆盖 {} 类型的配置项", other.type_str()),
+        None => {
+            let trimmed = raw.trim();
+            if let Ok(integer) = trimmed.parse() {
+                Value::Integer(integer)
+            } else if let Ok(float) = trimmed.parse() {
+                Value::Float(float)
+            } else if let Ok(boolean) = trimmed.parse() {
+                Value::Boolean(boolean)
+            } else {
+                Value::String(raw.to_string())
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let content = fs::read_to_string("config/config.example.toml").unwrap();
+        let (config, overrides) = Config::from_layers(
+            Some(&content),
+            vars(&[
+                ("AW_SCREENSHOT_STORAGE__S3__SECRET_KEY", "12345"),
+                ("AW_SCREENSHOT_ACTIVITYWATCH__PORT", "5700"),
+                ("AW_SCREENSHOT_IDLE__ENABLE", "true"),
+                ("AW_SCREENSHOT_CONFIG", "/ignored.toml"),
+                ("HOME", "/root"),
+            ]),
+        )
+        .unwrap();
+
+        // 数字形式的密钥仍是字符串
+        assert_eq!(config.storage.s3.secret_key.expose(), "12345");
+        assert_eq!(config.activitywatch.port, 5700);
+        assert!(config.idle.enable);
+        assert_eq!(
+            overrides,
+            vec![
+                "AW_SCREENSHOT_ACTIVITYWATCH__PORT",
+                "AW_SCREENSHOT_IDLE__ENABLE",
+                "AW_SCREENSHOT_STORAGE__S3__SECRET_KEY",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_env_overrides_on_defaults() {
+        let (config, _) = Config::from_layers(
+            None,
+            vars(&[
+                ("AW_SCREENSHOT_MONITORS__DEFAULT__INTERVAL", "2000"),
+                ("AW_SCREENSHOT_LOGGING__LEVEL", "debug"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.monitors["default"].interval, 2000);
+        assert_eq!(config.logging.level, "debug");
+    }
+
+    #[test]
+    fn test_env_overrides_match_monitor_id_case_insensitively() {
+        let mut table = Table::try_from(Config::default()).unwrap();
+        let monitor = table["monitors"]["default"].clone();
+        table["monitors"]
+            .as_table_mut()
+            .unwrap()
+            .insert("DP-1_1920_1080_0_0".to_string(), monitor);
+
+        apply_env_overrides(
+            &mut table,
+            vars(&[(
+                "AW_SCREENSHOT_MONITORS__dp-1_1920_1080_0_0__ENABLE",
+                "false",
+            )]),
+        )
+        .unwrap();
+        assert_eq!(
+            table["monitors"]["DP-1_1920_1080_0_0"]["enable"],
+            Value::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn test_invalid_env_overrides() {
+        let unknown = Config::from_layers(None, vars(&[("AW_SCREENSHOT_STORAGE__FTP", "x")]));
+        assert!(unknown.is_err());
+
+        let bad_type =
+            Config::from_layers(None, vars(&[("AW_SCREENSHOT_ACTIVITYWATCH__PORT", "high")]));
+        assert!(bad_type.is_err());
+
+        // 覆盖后的配置同样需要通过校验
+        let invalid = Config::from_layers(
+            None,
+            vars(&[("AW_SCREENSHOT_MONITORS__DEFAULT__INTERVAL", "0")]),
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_collected() {
+        let merged = Config::merge_layers(
+            Some("[activitywatch]\nhots = \"localhost\"\n\n[monitors.DP-1.adaptive]\nmin = 1\n"),
+            vars(&[("AW_SCREENSHOT_MONITORS__DP-1__FOO", "1")]),
+        )
+        .unwrap();
+        let mut unknown: Vec<String> = merged
+            .unknown_fields
+            .iter()
+            .map(|path| path.join("."))
+            .collect();
+        unknown.sort();
+        assert_eq!(
+            unknown,
+            vec![
+                "activitywatch.hots",
+                "monitors.DP-1.adaptive.min",
+                "monitors.DP-1.foo",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locate_explicit_path() {
+        let source = ConfigSource::locate(Some(Path::new("config/config.example.toml"))).unwrap();
+        assert_eq!(source.path(), Some(Path::new("config/config.example.toml")));
+        assert!(ConfigSource::locate(Some(Path::new("config/missing.toml"))).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! 配置文件格式升级
+//!
+//! 基于 toml_edit 修改原文件,保留注释、顺序与格式
+
+use anyhow::{Context, Result, bail};
+use toml_edit::{DocumentMut, Item, Table, value};
+
+use crate::config::{CONFIG_VERSION, Config, IdleConfig};
+
+/// 第 `i` 步把配置从版本 `i + 1` 升级到 `i + 2`,返回所做修改的说明
+type MigrationStep = fn(&mut DocumentMut) -> Vec<String>;
+
+const MIGRATIONS: &[MigrationStep] = &[migrate_v1_to_v2];
+
+/// 一次升级的结果
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub from: u32,
+    pub to: u32,
+    pub changes: Vec<String>,
+    /// 升级后的配置文件内容
+    pub content: String,
+}
+
+impl Migration {
+    pub fn is_noop(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/// 把配置文件内容升级到 `CONFIG_VERSION`
+pub fn migrate(content: &str) -> Result<Migration> {
+    let mut document: DocumentMut = content.parse().context("Failed to parse config file")?;
+    let from = match document.get("config_version") {
+        None => 1,
+        Some(item) => item
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|&v| v > 0)
+            .context("config_version 必须是正整数")?,
+    };
+    if from > CONFIG_VERSION {
+        bail!(
+            "配置文件版本 {} 高于当前程序支持的版本 {}",
+            from,
+            CONFIG_VERSION
+        );
+    }
+
+    let mut changes = Vec::new();
+    for (index, step) in MIGRATIONS.iter().enumerate().skip(from as usize - 1) {
+        changes.extend(step(&mut document));
+        set_version(&mut document, index as u32 + 2);
+    }
+
+    let content = document.to_string();
+    Config::merge_layers(Some(&content), Vec::new()).context("升级后的配置无法解析")?;
+
+    Ok(Migration {
+        from,
+        to: CONFIG_VERSION,
+        changes,
+        content,
+    })
+}
+
+fn set_version(document: &mut DocumentMut, version: u32) {
+    if let Some(existing) = document
+        .get_mut("config_version")
+        .and_then(Item::as_value_mut)
+    {
+        let decor = existing.decor().clone();
+        *existing = (version as i64).into();
+        *existing.decor_mut() = decor;
+        return;
+    }
+
+    document.insert("config_version", value(version as i64));
+    if let Some(mut key) = document.key_mut("config_version") {
+        key.leaf_decor_mut()
+            .set_prefix("# Config schema version, upgrade old files with `config migrate`\n");
+    }
+
+    // 原文件开头的注释属于第一个配置段,与版本号之间空一行
+    let first_table = document
+        .iter_mut()
+        .filter_map(|(_, item)| item.as_table_mut())
+        .min_by_key(|table| table.position().unwrap_or(isize::MAX));
+    if let Some(table) = first_table {
+        let prefix = table
+            .decor()
+            .prefix()
+            .and_then(|p| p.as_str())
+            .unwrap_or("")
+            .to_string();
+        if !prefix.starts_with('\n') {
+            table.decor_mut().set_prefix(format!("\n{}", prefix));
+        }
+    }
+}
+
+/// 版本 2:加入 `config_version` 与 `[idle]` 配置段
+fn migrate_v1_to_v2(document: &mut DocumentMut) -> Vec<String> {
+    let mut changes = vec!["新增 config_version".to_string()];
+
+    if !document.contains_key("idle") {
+        let defaults = IdleConfig::default();
+        let mut idle = Table::new();
+        idle.insert("enable", value(defaults.enable));
+        idle.insert("bucket", value(defaults.bucket));
+        idle.insert("poll_interval", value(defaults.poll_interval as i64));
+        idle.insert("idle_interval", value(defaults.idle_interval as i64));
+        idle.decor_mut().set_prefix(
+            "\n# Idle (AFK) Configuration, requires aw-watcher-afk\n\
+             #   bucket: afkstatus bucket id, empty to auto-detect\n\
+             #   idle_interval: check interval while AFK (ms), 0 pauses capture\n",
+        );
+        document.insert("idle", Item::Table(idle));
+        changes.push("新增 [idle] 配置段(默认关闭)".to_string());
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// 去掉版本号与 [idle] 段,模拟引入版本号之前的配置文件
+    fn legacy_config() -> String {
+        let content = fs::read_to_string("config/config.example.toml").unwrap();
+        let mut document: DocumentMut = content.parse().unwrap();
+        document.remove("config_version");
+        document.remove("idle");
+        document.to_string()
+    }
+
+    #[test]
+    fn test_steps_cover_all_versions() {
+        assert_eq!(MIGRATIONS.len() as u32 + 1, CONFIG_VERSION);
+    }
+
+    #[test]
+    fn test_migrate_legacy_config() {
+        let legacy = legacy_config();
+        let migration = migrate(&legacy).unwrap();
+        assert_eq!(migration.from, 1);
+        assert_eq!(migration.to, CONFIG_VERSION);
+        assert_eq!(migration.changes.len(), 2);
+
+        // 原有注释保留
+        assert!(
+            migration
+                .content
+                .contains("# ActivityWatch Server Configuration")
+        );
+        assert!(migration.content.contains("# DHash threshold"));
+
+        let merged = Config::merge_layers(Some(&migration.content), Vec::new()).unwrap();
+        assert_eq!(merged.config.config_version, CONFIG_VERSION);
+        assert!(merged.unknown_fields.is_empty());
+        assert!(
+            merged
+                .config
+                .diagnose()
+                .iter()
+                .all(|d| d.path.first().map(String::as_str) != Some("config_version"))
+        );
+
+        assert!(migrate(&migration.content).unwrap().is_noop());
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_version() {
+        let content = format!("config_version = {}\n", CONFIG_VERSION + 1);
+        assert!(migrate(&content).is_err());
+        assert!(migrate("config_version = 0\n").is_err());
+    }
+}
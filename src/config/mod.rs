@@ -1,4 +1,12 @@
 pub mod config;
+pub mod credentials;
+pub mod diagnostics;
 pub mod init;
+pub mod layered;
+pub mod migrate;
+pub mod reload;
+pub mod template;
 
 pub use config::*;
+pub use layered::*;
+pub use reload::*;
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::PathBuf;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::mpsc::Sender;
+use tokio::time::{Duration, interval};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::config::Config;
+
+/// 监视配置文件变化,并在收到 SIGHUP 时强制重新加载
+///
+/// 重新加载时同样叠加 `AW_SCREENSHOT_*` 环境变量覆盖。
+/// 只有通过 `Config::validate` 的配置才会发送给调用方,无效的修改会被记录并忽略
+pub struct ConfigWatcher {
+    path: PathBuf,
+    poll_interval: Duration,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+
+    /// 持续监视直到收到取消信号或接收端关闭
+    pub async fn run(self, sender: Sender<Config>, cancel_token: CancellationToken) {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => Some(hangup),
+            Err(e) => {
+                warn!("Failed to listen for SIGHUP: {}", e);
+                None
+            }
+        };
+        let mut ticker = interval(self.poll_interval);
+        let mut last_content = fs::read_to_string(&self.path).ok();
+
+        info!("Watching config file {}", self.path.display());
+
+        loop {
+            let forced = tokio::select! {
+                _ = ticker.tick() => false,
+                Some(()) = async { hangup.as_mut()?.recv().await } => {
+                    info!("Received SIGHUP, reloading config");
+                    true
+                }
+                _ = cancel_token.cancelled() => {
+                    info!("Config watcher received cancellation signal");
+                    break;
+                }
+            };
+
+            let content = match fs::read_to_string(&self.path) {
+                Ok(content) => content,
+                Err(e) => {
+                    // 编辑器保存时可能短暂删除文件,下次检查再读
+                    debug!("Failed to read config file: {}", e);
+                    continue;
+                }
+            };
+            if !forced && last_content.as_deref() == Some(content.as_str()) {
+                continue;
+            }
+            last_content = Some(content.clone());
+
+            match Config::from_toml(&content) {
+                Ok(config) => {
+                    info!("Config file {} reloaded", self.path.display());
+                    if sender.send(config).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Rejected config reload, keeping current config: {:#}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_reload_on_change_and_reject_invalid() {
+        let path = std::env::temp_dir().join(format!(
+            "aw-watcher-screenshot-reload-{}.toml",
+            std::process::id()
+        ));
+        let original = fs::read_to_string("config/config.example.toml").unwrap();
+        fs::write(&path, &original).unwrap();
+
+        let (tx, mut rx) = mpsc::channel(4);
+        let cancel_token = CancellationToken::new();
+        let watcher = ConfigWatcher {
+            path: path.clone(),
+            poll_interval: Duration::from_millis(20),
+        };
+        let handle = tokio::spawn(watcher.run(tx, cancel_token.clone()));
+
+        // 无效配置被拒绝,不会发送
+        fs::write(&path, "[activitywatch]\nhost = ").unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err());
+
+        fs::write(&path, original.replace("port = 5600", "port = 5700")).unwrap();
+        let config = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.activitywatch.port, 5700);
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,335 @@
+//! 生成带注释的配置文件,供 `config init` 使用
+
+use std::fmt::Write;
+
+use crate::config::{Config, MonitorConfig};
+
+/// 以 `Config::default()` 的取值生成带注释的配置文件,每个显示器一个配置段
+///
+/// `monitor_ids` 为空时保留默认配置中的显示器
+pub fn render_config(monitor_ids: &[String]) -> String {
+    let defaults = Config::default();
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "# aw-watcher-screenshot configuration, generated by `config init`.
+# Config file lookup order: --config, $AW_SCREENSHOT_CONFIG,
+# ~/.config/aw-watcher-screenshot/config.toml, then built-in defaults.
+# Any field can be overridden with AW_SCREENSHOT_<SECTION>__<FIELD>,
+# e.g. AW_SCREENSHOT_ACTIVITYWATCH__PORT=5666.
+"
+    );
+
+    let _ = writeln!(
+        out,
+        "# Config schema version, upgrade old files with `config migrate`
+config_version = {}
+",
+        defaults.config_version
+    );
+
+    let aw = &defaults.activitywatch;
+    let _ = writeln!(
+        out,
+        "# ActivityWatch Server Configuration
+[activitywatch]
+host = {}
+port = {}
+# Report saved screenshots to aw-server: \"off\", \"event\" (one event per
+# screenshot) or \"heartbeat\" (consecutive similar screenshots of a source
+# merge into one event, pulsetime derived from its enforce_interval)
+screenshot_events = \"{}\"
+# Annotate captures with the active app, title, category, browser URL and AFK
+# state from aw-server; events ending up to annotate_max_age ms before a capture
+# still count, captures within annotate_cache ms share one query
+annotate = {}
+annotate_max_age = {}
+annotate_cache = {}
+",
+        quote(&aw.host),
+        aw.port,
+        aw.screenshot_events,
+        aw.annotate,
+        aw.annotate_max_age,
+        aw.annotate_cache
+    );
+
+    let s3 = &defaults.storage.s3;
+    let _ = writeln!(
+        out,
+        "# S3 Storage Configuration
+[storage.s3]
+enable = {}
+bucket = {}
+region = {}
+endpoint = {}
+# Credentials are looked up in this order (first match wins):
+#   1. credential_process: command printing AWS credential_process JSON
+#   2. credentials_file: AWS shared-credentials INI file, must be chmod 600
+#   3. access_key/secret_key (plain text, avoid)
+#   4. AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY environment variables
+#   5. $AWS_SHARED_CREDENTIALS_FILE or ~/.aws/credentials
+# credential_process = \"pass show aws/aw-screenshots-json\"
+# credentials_file = \"~/.config/aw-watcher-screenshot/credentials\"
+# profile = \"default\"
+",
+        s3.enable,
+        quote(&s3.bucket),
+        quote(&s3.region),
+        quote(&s3.endpoint)
+    );
+
+    let local = &defaults.storage.local;
+    let _ = writeln!(
+        out,
+        "# Local Storage Configuration
+[storage.local]
+enable = {}
+path = {}
+",
+        local.enable,
+        quote(&local.path)
+    );
+
+    let thumbnail = &defaults.storage.thumbnail;
+    let _ = writeln!(
+        out,
+        "# Thumbnails saved next to each screenshot as <screenshot_id>.thumb.<ext>
+#   max_size: longest side in pixels
+#   format: \"jpeg\", \"png\" or \"webp\"
+[storage.thumbnail]
+enable = {}
+max_size = {}
+format = \"{}\"
+",
+        thumbnail.enable, thumbnail.max_size, thumbnail.format
+    );
+
+    let monitor = MonitorConfig::default();
+    let default_ids = ["default".to_string()];
+    let monitor_ids = if monitor_ids.is_empty() {
+        &default_ids[..]
+    } else {
+        monitor_ids
+    };
+    let _ = writeln!(
+        out,
+        "# Monitor Configuration
+# Section names are monitor ids as printed by `list-monitors`:
+# <name>_<width>_<height>_<x>_<y>
+#   interval: check interval (ms)
+#   enforce_interval: save a screenshot at least this often even if unchanged (ms)
+#   dhash_resolution / dhash_threshold: change detection sensitivity
+#   capture_full: save the whole screen, disable to keep only regions
+#   draw_cursor: draw the mouse cursor into screenshots (X11 only)
+# Named crop regions are deduplicated on their own and saved with id
+# <monitor_id>@<name>, e.g.:
+#   [[monitors.<id>.regions]]
+#   name = \"terminal\"
+#   x = 0
+#   y = 0
+#   width = 1280
+#   height = 720
+# Tile-based change detection ignores small noise such as a clock, with
+# optional masks and a heatmap for tuning:
+#   [monitors.<id>.change_detection]
+#   columns = 16
+#   rows = 9
+#   tile_threshold = 8
+#   heatmap_dir = \"/tmp/aw-screenshots/heatmaps\"
+"
+    );
+    for id in monitor_ids {
+        let _ = writeln!(
+            out,
+            "[monitors.{}]
+enable = {}
+interval = {}
+enforce_interval = {}
+dhash_resolution = {}
+dhash_threshold = {}
+",
+            key(id),
+            monitor.enable,
+            monitor.interval,
+            monitor.enforce_interval,
+            monitor.dhash_resolution,
+            monitor.dhash_threshold
+        );
+    }
+
+    let window = &defaults.window;
+    let _ = writeln!(
+        out,
+        "# Focused Window Capture Configuration
+#   heartbeat: send the focused window to aw-server as currentwindow
+#   heartbeats linked to its latest screenshot
+[window]
+enable = {}
+interval = {}
+enforce_interval = {}
+dhash_resolution = {}
+dhash_threshold = {}
+enable_ocr = {}
+heartbeat = {}
+",
+        window.enable,
+        window.interval,
+        window.enforce_interval,
+        window.dhash_resolution,
+        window.dhash_threshold,
+        window.enable_ocr,
+        window.heartbeat
+    );
+
+    out.push_str(
+        "# Window Rules
+# Windows matching an exclude rule are never captured. app and title are
+# regular expressions, both must match when set.
+# Check rules against open windows with `list-windows --test-rules`.
+# [[windows.exclude]]
+# name = \"passwords\"
+# app = \"(?i)keepassxc|1password\"
+# Windows matching a target are captured on their own schedule even when
+# unfocused, each matching window deduplicated separately. name is required
+# and must be unique.
+# [[windows.targets]]
+# name = \"dashboard\"
+# title = \"Grafana\"
+# interval = 10000
+# enforce_interval = 300000
+
+",
+    );
+
+    let desktop = &defaults.desktop;
+    let _ = writeln!(
+        out,
+        "# Desktop Screenshot Configuration
+# Stitches all enabled monitors into one image with id \"desktop\"
+#   replace_monitors: keep only the stitched image
+[desktop]
+enable = {}
+interval = {}
+enforce_interval = {}
+dhash_resolution = {}
+dhash_threshold = {}
+replace_monitors = {}
+",
+        desktop.enable,
+        desktop.interval,
+        desktop.enforce_interval,
+        desktop.dhash_resolution,
+        desktop.dhash_threshold,
+        desktop.replace_monitors
+    );
+
+    let schedule = &defaults.schedule;
+    let _ = writeln!(
+        out,
+        "# Shared Capture Clock
+#   synchronized: drive all sources from one clock and tag screenshots with
+#   the tick time, the per-source interval is ignored
+[schedule]
+synchronized = {}
+interval = {}
+",
+        schedule.synchronized, schedule.interval
+    );
+
+    let focus = &defaults.focus;
+    let _ = writeln!(
+        out,
+        "# Focus Change Trigger
+#   settle_delay: wait for the new window to render before capturing (ms)
+#   trigger_monitors: also capture monitors and desktop on focus change
+[focus]
+enable = {}
+settle_delay = {}
+trigger_monitors = {}
+",
+        focus.enable, focus.settle_delay, focus.trigger_monitors
+    );
+
+    let idle = &defaults.idle;
+    let _ = writeln!(
+        out,
+        "# Idle (AFK) Configuration, requires aw-watcher-afk
+#   bucket: afkstatus bucket id, empty to auto-detect
+#   idle_interval: check interval while AFK (ms), 0 pauses capture
+[idle]
+enable = {}
+bucket = {}
+poll_interval = {}
+idle_interval = {}
+",
+        idle.enable,
+        quote(&idle.bucket),
+        idle.poll_interval,
+        idle.idle_interval
+    );
+
+    let _ = write!(
+        out,
+        "# Logging Configuration
+[logging]
+level = {}
+",
+        quote(&defaults.logging.level)
+    );
+
+    out
+}
+
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+/// 显示器 ID 只含字母、数字、`_`、`-` 时用裸键,否则加引号
+fn key(id: &str) -> String {
+    if !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        id.to_string()
+    } else {
+        quote(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rendered_config_round_trips() {
+        let ids = vec![
+            "DP-1_2560_1440_0_0".to_string(),
+            "Generic PnP Monitor_1920_1080_2560_0".to_string(),
+        ];
+        let rendered = render_config(&ids);
+        let (config, _) = Config::from_layers(Some(&rendered), Vec::new()).unwrap();
+
+        assert_eq!(config.monitors.len(), 2);
+        assert_eq!(
+            config.monitors["Generic PnP Monitor_1920_1080_2560_0"],
+            Config::default().monitors["default"]
+        );
+        assert_eq!(config.storage, Config::default().storage);
+        assert_eq!(config.activitywatch, Config::default().activitywatch);
+        assert_eq!(config.idle, Config::default().idle);
+        assert_eq!(config.desktop, Config::default().desktop);
+        assert_eq!(config.schedule, Config::default().schedule);
+        assert_eq!(config.focus, Config::default().focus);
+        assert!(config.diagnose().is_empty());
+    }
+
+    #[test]
+    fn test_rendered_config_without_monitors() {
+        let rendered = render_config(&[]);
+        let (config, _) = Config::from_layers(Some(&rendered), Vec::new()).unwrap();
+        assert!(config.monitors.contains_key("default"));
+    }
+}
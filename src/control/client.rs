@@ -0,0 +1,27 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use crate::control::protocol::{Request, Response};
+
+/// 向运行中的实例发送一条控制命令并等待响应
+pub async fn send_request<P: AsRef<Path>>(path: P, request: &Request) -> Result<Response> {
+    let path = path.as_ref();
+    let stream = UnixStream::connect(path).await.with_context(|| {
+        format!(
+            "无法连接控制 socket {},请确认截图程序正在运行",
+            path.display()
+        )
+    })?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut payload = serde_json::to_string(request)?;
+    payload.push('\n');
+    writer.write_all(payload.as_bytes()).await?;
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+    let response = serde_json::from_str(&line).context("Failed to parse control response")?;
+    Ok(response)
+}
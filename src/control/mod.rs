@@ -0,0 +1,21 @@
+//! 控制 socket 基于 Unix domain socket,客户端与服务端只在 Unix 平台编译
+#[cfg(unix)]
+pub mod client;
+pub mod protocol;
+#[cfg(unix)]
+pub mod server;
+
+#[cfg(unix)]
+pub use client::*;
+pub use protocol::*;
+#[cfg(unix)]
+pub use server::*;
+
+/// 默认控制 socket 路径: `$XDG_RUNTIME_DIR/aw-watcher-screenshot.sock`,
+/// 无运行时目录时退回系统临时目录
+#[cfg(unix)]
+pub fn default_socket_path() -> std::path::PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("aw-watcher-screenshot.sock")
+}
@@ -0,0 +1,131 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::capture::{PauseState, SourceStatus, StorageHealth};
+
+/// 控制端发送给运行中实例的请求
+///
+/// 每个连接发送一行 JSON 请求,读取一行 JSON 响应
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// `duration_secs` 为 `None` 表示无限期暂停
+    Pause {
+        duration_secs: Option<u64>,
+    },
+    Resume,
+    Status,
+    SnapshotNow,
+}
+
+/// 运行中实例返回的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Ok { message: String },
+    Status(StatusReport),
+    Error { message: String },
+}
+
+/// `status` 命令返回的运行状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusReport {
+    pub started_at: DateTime<Utc>,
+    pub pause: PauseState,
+    pub afk: bool,
+    pub sources: BTreeMap<String, SourceStatus>,
+    /// 截图结果通道中等待处理的数量与容量,截图任务未启动时为 `None`
+    pub queue: Option<(usize, usize)>,
+    pub storage: StorageHealth,
+    /// 未配置 ActivityWatch 时为 `None`
+    pub activitywatch: Option<ActivityWatchHealth>,
+}
+
+/// aw-server 连通性检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityWatchHealth {
+    pub url: String,
+    pub version: Option<String>,
+    pub error: Option<String>,
+}
+
+impl ActivityWatchHealth {
+    pub fn is_connected(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// 解析形如 `30m`、`1h30m`、`90s`、`2d` 的时长,纯数字视为秒
+pub fn parse_duration(input: &str) -> Result<std::time::Duration> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(anyhow!("Empty duration"));
+    }
+    if let Ok(secs) = input.parse::<u64>() {
+        return Ok(std::time::Duration::from_secs(secs));
+    }
+
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(anyhow!("Invalid duration unit '{}' in '{}'", c, input)),
+        };
+        let value: u64 = number
+            .parse()
+            .map_err(|_| anyhow!("Missing number before '{}' in '{}'", c, input))?;
+        total = value
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(|| anyhow!("Duration '{}' is too long", input))?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(anyhow!("Missing unit after '{}' in '{}'", number, input));
+    }
+
+    Ok(std::time::Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap().as_secs(), 90);
+        assert_eq!(parse_duration("30m").unwrap().as_secs(), 30 * 60);
+        assert_eq!(parse_duration("1h30m").unwrap().as_secs(), 90 * 60);
+        assert_eq!(parse_duration("2d").unwrap().as_secs(), 2 * 24 * 3600);
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("99999999999999999999d").is_err());
+        assert!(parse_duration("999999999999999999d").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("m").is_err());
+    }
+
+    #[test]
+    fn test_request_format() {
+        let json = serde_json::to_string(&Request::Pause {
+            duration_secs: Some(1800),
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"command":"pause","duration_secs":1800}"#);
+
+        let request: Request = serde_json::from_str(r#"{"command":"snapshot_now"}"#).unwrap();
+        assert_eq!(request, Request::SnapshotNow);
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::activitywatch::AwClient;
+use crate::capture::{CaptureState, PauseState};
+use crate::control::protocol::{ActivityWatchHealth, Request, Response, StatusReport};
+
+/// 本地控制 socket 服务
+///
+/// 监听 Unix domain socket,将收到的命令应用到共享的 `CaptureState`
+pub struct ControlServer {
+    path: PathBuf,
+    listener: UnixListener,
+    state: CaptureState,
+    activitywatch: Option<AwClient>,
+}
+
+impl ControlServer {
+    /// 绑定控制 socket
+    ///
+    /// 如果路径上残留了无人监听的旧 socket 会先删除;若已有实例在监听则返回错误
+    pub async fn bind<P: AsRef<Path>>(path: P, state: CaptureState) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        if path.exists() {
+            if UnixStream::connect(&path).await.is_ok() {
+                anyhow::bail!("控制 socket {} 已被其他实例占用", path.display());
+            }
+            debug!("Removing stale control socket {}", path.display());
+            std::fs::remove_file(&path).context("Failed to remove stale control socket")?;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let listener = UnixListener::bind(&path).context("Failed to bind control socket")?;
+        info!("Control socket listening on {}", path.display());
+
+        Ok(Self {
+            path,
+            listener,
+            state,
+            activitywatch: None,
+        })
+    }
+
+    /// 在 `status` 中报告 aw-server 的连通性
+    pub fn with_activitywatch(mut self, client: AwClient) -> Self {
+        self.activitywatch = Some(client);
+        self
+    }
+
+    /// 处理控制连接,直到收到取消信号
+    pub async fn run(self, cancel_token: CancellationToken) {
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let state = self.state.clone();
+                        let activitywatch = self.activitywatch.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) =
+                                Self::handle_connection(stream, &state, activitywatch.as_ref()).await
+                            {
+                                warn!("Control connection error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Failed to accept control connection: {}", e),
+                },
+                _ = cancel_token.cancelled() => {
+                    info!("Control socket received cancellation signal");
+                    break;
+                }
+            }
+        }
+
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove control socket {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    async fn handle_connection(
+        stream: UnixStream,
+        state: &CaptureState,
+        activitywatch: Option<&AwClient>,
+    ) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await?;
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                debug!("Control request: {:?}", request);
+                Self::handle_request(request, state, activitywatch).await
+            }
+            Err(e) => Response::Error {
+                message: format!("Invalid request: {}", e),
+            },
+        };
+
+        let mut payload = serde_json::to_string(&response)?;
+        payload.push('\n');
+        writer.write_all(payload.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn handle_request(
+        request: Request,
+        state: &CaptureState,
+        activitywatch: Option<&AwClient>,
+    ) -> Response {
+        match request {
+            Request::Pause { duration_secs } => {
+                let duration = match duration_secs {
+                    Some(secs) => match i64::try_from(secs)
+                        .ok()
+                        .and_then(chrono::TimeDelta::try_seconds)
+                    {
+                        Some(duration) => Some(duration),
+                        None => {
+                            return Response::Error {
+                                message: format!("Pause duration too long: {}s", secs),
+                            };
+                        }
+                    },
+                    None => None,
+                };
+                let message = match state.pause(duration) {
+                    Ok(PauseState::Paused { until: Some(until) }) => {
+                        format!("已暂停,将于 {} 自动恢复", until.with_timezone(&Local))
+                    }
+                    Ok(_) => "已暂停,直到手动恢复".to_string(),
+                    Err(e) => {
+                        return Response::Error {
+                            message: e.to_string(),
+                        };
+                    }
+                };
+                Response::Ok { message }
+            }
+            Request::Resume => {
+                state.resume();
+                Response::Ok {
+                    message: "已恢复截图".to_string(),
+                }
+            }
+            Request::Status => {
+                let activitywatch = match activitywatch {
+                    Some(client) => Some(Self::check_activitywatch(client).await),
+                    None => None,
+                };
+                Response::Status(StatusReport {
+                    started_at: state.started_at(),
+                    pause: state.pause_state(),
+                    afk: state.is_afk(),
+                    sources: state.sources(),
+                    queue: state.queue_depth(),
+                    storage: state.storage(),
+                    activitywatch,
+                })
+            }
+            Request::SnapshotNow => {
+                state.request_snapshot();
+                Response::Ok {
+                    message: "已请求立即截图".to_string(),
+                }
+            }
+        }
+    }
+
+    async fn check_activitywatch(client: &AwClient) -> ActivityWatchHealth {
+        match client.info().await {
+            Ok(info) => ActivityWatchHealth {
+                url: client.base_url().to_string(),
+                version: Some(info.version),
+                error: None,
+            },
+            Err(e) => ActivityWatchHealth {
+                url: client.base_url().to_string(),
+                version: None,
+                error: Some(format!("{:#}", e)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::client::send_request;
+
+    #[tokio::test]
+    async fn test_pause_resume_roundtrip() {
+        let path = std::env::temp_dir().join(format!(
+            "aw-watcher-screenshot-test-{}.sock",
+            std::process::id()
+        ));
+        let state = CaptureState::new();
+        let server = ControlServer::bind(&path, state.clone()).await.unwrap();
+        let cancel_token = CancellationToken::new();
+        let handle = tokio::spawn(server.run(cancel_token.clone()));
+
+        let response = send_request(
+            &path,
+            &Request::Pause {
+                duration_secs: Some(60),
+            },
+        )
+        .await
+        .unwrap();
+        assert!(matches!(response, Response::Ok { .. }));
+        assert!(state.is_paused());
+
+        match send_request(&path, &Request::Status).await.unwrap() {
+            Response::Status(report) => {
+                assert!(matches!(
+                    report.pause,
+                    PauseState::Paused { until: Some(_) }
+                ))
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        send_request(&path, &Request::Resume).await.unwrap();
+        assert!(!state.is_paused());
+
+        // 过长的时长返回错误,而不是让守护进程崩溃
+        let response = send_request(
+            &path,
+            &Request::Pause {
+                duration_secs: Some(99_999_999_999_999_999),
+            },
+        )
+        .await
+        .unwrap();
+        assert!(matches!(response, Response::Error { .. }));
+        assert!(!state.is_paused());
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -1,10 +1,21 @@
 use chrono::{DateTime, Utc};
 use image::RgbaImage;
+use serde::{Deserialize, Serialize};
 
 pub struct CaptureResult {
     pub monitor_id: String,
     pub image: RgbaImage,
     pub timestamp: DateTime<Utc>,
+    /// 与上一张保存的截图相比发生变化的区域,仅在启用分块变化检测时提供
+    pub changed_area: Option<ChangedArea>,
+    /// 截图时的鼠标指针位置,无法读取指针时为 `None`
+    pub cursor: Option<CursorPosition>,
+    /// 同步截图时所属节拍的时刻(Unix 毫秒),同一节拍的截图取值相同
+    pub tick: Option<i64>,
+    /// 与上一张保存的截图相似,只是距上次保存已超过 `enforce_interval` 才保存
+    pub similar: bool,
+    /// 该来源的强制截图间隔(毫秒),未知时为 0
+    pub enforce_interval: u64,
 }
 
 impl CaptureResult {
@@ -13,6 +24,117 @@ impl CaptureResult {
             monitor_id,
             image,
             timestamp,
+            changed_area: None,
+            cursor: None,
+            tick: None,
+            similar: false,
+            enforce_interval: 0,
         }
     }
+
+    pub fn with_changed_area(mut self, changed_area: Option<ChangedArea>) -> Self {
+        self.changed_area = changed_area;
+        self
+    }
+
+    pub fn with_cursor(mut self, cursor: Option<CursorPosition>) -> Self {
+        self.cursor = cursor;
+        self
+    }
+
+    pub fn with_tick(mut self, tick: Option<i64>) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    pub fn with_dedup(mut self, similar: bool, enforce_interval: u64) -> Self {
+        self.similar = similar;
+        self.enforce_interval = enforce_interval;
+        self
+    }
+
+    /// 截图的唯一标识,格式为 `{monitor_id}_{%Y%m%d_%H%M%S%.3f}`,也用作保存的文件名
+    pub fn screenshot_id(&self) -> String {
+        screenshot_id(&self.monitor_id, self.timestamp)
+    }
+}
+
+/// 按来源与截图时间生成截图ID,见 [`CaptureResult::screenshot_id`]
+pub fn screenshot_id(monitor_id: &str, timestamp: DateTime<Utc>) -> String {
+    format!("{}_{}", monitor_id, timestamp.format("%Y%m%d_%H%M%S%.3f"))
+}
+
+/// 来源所属的截图流,同一截图流的截图属于同一条时间线
+///
+/// 焦点窗口截图的来源随窗口变化,统一为 `window`;窗口目标去掉末尾的窗口 ID
+pub fn screenshot_stream(source: &str) -> &str {
+    if source.starts_with("window_") {
+        return "window";
+    }
+    match source.rsplit_once('_') {
+        Some((target, window_id))
+            if source.starts_with("target_") && window_id.parse::<u32>().is_ok() =>
+        {
+            target
+        }
+        _ => source,
+    }
+}
+
+/// 变化分块的外接矩形(像素)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedArea {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// 变化的分块数与总分块数
+    pub changed_tiles: u32,
+    pub total_tiles: u32,
+}
+
+/// 截图时的鼠标指针位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorPosition {
+    /// 全局桌面坐标
+    pub x: i32,
+    pub y: i32,
+    /// 相对于显示器左上角的坐标
+    pub monitor_x: i32,
+    pub monitor_y: i32,
+    /// 指针是否在该显示器上
+    pub on_monitor: bool,
+}
+
+/// 截图时 ActivityWatch 记录的活动,没有重叠事件的字段为 `None`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityContext {
+    /// 活动窗口的应用名与标题,来自 `currentwindow` bucket
+    pub app: Option<String>,
+    pub title: Option<String>,
+    /// 活动窗口的分类,来自 aw-server 的分类规则,例如 `["Work", "Programming"]`
+    pub category: Vec<String>,
+    /// 浏览器当前标签页的网址,只在其标题出现在活动窗口标题中时提供
+    pub url: Option<String>,
+    /// 来自 `afkstatus` bucket
+    pub afk: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screenshot_stream() {
+        assert_eq!(screenshot_stream("window_firefox_42"), "window");
+        assert_eq!(
+            screenshot_stream("target_dashboard_12345"),
+            "target_dashboard"
+        );
+        assert_eq!(
+            screenshot_stream("DP-1_1920_1080_0_0@clock"),
+            "DP-1_1920_1080_0_0@clock"
+        );
+        assert_eq!(screenshot_stream("desktop"), "desktop");
+    }
 }
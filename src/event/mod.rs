@@ -1,3 +1,7 @@
 pub mod capture_result;
+pub mod window_activity;
 
-pub use capture_result::CaptureResult;
+pub use capture_result::{
+    ActivityContext, CaptureResult, ChangedArea, CursorPosition, screenshot_id, screenshot_stream,
+};
+pub use window_activity::WindowActivity;
@@ -0,0 +1,15 @@
+use chrono::{DateTime, Utc};
+
+/// 窗口任务每次检查时看到的焦点窗口
+///
+/// 无论是否保存截图都会发布,用于生成 `currentwindow` 心跳
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowActivity {
+    pub timestamp: DateTime<Utc>,
+    pub app: String,
+    pub title: String,
+    /// 该窗口最近一次保存的截图ID,见 `CaptureResult::screenshot_id`
+    pub screenshot: Option<String>,
+    /// 窗口任务的检查间隔(毫秒),用于计算心跳的 pulsetime
+    pub interval: u64,
+}
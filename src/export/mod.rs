@@ -0,0 +1,3 @@
+pub mod timelapse;
+
+pub use timelapse::*;
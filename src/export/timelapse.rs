@@ -0,0 +1,412 @@
+use ab_glyph::{FontVec, PxScale};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{Delay, DynamicImage, Frame, ImageResult, Rgba, RgbaImage};
+use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut, text_size};
+use imageproc::rect::Rect;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::event::screenshot_stream;
+use crate::storage::{CaptureMetadata, IndexEntry, Storage, StorageIndex, image_key, metadata_key};
+
+/// 未指定字体时依次尝试的系统字体,CJK 字体优先以显示中文应用名
+const FONT_CANDIDATES: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/System/Library/Fonts/PingFang.ttc",
+    "C:\\Windows\\Fonts\\msyh.ttc",
+];
+
+/// 预读的截图数,读取 S3 时与编码并行
+const PREFETCH: usize = 8;
+
+/// MJPEG 每帧的 JPEG 质量
+const JPEG_QUALITY: u8 = 85;
+
+/// 延时动画的输出格式,由输出文件的扩展名决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelapseFormat {
+    /// 循环播放的 GIF 动画
+    Gif,
+    /// 逐帧拼接的 JPEG 流,可用 ffplay、VLC 等直接播放
+    Mjpeg,
+}
+
+impl TimelapseFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("gif") => Ok(Self::Gif),
+            Some("mjpeg" | "mjpg") => Ok(Self::Mjpeg),
+            _ => bail!(
+                "Unsupported timelapse output {}, use .gif or .mjpeg",
+                path.display()
+            ),
+        }
+    }
+}
+
+pub struct TimelapseOptions {
+    /// 输出宽度,高度按首帧的宽高比计算
+    pub width: u32,
+    /// 每秒帧数
+    pub fps: u32,
+    /// 叠加截图时间与应用名所用的字体,`None` 时不叠加
+    pub overlay: Option<FontVec>,
+}
+
+/// 一帧的原始数据
+struct SourceFrame {
+    entry: IndexEntry,
+    image: Vec<u8>,
+    metadata: Option<CaptureMetadata>,
+}
+
+/// 选出 `[start, end)` 内 `source` 的截图,按时间排序
+///
+/// `source` 可以是来源ID,也可以是截图流(例如 `window` 表示所有焦点窗口截图)。
+/// 超过 `max_frames`(不为 0)时均匀抽取
+pub fn select_frames(
+    index: &StorageIndex,
+    source: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    max_frames: usize,
+) -> Vec<IndexEntry> {
+    let entries: Vec<&IndexEntry> = index
+        .range(start, end)
+        .into_iter()
+        .filter(|entry| entry.source == source || screenshot_stream(&entry.source) == source)
+        .collect();
+    if max_frames == 0 || entries.len() <= max_frames {
+        return entries.into_iter().cloned().collect();
+    }
+    (0..max_frames)
+        .map(|i| entries[i * entries.len() / max_frames].clone())
+        .collect()
+}
+
+/// 读取截图并写入延时动画,返回写入的帧数
+///
+/// 尺寸不同的截图等比缩放后居中放在统一大小的画布上,无法读取或解码的截图跳过
+pub async fn write_timelapse(
+    storage: &Storage,
+    entries: Vec<IndexEntry>,
+    options: TimelapseOptions,
+    output: &Path,
+) -> Result<usize> {
+    let format = TimelapseFormat::from_path(output)?;
+    let overlay = options.overlay.is_some();
+    let (tx, rx) = mpsc::channel(PREFETCH);
+    let output = output.to_path_buf();
+    let writer = tokio::task::spawn_blocking(move || write_frames(rx, format, options, output));
+
+    for entry in entries {
+        let image = match storage.get(&image_key(&entry.id)).await {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Skipping {}: {:#}", entry.id, e);
+                continue;
+            }
+        };
+        // 只有叠加文字时才需要元数据中的应用名
+        let metadata = if overlay {
+            match storage.get(&metadata_key(&entry.id)).await {
+                Ok(content) => serde_json::from_slice(&content).ok(),
+                Err(e) => {
+                    debug!("No metadata for {}: {:#}", entry.id, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let frame = SourceFrame {
+            entry,
+            image,
+            metadata,
+        };
+        // 编码失败时接收端已关闭,错误由 writer 返回
+        if tx.send(frame).await.is_err() {
+            break;
+        }
+    }
+    drop(tx);
+    writer.await?
+}
+
+fn write_frames(
+    mut rx: mpsc::Receiver<SourceFrame>,
+    format: TimelapseFormat,
+    options: TimelapseOptions,
+    output: PathBuf,
+) -> Result<usize> {
+    let mut size = None;
+    // 首帧解码成功后才创建输出文件,截图全部无法读取时不留下无效的文件
+    let first = next_frame(&mut rx, &options, &mut size)
+        .ok_or_else(|| anyhow!("None of the selected screenshots could be read"))?;
+    let file =
+        File::create(&output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut writer = BufWriter::new(file);
+    let frames = std::iter::once(first).chain(std::iter::from_fn(|| {
+        next_frame(&mut rx, &options, &mut size)
+    }));
+    let count = match format {
+        TimelapseFormat::Gif => {
+            let mut encoder = GifEncoder::new_with_speed(&mut writer, 10);
+            encoder.set_repeat(Repeat::Infinite)?;
+            let delay = Delay::from_numer_denom_ms(1000, options.fps.max(1));
+            encode_frames(frames, |canvas| {
+                encoder.encode_frame(Frame::from_parts(canvas, 0, 0, delay))
+            })?
+        }
+        TimelapseFormat::Mjpeg => encode_frames(frames, |canvas| {
+            DynamicImage::ImageRgba8(canvas)
+                .to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut writer, JPEG_QUALITY))
+        })?,
+    };
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(count)
+}
+
+/// 把每帧交给 `encode`,返回帧数
+fn encode_frames(
+    frames: impl Iterator<Item = (String, RgbaImage)>,
+    mut encode: impl FnMut(RgbaImage) -> ImageResult<()>,
+) -> Result<usize> {
+    let mut count = 0;
+    for (id, canvas) in frames {
+        encode(canvas).with_context(|| format!("Failed to encode frame {}", id))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// 取出下一张能解码的截图,缩放并叠加文字,返回截图ID与画布
+///
+/// `size` 为输出尺寸,由首帧确定
+fn next_frame(
+    rx: &mut mpsc::Receiver<SourceFrame>,
+    options: &TimelapseOptions,
+    size: &mut Option<(u32, u32)>,
+) -> Option<(String, RgbaImage)> {
+    while let Some(frame) = rx.blocking_recv() {
+        let image = match image::load_from_memory(&frame.image) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                warn!("Skipping {}: {}", frame.entry.id, e);
+                continue;
+            }
+        };
+        let (width, height) = *size.get_or_insert_with(|| {
+            let (width, height) = image.dimensions();
+            let scaled = (options.width as f64 * height as f64 / width as f64).round() as u32;
+            (options.width.max(1), scaled.max(1))
+        });
+        let mut canvas = fit(&image, width, height);
+        if let Some(font) = &options.overlay {
+            let app = frame
+                .metadata
+                .as_ref()
+                .and_then(|m| m.activity.as_ref()?.app.as_deref());
+            let time = frame.entry.timestamp.with_timezone(&Local);
+            let text = match app {
+                Some(app) => format!("{}  {}", time.format("%Y-%m-%d %H:%M:%S"), app),
+                None => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            };
+            draw_caption(&mut canvas, font, &text);
+        }
+        return Some((frame.entry.id, canvas));
+    }
+    None
+}
+
+/// 等比缩放后居中放在 `width`×`height` 的黑色画布上
+fn fit(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    if image.dimensions() == (width, height) {
+        return image.clone();
+    }
+    let scale = (width as f64 / image.width() as f64).min(height as f64 / image.height() as f64);
+    let scaled_width = ((image.width() as f64 * scale).round() as u32).clamp(1, width);
+    let scaled_height = ((image.height() as f64 * scale).round() as u32).clamp(1, height);
+    let scaled = imageops::resize(image, scaled_width, scaled_height, FilterType::Triangle);
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    imageops::overlay(
+        &mut canvas,
+        &scaled,
+        ((width - scaled_width) / 2) as i64,
+        ((height - scaled_height) / 2) as i64,
+    );
+    canvas
+}
+
+/// 在左下角绘制带底色的文字
+fn draw_caption(canvas: &mut RgbaImage, font: &FontVec, text: &str) {
+    let scale = PxScale::from((canvas.height() as f32 / 24.0).max(12.0));
+    let (text_width, text_height) = text_size(scale, font, text);
+    let padding = (scale.y / 4.0) as u32;
+    let box_width = text_width + padding * 2;
+    let box_height = text_height + padding * 2;
+    // 画布放不下文字时不绘制
+    let Some(y) = canvas
+        .height()
+        .checked_sub(box_height)
+        .and_then(|y| y.checked_sub(padding))
+    else {
+        return;
+    };
+    let y = y as i32;
+    draw_filled_rect_mut(
+        canvas,
+        Rect::at(padding as i32, y).of_size(box_width.max(1), box_height.max(1)),
+        Rgba([0, 0, 0, 255]),
+    );
+    draw_text_mut(
+        canvas,
+        Rgba([255, 255, 255, 255]),
+        (padding * 2) as i32,
+        y + padding as i32,
+        scale,
+        font,
+        text,
+    );
+}
+
+/// 加载叠加文字的字体,未指定时使用第一个存在的系统字体
+pub fn load_font(path: Option<&Path>) -> Result<FontVec> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => FONT_CANDIDATES
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.is_file())
+            .ok_or_else(|| anyhow!("No system font found for the overlay, pass --font"))?,
+    };
+    let data =
+        std::fs::read(&path).with_context(|| format!("Failed to read font {}", path.display()))?;
+    FontVec::try_from_vec_and_index(data, 0)
+        .with_context(|| format!("Invalid font {}", path.display()))
+}
+
+/// 解析本地时间,接受 RFC 3339、`YYYY-MM-DD HH:MM[:SS]` 与 `YYYY-MM-DD`(当天零点)
+pub fn parse_local_time(input: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.to_utc());
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| anyhow!("Invalid time {:?}, expected YYYY-MM-DD [HH:MM[:SS]]", input))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.to_utc())
+        .ok_or_else(|| anyhow!("Time {:?} does not exist in the local time zone", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::CaptureResult;
+    use crate::storage::CaptureStore;
+    use image::AnimationDecoder;
+    use image::codecs::gif::GifDecoder;
+    use std::io::BufReader;
+
+    #[tokio::test]
+    async fn test_exports_gif_timelapse() {
+        let root = std::env::temp_dir().join(format!(
+            "aw-watcher-screenshot-timelapse-{}",
+            std::process::id()
+        ));
+        let storage = Storage::local(&root);
+        let store = CaptureStore::new(vec![storage.clone()]);
+        let start: DateTime<Utc> = "2024-06-01T12:00:00Z".parse().unwrap();
+        // 中途分辨率变化的显示器截图,以及另一来源的截图
+        for (i, (source, width, height)) in [
+            ("DP-1", 64, 48),
+            ("desktop", 64, 48),
+            ("DP-1", 64, 48),
+            ("DP-1", 100, 100),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let at = start + chrono::Duration::seconds(i as i64 * 30);
+            let image = RgbaImage::from_pixel(width, height, Rgba([200, 100, 50, 255]));
+            store
+                .save(&CaptureResult::new(source.to_string(), image, at))
+                .await
+                .unwrap();
+        }
+
+        let index = StorageIndex::load(&storage).await.unwrap();
+        let end = start + chrono::Duration::hours(1);
+        assert_eq!(select_frames(&index, "DP-1", start, end, 0).len(), 3);
+        let entries = select_frames(&index, "DP-1", start, end, 2);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].id, "DP-1_20240601_120100.000");
+        let entries = select_frames(&index, "DP-1", start, end, 0);
+
+        let output = root.join("timelapse.gif");
+        let options = TimelapseOptions {
+            width: 32,
+            fps: 10,
+            overlay: None,
+        };
+        let count = write_timelapse(&storage, entries, options, &output)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+
+        let decoder = GifDecoder::new(BufReader::new(File::open(&output).unwrap())).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 3);
+        for frame in &frames {
+            assert_eq!(frame.buffer().dimensions(), (32, 24));
+        }
+        // 正方形的末帧左右留黑边
+        let last = frames[2].buffer();
+        assert_eq!(last.get_pixel(0, 12)[0], 0);
+        assert!(last.get_pixel(16, 12)[0] > 150);
+
+        // 截图全部无法读取时返回错误,不创建输出文件
+        let missing = IndexEntry::from_key("DP-1_20240601_130000.000.png").unwrap();
+        let output = root.join("missing.gif");
+        let options = TimelapseOptions {
+            width: 32,
+            fps: 10,
+            overlay: None,
+        };
+        assert!(
+            write_timelapse(&storage, vec![missing], options, &output)
+                .await
+                .is_err()
+        );
+        assert!(!output.exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,7 +1,12 @@
+mod activitywatch;
 mod capture;
 mod cli;
 mod config;
+mod control;
 mod event;
+mod export;
+mod serve;
+mod storage;
 
 use anyhow::Result;
 
@@ -0,0 +1,202 @@
+//! 查看器使用的最小 HTTP/1.1 实现
+//!
+//! 只处理 GET 请求,每个连接处理一个请求后关闭
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// 请求头的长度上限
+const MAX_HEAD: usize = 16 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: String,
+    /// 已做百分号解码的路径
+    pub path: String,
+    pub query: HashMap<String, String>,
+}
+
+impl HttpRequest {
+    /// 解析请求头,`head` 不含结尾的空行
+    pub fn parse(head: &str) -> Result<Self> {
+        let mut request_line = head
+            .lines()
+            .next()
+            .ok_or_else(|| anyhow!("Empty request"))?
+            .split_whitespace();
+        let method = request_line
+            .next()
+            .ok_or_else(|| anyhow!("Missing method"))?;
+        let target = request_line
+            .next()
+            .ok_or_else(|| anyhow!("Missing request target"))?;
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                // 查询参数中的 `+` 表示空格
+                let pair = pair.replace('+', " ");
+                let (key, value) = pair.split_once('=').unwrap_or((&pair, ""));
+                (percent_decode(key), percent_decode(value))
+            })
+            .collect();
+        Ok(Self {
+            method: method.to_string(),
+            path: percent_decode(path),
+            query,
+        })
+    }
+
+    /// 非空的查询参数
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.query
+            .get(key)
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+    /// 截图内容不会变化,允许浏览器长期缓存
+    pub immutable: bool,
+}
+
+impl HttpResponse {
+    pub fn ok(content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body: body.into(),
+            immutable: false,
+        }
+    }
+
+    pub fn json(value: &impl Serialize) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Self::ok("application/json", body),
+            Err(e) => Self::error(500, e.to_string()),
+        }
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: message.into().into_bytes(),
+            immutable: false,
+        }
+    }
+
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            500 => "Internal Server Error",
+            _ => "Error",
+        }
+    }
+}
+
+/// 读取一个请求,对端未发送完整请求头就关闭连接时返回 `None`
+pub async fn read_request(stream: &mut TcpStream) -> Result<Option<HttpRequest>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buffer[..end]);
+            return HttpRequest::parse(&head).map(Some);
+        }
+        if buffer.len() > MAX_HEAD {
+            return Err(anyhow!("Request head too large"));
+        }
+    }
+}
+
+pub async fn write_response(stream: &mut TcpStream, response: &HttpResponse) -> Result<()> {
+    let cache_control = if response.immutable {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len(),
+        cache_control
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// 解码 `%XX`,无效的转义原样保留
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| Some(hex(*bytes.get(i + 1)?)? << 4 | hex(*bytes.get(i + 2)?)?))
+            .flatten();
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            None => decoded.push(bytes[i]),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|d| d as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = HttpRequest::parse(
+            "GET /image/Generic%20PnP%20Monitor_1920_1080_0_0_20240601_120000.000?date=2024-06-01&app=&q=a+b HTTP/1.1\r\nHost: localhost",
+        )
+        .unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(
+            request.path,
+            "/image/Generic PnP Monitor_1920_1080_0_0_20240601_120000.000"
+        );
+        assert_eq!(request.param("date"), Some("2024-06-01"));
+        assert_eq!(request.param("app"), None);
+        assert_eq!(request.param("q"), Some("a b"));
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%E4%BD%A0%zz"), "你%zz");
+    }
+}
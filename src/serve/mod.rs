@@ -0,0 +1,459 @@
+pub mod http;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use image::ImageFormat;
+use lru::LruCache;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::config::ThumbnailFormat;
+use crate::event::ActivityContext;
+use crate::serve::http::{HttpRequest, HttpResponse, read_request, write_response};
+use crate::storage::{
+    CaptureMetadata, IndexEntry, Storage, StorageIndex, encode_thumbnail, image_key, is_not_found,
+    metadata_key,
+};
+
+/// 查看器页面
+const PAGE: &str = include_str!("viewer.html");
+
+/// 索引的有效期,过期后重新列出存储以发现新截图
+const INDEX_TTL: Duration = Duration::from_secs(10);
+
+/// 没有保存缩略图时即时生成的缩略图的长边
+const THUMBNAIL_SIZE: u32 = 320;
+
+/// 缓存的元数据与缩略图数量
+const METADATA_CACHE: usize = 20_000;
+const THUMBNAIL_CACHE: usize = 1_000;
+
+/// 同时读取元数据的请求数,避免 S3 上一天的截图同时发出上千个请求
+const METADATA_CONCURRENCY: usize = 16;
+
+/// `/api/captures` 中的一张截图
+#[derive(Debug, Clone, Serialize)]
+struct CaptureSummary {
+    #[serde(flatten)]
+    entry: IndexEntry,
+    /// 没有元数据文件(早期保存的截图)时为 `None`
+    activity: Option<ActivityContext>,
+}
+
+#[derive(Debug, Serialize)]
+struct DaySummary {
+    date: NaiveDate,
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct CaptureList {
+    date: NaiveDate,
+    /// 当天的全部来源与应用,用于筛选
+    sources: Vec<String>,
+    apps: Vec<String>,
+    captures: Vec<CaptureSummary>,
+}
+
+/// 缩略图的内容类型与数据
+type Thumbnail = (&'static str, Arc<Vec<u8>>);
+
+struct ViewerState {
+    storage: Storage,
+    index: tokio::sync::Mutex<Option<(Instant, Arc<StorageIndex>)>>,
+    /// 只缓存读取成功的元数据,不存在或读取失败时下次重新读取
+    metadata: Mutex<LruCache<String, Arc<CaptureMetadata>>>,
+    thumbnails: Mutex<LruCache<String, Thumbnail>>,
+}
+
+/// 本地网页查看器,按日期与来源浏览存储中的截图
+pub struct Viewer {
+    listener: TcpListener,
+    state: Arc<ViewerState>,
+}
+
+impl Viewer {
+    pub async fn bind(addr: &str, storage: Storage) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to listen on {}", addr))?;
+        let state = ViewerState {
+            storage,
+            index: tokio::sync::Mutex::new(None),
+            metadata: Mutex::new(LruCache::new(NonZeroUsize::new(METADATA_CACHE).unwrap())),
+            thumbnails: Mutex::new(LruCache::new(NonZeroUsize::new(THUMBNAIL_CACHE).unwrap())),
+        };
+        Ok(Self {
+            listener,
+            state: Arc::new(state),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// 处理请求,直到收到取消信号
+    pub async fn run(self, cancel_token: CancellationToken) {
+        info!("Viewer serving {}", self.state.storage);
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let state = self.state.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = handle_connection(stream, &state).await {
+                                debug!("Viewer connection error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Failed to accept viewer connection: {}", e),
+                },
+                _ = cancel_token.cancelled() => {
+                    info!("Viewer received cancellation signal");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: &Arc<ViewerState>) -> Result<()> {
+    let Some(request) = read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    debug!("Viewer request: {} {}", request.method, request.path);
+    let response = if request.method == "GET" {
+        state.handle(&request).await
+    } else {
+        HttpResponse::error(405, "Only GET is supported")
+    };
+    write_response(&mut stream, &response).await
+}
+
+impl ViewerState {
+    async fn handle(self: &Arc<Self>, request: &HttpRequest) -> HttpResponse {
+        let path = request.path.as_str();
+        // 截图ID只能是索引能解析的文件名,其余请求不会读取存储
+        let id = ["/api/capture/", "/image/", "/thumb/"]
+            .iter()
+            .find_map(|prefix| path.strip_prefix(prefix));
+        if let Some(id) = id
+            && (id.contains(['/', '\\']) || IndexEntry::from_key(&image_key(id)).is_none())
+        {
+            return HttpResponse::error(404, format!("Invalid screenshot id {}", id));
+        }
+
+        let result = if path == "/" {
+            Ok(HttpResponse::ok("text/html; charset=utf-8", PAGE))
+        } else if path == "/api/days" {
+            self.days().await
+        } else if path == "/api/captures" {
+            self.captures(request).await
+        } else if let Some(id) = path.strip_prefix("/api/capture/") {
+            self.capture(id).await
+        } else if let Some(id) = path.strip_prefix("/image/") {
+            self.storage
+                .get(&image_key(id))
+                .await
+                .map(|image| HttpResponse::ok("image/png", image).immutable())
+        } else if let Some(id) = path.strip_prefix("/thumb/") {
+            self.thumbnail(id).await.map(|(content_type, thumb)| {
+                HttpResponse::ok(content_type, thumb.as_slice()).immutable()
+            })
+        } else {
+            return HttpResponse::error(404, "Not found");
+        };
+
+        result.unwrap_or_else(|e| {
+            if is_not_found(&e) {
+                debug!("Viewer request {} not found: {:#}", path, e);
+                HttpResponse::error(404, format!("{:#}", e))
+            } else {
+                warn!("Viewer request {} failed: {:#}", path, e);
+                HttpResponse::error(500, format!("{:#}", e))
+            }
+        })
+    }
+
+    /// 返回索引,过期后重新列出存储
+    async fn index(&self) -> Result<Arc<StorageIndex>> {
+        let mut cached = self.index.lock().await;
+        if let Some((at, index)) = cached.as_ref()
+            && at.elapsed() < INDEX_TTL
+        {
+            return Ok(index.clone());
+        }
+        let index = Arc::new(StorageIndex::load(&self.storage).await?);
+        *cached = Some((Instant::now(), index.clone()));
+        Ok(index)
+    }
+
+    async fn days(&self) -> Result<HttpResponse> {
+        let days: Vec<DaySummary> = self
+            .index()
+            .await?
+            .days()
+            .into_iter()
+            .map(|(date, count)| DaySummary { date, count })
+            .collect();
+        Ok(HttpResponse::json(&days))
+    }
+
+    /// 某一天的截图,可按来源与应用名(不区分大小写的子串)筛选
+    async fn captures(self: &Arc<Self>, request: &HttpRequest) -> Result<HttpResponse> {
+        let index = self.index().await?;
+        let date = match request.param("date") {
+            Some(date) => match date.parse::<NaiveDate>() {
+                Ok(date) => date,
+                Err(e) => return Ok(HttpResponse::error(400, format!("Invalid date: {}", e))),
+            },
+            None => match index.days().last() {
+                Some((date, _)) => *date,
+                None => chrono::Local::now().date_naive(),
+            },
+        };
+
+        let entries = index.day(date);
+        let metadata = self.load_metadata(entries).await;
+        let mut sources: Vec<String> = entries.iter().map(|e| e.source.clone()).collect();
+        sources.sort();
+        sources.dedup();
+        let mut apps: Vec<String> = metadata
+            .iter()
+            .flatten()
+            .filter_map(|m| m.activity.as_ref()?.app.clone())
+            .collect();
+        apps.sort();
+        apps.dedup();
+
+        let source = request.param("source");
+        let app = request.param("app").map(str::to_lowercase);
+        let captures = entries
+            .iter()
+            .zip(metadata)
+            .filter(|(entry, _)| source.is_none_or(|s| entry.source == s))
+            .map(|(entry, metadata)| CaptureSummary {
+                entry: entry.clone(),
+                activity: metadata.and_then(|m| m.activity.clone()),
+            })
+            .filter(|capture| {
+                app.as_ref().is_none_or(|app| {
+                    capture
+                        .activity
+                        .as_ref()
+                        .and_then(|a| a.app.as_ref())
+                        .is_some_and(|name| name.to_lowercase().contains(app))
+                })
+            })
+            .collect();
+
+        Ok(HttpResponse::json(&CaptureList {
+            date,
+            sources,
+            apps,
+            captures,
+        }))
+    }
+
+    /// 单张截图的完整元数据,没有元数据文件时只返回索引中的信息
+    async fn capture(&self, id: &str) -> Result<HttpResponse> {
+        let entry = IndexEntry::from_key(&image_key(id))
+            .with_context(|| format!("Invalid screenshot id {}", id))?;
+        Ok(match self.metadata(&entry).await {
+            Some(metadata) => HttpResponse::json(metadata.as_ref()),
+            None => HttpResponse::json(&entry),
+        })
+    }
+
+    async fn metadata(&self, entry: &IndexEntry) -> Option<Arc<CaptureMetadata>> {
+        if let Some(cached) = self.metadata.lock().unwrap().get(&entry.id) {
+            return Some(cached.clone());
+        }
+        let metadata: Arc<CaptureMetadata> = match self.storage.get(&metadata_key(&entry.id)).await
+        {
+            Ok(content) => serde_json::from_slice(&content)
+                .inspect_err(|e| warn!("Invalid metadata for {}: {}", entry.id, e))
+                .ok()
+                .map(Arc::new)?,
+            Err(e) => {
+                debug!("No metadata for {}: {:#}", entry.id, e);
+                return None;
+            }
+        };
+        self.metadata
+            .lock()
+            .unwrap()
+            .put(entry.id.clone(), metadata.clone());
+        Some(metadata)
+    }
+
+    /// 并发读取多张截图的元数据,结果与 `entries` 一一对应
+    async fn load_metadata(
+        self: &Arc<Self>,
+        entries: &[IndexEntry],
+    ) -> Vec<Option<Arc<CaptureMetadata>>> {
+        let permits = Arc::new(Semaphore::new(METADATA_CONCURRENCY));
+        let mut tasks = JoinSet::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let state = self.clone();
+            let permits = permits.clone();
+            let entry = entry.clone();
+            tasks.spawn(async move {
+                let _permit = permits.acquire().await;
+                (i, state.metadata(&entry).await)
+            });
+        }
+
+        let mut metadata = vec![None; entries.len()];
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok((i, loaded)) = joined {
+                metadata[i] = loaded;
+            }
+        }
+        metadata
+    }
+
+    /// 优先读取保存的缩略图,没有时读取原图并缩小为 JPEG
+    async fn thumbnail(&self, id: &str) -> Result<Thumbnail> {
+        if let Some(cached) = self.thumbnails.lock().unwrap().get(id) {
+            return Ok(cached.clone());
+        }
+        let stored = match IndexEntry::from_key(&image_key(id)) {
+            Some(entry) => self.stored_thumbnail(&entry).await,
+            None => None,
+        };
+        let thumb = match stored {
+            Some(thumb) => thumb,
+            None => {
+                let original = self.storage.get(&image_key(id)).await?;
+                let encoded = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+                    let image = image::load_from_memory(&original)?.to_rgba8();
+                    encode_thumbnail(&image, THUMBNAIL_SIZE, ThumbnailFormat::Jpeg)
+                })
+                .await??;
+                (ThumbnailFormat::Jpeg.content_type(), Arc::new(encoded))
+            }
+        };
+        self.thumbnails
+            .lock()
+            .unwrap()
+            .put(id.to_string(), thumb.clone());
+        Ok(thumb)
+    }
+
+    /// 元数据中记录的缩略图
+    async fn stored_thumbnail(&self, entry: &IndexEntry) -> Option<Thumbnail> {
+        let key = self.metadata(entry).await?.thumbnail.clone()?;
+        let content_type = ImageFormat::from_path(&key).ok()?.to_mime_type();
+        match self.storage.get(&key).await {
+            Ok(content) => Some((content_type, Arc::new(content))),
+            Err(e) => {
+                debug!("Stored thumbnail {} unavailable: {:#}", key, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ThumbnailConfig;
+    use crate::event::CaptureResult;
+    use crate::storage::CaptureStore;
+    use image::RgbaImage;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn get(addr: SocketAddr, target: &str) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).as_bytes())
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&response[..end]).to_string();
+        (head, response[end + 4..].to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_viewer_lists_and_serves_captures() {
+        let root = std::env::temp_dir().join(format!(
+            "aw-watcher-screenshot-viewer-{}",
+            std::process::id()
+        ));
+        let storage = Storage::local(&root);
+        let at = "2024-06-01T12:00:00.250Z".parse().unwrap();
+        let activity = ActivityContext {
+            app: Some("firefox".to_string()),
+            ..Default::default()
+        };
+        // 显示器截图保存了 PNG 缩略图,桌面截图没有,由查看器即时生成
+        let store = CaptureStore::new(vec![storage.clone()]);
+        let thumbnail_store =
+            CaptureStore::new(vec![storage.clone()]).with_thumbnail(ThumbnailConfig {
+                enable: true,
+                max_size: 160,
+                format: ThumbnailFormat::Png,
+            });
+        for (source, activity, store) in [
+            ("DP-1_1920_1080_0_0", Some(activity), &thumbnail_store),
+            ("desktop", None, &store),
+        ] {
+            let result = CaptureResult::new(source.to_string(), RgbaImage::new(640, 480), at);
+            let mut metadata = store.save(&result).await.unwrap().metadata;
+            // 活动标注在保存之后补写
+            metadata.activity = activity;
+            store.update_metadata(&metadata).await.unwrap();
+        }
+
+        let viewer = Viewer::bind("127.0.0.1:0", storage).await.unwrap();
+        let addr = viewer.local_addr().unwrap();
+        let cancel_token = CancellationToken::new();
+        tokio::spawn(viewer.run(cancel_token.clone()));
+
+        let date = IndexEntry::from_key("desktop_20240601_120000.250.png")
+            .unwrap()
+            .local_date();
+        let (head, body) = get(addr, &format!("/api/captures?date={}&app=FIRE", date)).await;
+        assert!(head.starts_with("HTTP/1.1 200"));
+        let list: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            list["sources"],
+            serde_json::json!(["DP-1_1920_1080_0_0", "desktop"])
+        );
+        assert_eq!(list["apps"], serde_json::json!(["firefox"]));
+        assert_eq!(list["captures"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            list["captures"][0]["id"],
+            "DP-1_1920_1080_0_0_20240601_120000.250"
+        );
+
+        let (head, body) = get(addr, "/thumb/desktop_20240601_120000.250").await;
+        assert!(head.contains("image/jpeg"));
+        let thumb = image::load_from_memory(&body).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (320, 240));
+        let (head, body) = get(addr, "/thumb/DP-1_1920_1080_0_0_20240601_120000.250").await;
+        assert!(head.contains("image/png"));
+        let thumb = image::load_from_memory(&body).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (160, 120));
+
+        let (head, _) = get(addr, "/image/..%2F..%2Fetc%2Fpasswd").await;
+        assert!(head.starts_with("HTTP/1.1 404"));
+        let (head, _) = get(addr, "/api/capture/nonsense").await;
+        assert!(head.starts_with("HTTP/1.1 404"));
+
+        cancel_token.cancel();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
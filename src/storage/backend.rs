@@ -0,0 +1,183 @@
+use anyhow::{Context, Result, anyhow};
+use s3::Bucket;
+use s3::error::S3Error;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::S3Config;
+
+/// 截图存储后端
+///
+/// 对象以 key(例如 `{screenshot_id}.png`)寻址,本地存储中 key 为相对存储目录的路径
+#[derive(Clone)]
+pub enum Storage {
+    Local(PathBuf),
+    S3(Box<Bucket>),
+}
+
+impl Storage {
+    pub fn local(path: impl Into<PathBuf>) -> Self {
+        Self::Local(path.into())
+    }
+
+    /// 按配置解析凭证并连接 S3 存储桶
+    pub fn s3(config: &S3Config) -> Result<Self> {
+        let credentials = config.credentials()?;
+        Ok(Self::S3(config.bucket(&credentials)?))
+    }
+
+    /// 写入对象,已存在时覆盖
+    pub async fn put(&self, key: &str, content: &[u8], content_type: &str) -> Result<()> {
+        match self {
+            Self::Local(root) => {
+                let path = local_path(root, key)?;
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                tokio::fs::write(&path, content)
+                    .await
+                    .with_context(|| format!("Failed to write {}", path.display()))
+            }
+            Self::S3(bucket) => {
+                bucket
+                    .put_object_with_content_type(key, content, content_type)
+                    .await
+                    .with_context(|| format!("Failed to upload {} to S3", key))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// 读取对象
+    pub async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Local(root) => {
+                let path = local_path(root, key)?;
+                tokio::fs::read(&path)
+                    .await
+                    .with_context(|| format!("Failed to read {}", path.display()))
+            }
+            Self::S3(bucket) => Ok(bucket
+                .get_object(key)
+                .await
+                .with_context(|| format!("Failed to download {} from S3", key))?
+                .to_vec()),
+        }
+    }
+
+    /// 列出存储根目录下以 `prefix` 开头的 key
+    pub async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        match self {
+            Self::Local(root) => {
+                let mut keys = Vec::new();
+                let mut entries = match tokio::fs::read_dir(root).await {
+                    Ok(entries) => entries,
+                    // 尚未保存过截图
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(keys),
+                    Err(e) => return Err(e).context("Failed to list local storage"),
+                };
+                while let Some(entry) = entries.next_entry().await? {
+                    if let Some(name) = entry.file_name().to_str()
+                        && name.starts_with(prefix)
+                        && entry.file_type().await?.is_file()
+                    {
+                        keys.push(name.to_string());
+                    }
+                }
+                Ok(keys)
+            }
+            Self::S3(bucket) => {
+                let pages = bucket
+                    .list(prefix.to_string(), Some("/".to_string()))
+                    .await
+                    .context("Failed to list S3 bucket")?;
+                Ok(pages
+                    .into_iter()
+                    .flat_map(|page| page.contents)
+                    .map(|object| object.key)
+                    .collect())
+            }
+        }
+    }
+
+    /// 对象在存储中的位置,用于日志与输出
+    pub fn location(&self, key: &str) -> String {
+        match self {
+            Self::Local(root) => root.join(key).display().to_string(),
+            Self::S3(bucket) => format!("s3://{}/{}", bucket.name, key),
+        }
+    }
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(root) => write!(f, "local {}", root.display()),
+            Self::S3(bucket) => write!(f, "s3://{}", bucket.name),
+        }
+    }
+}
+
+/// 错误是否由对象不存在引起,区分本地文件不存在与 S3 返回 404
+pub fn is_not_found(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+            || matches!(
+                cause.downcast_ref::<S3Error>(),
+                Some(S3Error::HttpFailWithBody(404, _))
+            )
+    })
+}
+
+/// key 只能指向存储目录内部,拒绝绝对路径与 `..`
+fn local_path(root: &Path, key: &str) -> Result<PathBuf> {
+    let relative = Path::new(key);
+    if key.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(anyhow!("Invalid storage key {:?}", key));
+    }
+    Ok(root.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_round_trip() {
+        let root = std::env::temp_dir().join(format!(
+            "aw-watcher-screenshot-storage-{}",
+            std::process::id()
+        ));
+        let storage = Storage::local(&root);
+
+        storage
+            .put("DP-1_20240601_120000.000.png", b"png", "image/png")
+            .await
+            .unwrap();
+        storage
+            .put("desktop_20240601_120000.000.png", b"png", "image/png")
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get("DP-1_20240601_120000.000.png").await.unwrap(),
+            b"png"
+        );
+        assert_eq!(
+            storage.list("DP-1_").await.unwrap(),
+            vec!["DP-1_20240601_120000.000.png"]
+        );
+
+        let missing = storage.get("DP-1_20240601_130000.000.png").await;
+        assert!(is_not_found(&missing.unwrap_err()));
+        let invalid = storage.get("../etc/passwd").await;
+        assert!(!is_not_found(&invalid.unwrap_err()));
+        assert!(storage.put("/tmp/x", b"", "text/plain").await.is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::ThumbnailFormat;
+use crate::event::{ActivityContext, CaptureResult, ChangedArea, CursorPosition};
+use crate::storage::backend::Storage;
+
+/// 截图文件的扩展名
+pub const IMAGE_EXTENSION: &str = "png";
+
+/// 元数据文件的扩展名
+pub const METADATA_EXTENSION: &str = "json";
+
+/// 与截图同名保存的元数据,`{screenshot_id}.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureMetadata {
+    pub id: String,
+    pub source: String,
+    pub timestamp: DateTime<Utc>,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub similar: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tick: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_area: Option<ChangedArea>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<CursorPosition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity: Option<ActivityContext>,
+    /// 缩略图的 key,未生成缩略图时为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+impl CaptureMetadata {
+    pub fn new(result: &CaptureResult) -> Self {
+        Self {
+            id: result.screenshot_id(),
+            source: result.monitor_id.clone(),
+            timestamp: result.timestamp,
+            width: result.image.width(),
+            height: result.image.height(),
+            similar: result.similar,
+            tick: result.tick,
+            changed_area: result.changed_area,
+            cursor: result.cursor,
+            activity: None,
+            thumbnail: None,
+        }
+    }
+}
+
+/// 截图图像的 key
+pub fn image_key(id: &str) -> String {
+    format!("{}.{}", id, IMAGE_EXTENSION)
+}
+
+/// 截图元数据的 key
+pub fn metadata_key(id: &str) -> String {
+    format!("{}.{}", id, METADATA_EXTENSION)
+}
+
+/// 截图缩略图的 key,`{screenshot_id}.thumb.{ext}`
+pub fn thumbnail_key(id: &str, format: ThumbnailFormat) -> String {
+    format!("{}.thumb.{}", id, format.extension())
+}
+
+/// 索引中的一张截图,由存储中的 key 解析得到
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct IndexEntry {
+    pub timestamp: DateTime<Utc>,
+    pub source: String,
+    pub id: String,
+}
+
+impl IndexEntry {
+    /// 从 `{monitor_id}_{%Y%m%d_%H%M%S%.3f}.png` 解析出来源与截图时间,其他 key 返回 `None`
+    pub fn from_key(key: &str) -> Option<Self> {
+        let id = key.strip_suffix(&format!(".{}", IMAGE_EXTENSION))?;
+        let (rest, time) = id.rsplit_once('_')?;
+        let (source, date) = rest.rsplit_once('_')?;
+        let timestamp =
+            NaiveDateTime::parse_from_str(&format!("{}_{}", date, time), "%Y%m%d_%H%M%S%.3f")
+                .ok()?
+                .and_utc();
+        Some(Self {
+            timestamp,
+            source: source.to_string(),
+            id: id.to_string(),
+        })
+    }
+
+    /// 截图所在的本地日期
+    pub fn local_date(&self) -> NaiveDate {
+        self.timestamp.with_timezone(&Local).date_naive()
+    }
+}
+
+/// 存储中全部截图的索引,按本地日期分组、组内按时间排序
+#[derive(Debug, Default)]
+pub struct StorageIndex {
+    days: BTreeMap<NaiveDate, Vec<IndexEntry>>,
+}
+
+impl StorageIndex {
+    /// 列出存储中的截图建立索引
+    pub async fn load(storage: &Storage) -> Result<Self> {
+        Ok(Self::from_keys(storage.list("").await?))
+    }
+
+    pub fn from_keys(keys: impl IntoIterator<Item = String>) -> Self {
+        let mut days: BTreeMap<NaiveDate, Vec<IndexEntry>> = BTreeMap::new();
+        for entry in keys
+            .into_iter()
+            .filter_map(|key| IndexEntry::from_key(&key))
+        {
+            days.entry(entry.local_date()).or_default().push(entry);
+        }
+        for entries in days.values_mut() {
+            entries.sort();
+        }
+        Self { days }
+    }
+
+    /// 有截图的日期及每天的截图数,按日期升序
+    pub fn days(&self) -> Vec<(NaiveDate, usize)> {
+        self.days
+            .iter()
+            .map(|(day, entries)| (*day, entries.len()))
+            .collect()
+    }
+
+    /// 某一天的截图
+    pub fn day(&self, date: NaiveDate) -> &[IndexEntry] {
+        self.days.get(&date).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// `[start, end)` 内的截图,按时间排序
+    pub fn range(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<&IndexEntry> {
+        if start >= end {
+            return Vec::new();
+        }
+        let first = start.with_timezone(&Local).date_naive();
+        let last = end.with_timezone(&Local).date_naive();
+        self.days
+            .range(first..=last)
+            .flat_map(|(_, entries)| entries)
+            .filter(|entry| entry.timestamp >= start && entry.timestamp < end)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_from_keys() {
+        let index = StorageIndex::from_keys(
+            [
+                "DP-1_1920_1080_0_0_20240601_120001.500.png",
+                "DP-1_1920_1080_0_0_20240601_120001.500.json",
+                "DP-1_1920_1080_0_0_20240601_120001.500.thumb.png",
+                "window_firefox_42_20240601_120000.250.png",
+                "DP-1_1920_1080_0_0@clock_20240601_120000.000.png",
+                "notes.txt",
+            ]
+            .map(String::from),
+        );
+
+        let (day, count) = index.days()[0];
+        assert_eq!(count, 3);
+        let entries = index.day(day);
+        assert_eq!(entries[0].source, "DP-1_1920_1080_0_0@clock");
+        assert_eq!(entries[1].source, "window_firefox_42");
+        assert_eq!(entries[2].id, "DP-1_1920_1080_0_0_20240601_120001.500");
+        assert_eq!(
+            entries[2].timestamp,
+            "2024-06-01T12:00:01.500Z".parse::<DateTime<Utc>>().unwrap()
+        );
+
+        let range = index.range(
+            "2024-06-01T12:00:00.250Z".parse().unwrap(),
+            "2024-06-01T12:00:01.500Z".parse().unwrap(),
+        );
+        assert_eq!(range, vec![&entries[1]]);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod backend;
+pub mod index;
+pub mod store;
+
+pub use backend::*;
+pub use index::*;
+pub use store::*;
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use std::io::Cursor;
+use tracing::{debug, warn};
+
+use crate::capture::utils::thumbnail;
+use crate::config::{StorageConfig, ThumbnailConfig, ThumbnailFormat};
+use crate::event::CaptureResult;
+use crate::storage::backend::Storage;
+use crate::storage::index::{CaptureMetadata, image_key, metadata_key, thumbnail_key};
+
+/// 截图保存流程:编码一次,写入所有启用的存储后端
+///
+/// 每张截图保存为 `{screenshot_id}.png`,旁边是同名的元数据 `{screenshot_id}.json`,
+/// 启用缩略图时还有 `{screenshot_id}.thumb.{ext}`
+#[derive(Clone)]
+pub struct CaptureStore {
+    backends: Vec<Storage>,
+    thumbnail: Option<ThumbnailConfig>,
+}
+
+/// 一次保存的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedCapture {
+    /// 截图在各后端中的位置
+    pub locations: Vec<String>,
+    /// 写入的元数据
+    pub metadata: CaptureMetadata,
+}
+
+impl CaptureStore {
+    pub fn new(backends: Vec<Storage>) -> Self {
+        Self {
+            backends,
+            thumbnail: None,
+        }
+    }
+
+    /// 同时保存缩略图,`config.enable` 为 false 时不生成
+    pub fn with_thumbnail(mut self, config: ThumbnailConfig) -> Self {
+        self.thumbnail = config.enable.then_some(config);
+        self
+    }
+
+    /// 按配置启用本地存储与 S3,两者都启用时同时写入
+    pub fn from_config(config: &StorageConfig) -> Result<Self> {
+        let mut backends = Vec::new();
+        if config.local.enable {
+            backends.push(Storage::local(&config.local.path));
+        }
+        if config.s3.enable {
+            backends.push(Storage::s3(&config.s3)?);
+        }
+        Ok(Self::new(backends).with_thumbnail(config.thumbnail.clone()))
+    }
+
+    /// 保存截图、缩略图与元数据
+    ///
+    /// 任一后端失败时返回错误,已写入其他后端的文件保留;缩略图生成失败时只记录警告,
+    /// 截图照常保存
+    pub async fn save(&self, result: &CaptureResult) -> Result<SavedCapture> {
+        // 编码与缩放都很耗时,放到阻塞线程中进行
+        let source = result.image.clone();
+        let thumbnail_config = self.thumbnail.clone();
+        let (image, thumbnail) = tokio::task::spawn_blocking(move || -> Result<_> {
+            let mut image = Vec::new();
+            source
+                .write_to(&mut Cursor::new(&mut image), ImageFormat::Png)
+                .context("Failed to encode screenshot")?;
+            let thumbnail = thumbnail_config.map(|config| {
+                let encoded = encode_thumbnail(&source, config.max_size, config.format);
+                (config.format, encoded)
+            });
+            Ok((image, thumbnail))
+        })
+        .await??;
+
+        let id = result.screenshot_id();
+        let mut metadata = CaptureMetadata::new(result);
+        let thumbnail = match thumbnail {
+            Some((format, Ok(encoded))) => {
+                let key = thumbnail_key(&id, format);
+                metadata.thumbnail = Some(key.clone());
+                Some((key, format.content_type(), encoded))
+            }
+            Some((_, Err(e))) => {
+                warn!("Failed to create thumbnail for {}: {:#}", id, e);
+                None
+            }
+            None => None,
+        };
+        let encoded_metadata = serde_json::to_vec_pretty(&metadata)?;
+
+        let mut locations = Vec::new();
+        // 索引按截图列出,先写元数据与缩略图,列出截图时它们已经存在
+        for backend in &self.backends {
+            backend
+                .put(&metadata_key(&id), &encoded_metadata, "application/json")
+                .await?;
+            if let Some((key, content_type, encoded)) = &thumbnail {
+                backend.put(key, encoded, content_type).await?;
+            }
+            backend.put(&image_key(&id), &image, "image/png").await?;
+            debug!("Saved {} to {}", id, backend);
+            locations.push(backend.location(&image_key(&id)));
+        }
+        Ok(SavedCapture {
+            locations,
+            metadata,
+        })
+    }
+
+    /// 重新写入元数据,用于保存之后补充的活动标注
+    pub async fn update_metadata(&self, metadata: &CaptureMetadata) -> Result<()> {
+        let encoded = serde_json::to_vec_pretty(metadata)?;
+        for backend in &self.backends {
+            backend
+                .put(&metadata_key(&metadata.id), &encoded, "application/json")
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// 等比缩小到长边不超过 `max_size` 并编码,JPEG 不支持透明通道,先转为 RGB
+pub fn encode_thumbnail(
+    image: &RgbaImage,
+    max_size: u32,
+    format: ThumbnailFormat,
+) -> Result<Vec<u8>> {
+    let small = DynamicImage::ImageRgba8(thumbnail(image, max_size));
+    let small = match format {
+        ThumbnailFormat::Jpeg => DynamicImage::ImageRgb8(small.to_rgb8()),
+        ThumbnailFormat::Png | ThumbnailFormat::Webp => small,
+    };
+    let mut encoded = Vec::new();
+    small
+        .write_to(&mut Cursor::new(&mut encoded), format.image_format())
+        .with_context(|| format!("Failed to encode {} thumbnail", format))?;
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::credentials::Secret;
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_thumbnail_failure_still_saves() {
+        let root = std::env::temp_dir().join(format!(
+            "aw-watcher-screenshot-store-{}",
+            std::process::id()
+        ));
+        // JPEG 的边长不能超过 65535,缩略图编码失败而 PNG 正常
+        let store =
+            CaptureStore::new(vec![Storage::local(&root)]).with_thumbnail(ThumbnailConfig {
+                enable: true,
+                max_size: 100_000,
+                format: ThumbnailFormat::Jpeg,
+            });
+        let result = CaptureResult::new("DP-1".to_string(), RgbaImage::new(70_000, 1), Utc::now());
+
+        let saved = store.save(&result).await.unwrap();
+        assert_eq!(saved.locations.len(), 1);
+        assert_eq!(saved.metadata.thumbnail, None);
+        let id = result.screenshot_id();
+        assert!(root.join(image_key(&id)).exists());
+        assert!(root.join(metadata_key(&id)).exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_s3_only_writes_nothing_locally() {
+        let root = std::env::temp_dir().join(format!(
+            "aw-watcher-screenshot-s3-only-{}",
+            std::process::id()
+        ));
+        let mut config = StorageConfig::default();
+        config.local.enable = false;
+        config.local.path = root.to_string_lossy().to_string();
+        config.s3.enable = true;
+        config.s3.endpoint = "http://127.0.0.1:1".to_string();
+        config.s3.access_key = Secret::new("test");
+        config.s3.secret_key = Secret::new("test");
+
+        let store = CaptureStore::from_config(&config).unwrap();
+        assert_eq!(store.backends.len(), 1);
+        assert!(matches!(store.backends[0], Storage::S3(_)));
+
+        // 上传失败,本地目录不会被创建
+        let result = CaptureResult::new("DP-1".to_string(), RgbaImage::new(1, 1), Utc::now());
+        assert!(store.save(&result).await.is_err());
+        assert!(!root.exists());
+    }
+}